@@ -13,19 +13,62 @@
 // limitations under the License.
 
 use crate::cli::util::get_repository_path;
-use crate::cli::GenerateArgs;
+use crate::cli::{
+    GenerateArgs, OutputDestinationArgs, OutputEncodingArgs, PassphraseArgs, StoreGeneratedArgs,
+};
 use crate::configuration;
-use crate::crypto::pwgen;
-use crate::output::{output_secret, InputEncoding, OutputMethod};
+use crate::crypto::breach::BreachDatabase;
+use crate::crypto::derive::Derivation;
+use crate::crypto::key::digest_fingerprint;
+use crate::crypto::otp::{Otp, OtpKind};
+use crate::crypto::pwgen::{self, GeneratorSettings};
+use crate::crypto::shamir::{self, Share};
+use crate::crypto::strength;
+#[cfg(feature = "wifiqr")]
+use crate::output::encode_for_display;
+use crate::output::encoding::{self, SecretEncoding};
+use crate::output::{
+    output_secret, output_secret_to_command, output_secret_to_file, InputEncoding, OutputMethod,
+};
+use crate::repository::audit;
+use crate::repository::credential::{self, Request};
+use crate::repository::docker::{self, Credentials};
+use crate::repository::env::{format_variable, read_bindings, read_subtree, EnvBinding, EnvFormat};
+use crate::repository::path::Path as RepositoryPath;
 use crate::repository::serde::{export_serialize, import_deserialize};
+use crate::repository::template::Template;
 use crate::repository::Repository;
-use crate::util::{self, multiline_password_prompt, password_prompt};
+use crate::util::{self, editor, multiline_password_prompt, password_prompt};
 use anyhow::{bail, Result};
-use std::fs::File;
+use bdrck::crypto::secret::Secret;
+use rand::RngCore;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::CommandExt;
 use std::path::PathBuf;
+use std::process::Command;
+use std::time::{Duration, SystemTime};
 
 static NEW_PASSWORD_PROMPT: &'static str = "New password: ";
 static MULTILINE_PASSWORD_PROMPT: &'static str = "Enter password data, until 'EOF' is read:";
+static OTP_URI_PROMPT: &str = "otpauth:// URI: ";
+static CURRENT_PASSWORD_PROMPT: &str = "Current master password: ";
+static NEW_MASTER_PASSWORD_PROMPT: &str = "New master password: ";
+static RECOVERY_SHARE_PROMPT: &str = "Recovery share: ";
+
+static GIT_CREDENTIAL_GET: &str = "get";
+static GIT_CREDENTIAL_STORE: &str = "store";
+static GIT_CREDENTIAL_ERASE: &str = "erase";
+
+static DOCKER_CREDENTIAL_GET: &str = "get";
+static DOCKER_CREDENTIAL_STORE: &str = "store";
+static DOCKER_CREDENTIAL_ERASE: &str = "erase";
+static DOCKER_CREDENTIAL_LIST: &str = "list";
+
+/// The length of the random recovery secret which is split into shares.
+const RECOVERY_SECRET_LENGTH: usize = 32;
 
 pub(crate) fn config(key: Option<String>, set: Option<String>) -> Result<()> {
     let _handle = crate::init_with_configuration().unwrap();
@@ -63,20 +106,207 @@ pub(crate) fn init(repository: Option<PathBuf>) -> Result<()> {
     Ok(())
 }
 
-pub(crate) fn addkey(repository: Option<PathBuf>) -> Result<()> {
+pub(crate) fn addkey(
+    repository: Option<PathBuf>,
+    key_file: Option<PathBuf>,
+    label: Option<String>,
+) -> Result<()> {
     let _handle = crate::init_with_configuration().unwrap();
     let repository = get_repository_path(repository)?;
     let mut repository = Repository::new(&repository, false, None)?;
-    repository.add_password_key(None)?;
+    match key_file {
+        None => repository.add_password_key(None, label)?,
+        Some(key_file) => repository.add_key_file_key(&key_file, label)?,
+    };
 
     Ok(())
 }
 
-pub(crate) fn rmkey(repository: Option<PathBuf>) -> Result<()> {
+pub(crate) fn rmkey(
+    repository: Option<PathBuf>,
+    key_file: Option<PathBuf>,
+    label: Option<String>,
+) -> Result<()> {
     let _handle = crate::init_with_configuration().unwrap();
     let repository = get_repository_path(repository)?;
     let mut repository = Repository::new(&repository, false, None)?;
-    repository.remove_password_key(None)?;
+    match (key_file, label) {
+        (Some(key_file), _) => repository.remove_key_file_key(&key_file)?,
+        (None, Some(label)) => repository.remove_key_by_label(&label)?,
+        (None, None) => repository.remove_password_key(None)?,
+    };
+
+    Ok(())
+}
+
+/// Check a new password the user entered. If it's below the configured minimum
+/// strength, or appears in the configured breach database, warn about it and
+/// ask whether to use it anyway.
+fn confirm_password_strength(password: &Secret) -> Result<bool> {
+    let config = configuration::get()?;
+    if let Some(path) = config.breach_db.as_ref() {
+        if let Some(count) = BreachDatabase::open(path)?.lookup(password)? {
+            eprintln!(
+                "WARNING: This password has appeared in data breaches {} times.",
+                count
+            );
+            return Ok(bdrck::cli::continue_confirmation(
+                bdrck::cli::Stream::Stdin,
+                bdrck::cli::Stream::Stderr,
+                "Use it anyway? ",
+            )?);
+        }
+    }
+
+    let estimate = strength::estimate_secret(password);
+    if estimate.score >= config.get_min_password_strength() {
+        return Ok(true);
+    }
+
+    eprintln!(
+        "WARNING: This password is weak (strength {}/{}, ~{:.1} bits). {}",
+        estimate.score,
+        strength::MAX_SCORE,
+        estimate.guesses_log2,
+        estimate.warning().unwrap_or("")
+    );
+    Ok(bdrck::cli::continue_confirmation(
+        bdrck::cli::Stream::Stdin,
+        bdrck::cli::Stream::Stderr,
+        "Use it anyway? ",
+    )?)
+}
+
+pub(crate) fn passwd(repository: Option<PathBuf>) -> Result<()> {
+    let _handle = crate::init_with_configuration().unwrap();
+    let repository = get_repository_path(repository)?;
+    // Prompt for the old password just once, and use it both to open the
+    // repository and to identify the key to replace.
+    let old = password_prompt(CURRENT_PASSWORD_PROMPT, /*confirm=*/ false)?;
+    let mut repository = Repository::new(&repository, false, Some(old.try_clone()?))?;
+    let new = password_prompt(NEW_MASTER_PASSWORD_PROMPT, /*confirm=*/ true)?;
+    if !confirm_password_strength(&new)? {
+        return Ok(());
+    }
+    repository.change_password(Some(old), Some(new))?;
+
+    Ok(())
+}
+
+pub(crate) fn keys(repository: Option<PathBuf>) -> Result<()> {
+    let _handle = crate::init_with_configuration().unwrap();
+    let repository = get_repository_path(repository)?;
+    let repository = Repository::new(&repository, false, None)?;
+    for key in repository.list_keys()? {
+        println!(
+            "{}  {:<12}  {:<19}  {}",
+            digest_fingerprint(&key.digest),
+            key.key_type.to_string(),
+            key.added
+                .map_or("(unknown)".to_owned(), util::format_utc_time),
+            key.label.as_deref().unwrap_or("")
+        );
+    }
+
+    Ok(())
+}
+
+pub(crate) fn twofactor(repository: Option<PathBuf>, disable: bool) -> Result<()> {
+    let _handle = crate::init_with_configuration().unwrap();
+    let repository = get_repository_path(repository)?;
+    let mut repository = Repository::new(&repository, false, None)?;
+    repository.set_requires_two_factor(!disable, None)?;
+
+    Ok(())
+}
+
+pub(crate) fn recovery_split(
+    repository: Option<PathBuf>,
+    shares: u8,
+    threshold: u8,
+    #[cfg(feature = "wifiqr")] qr_dir: Option<PathBuf>,
+) -> Result<()> {
+    let _handle = crate::init_with_configuration().unwrap();
+    let repository = get_repository_path(repository)?;
+    let mut repository = Repository::new(&repository, false, None)?;
+
+    let mut recovery_secret = Secret::with_len(RECOVERY_SECRET_LENGTH)?;
+    let mut generator = crate::crypto::rng::Generator;
+    generator.fill_bytes(unsafe { recovery_secret.as_mut_slice() });
+    let split = shamir::split(&recovery_secret, shares, threshold)?;
+
+    // Check that we can write out all of the QR codes *before* replacing the
+    // recovery key, so we don't end up with an incomplete set of shares.
+    #[cfg(feature = "wifiqr")]
+    let qr_paths: Option<Vec<PathBuf>> = qr_dir.map(|qr_dir| {
+        split
+            .iter()
+            .map(|share| qr_dir.join(format!("share-{}.png", share.index)))
+            .collect()
+    });
+    #[cfg(feature = "wifiqr")]
+    if let Some(existing) = qr_paths.iter().flatten().find(|path| path.exists()) {
+        bail!("refusing to overwrite '{}'", existing.display());
+    }
+
+    repository.set_recovery_key(recovery_secret)?;
+
+    for share in &split {
+        println!(
+            "Share {} of {} ({} needed): {}",
+            share.index,
+            shares,
+            threshold,
+            share.to_mnemonic()?
+        );
+    }
+
+    #[cfg(feature = "wifiqr")]
+    for (share, path) in split.iter().zip(qr_paths.iter().flatten()) {
+        crate::wifiqr::write_qr_code(
+            share.to_mnemonic()?.as_bytes(),
+            crate::wifiqr::ErrorCorrection::default(),
+            path,
+            /*overwrite=*/ false,
+        )?;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn recovery_unlock(repository: Option<PathBuf>) -> Result<()> {
+    let _handle = crate::init_with_configuration().unwrap();
+    let repository = get_repository_path(repository)?;
+
+    let mut shares: Vec<Share> = Vec::new();
+    loop {
+        let mnemonic = bdrck::cli::prompt_for_string(
+            bdrck::cli::Stream::Stdin,
+            bdrck::cli::Stream::Stderr,
+            RECOVERY_SHARE_PROMPT,
+            /*is_sensitive=*/ false,
+        )?;
+        match Share::from_mnemonic(&mnemonic, RECOVERY_SECRET_LENGTH) {
+            Ok(share) if shares.iter().any(|s| s.index == share.index) => {
+                eprintln!("Share {} was already entered.", share.index);
+                continue;
+            }
+            Ok(share) => shares.push(share),
+            Err(e) => {
+                eprintln!("Invalid share ({}), try again.", e);
+                continue;
+            }
+        };
+        if shares.len() >= shares[0].threshold as usize {
+            break;
+        }
+    }
+
+    // The recovery key is derived from the recovery secret just like a
+    // password, so we can open the repository with it directly.
+    let recovery_secret = shamir::combine(&shares)?;
+    let mut repository = Repository::new(&repository, false, Some(recovery_secret))?;
+    repository.add_password_key(None, None)?;
 
     Ok(())
 }
@@ -93,24 +323,293 @@ pub(crate) fn ls(repository: Option<PathBuf>, path_prefix: String) -> Result<()>
     Ok(())
 }
 
+pub(crate) fn exec(
+    repository: Option<PathBuf>,
+    env: Vec<EnvBinding>,
+    command: Vec<OsString>,
+) -> Result<()> {
+    let handle = crate::init_with_configuration().unwrap();
+    let repository = get_repository_path(repository)?;
+    let repository = Repository::new(&repository, false, None)?;
+    let values = read_bindings(&repository, &env)?;
+
+    let mut child = Command::new(&command[0]);
+    child.args(&command[1..]);
+    for (name, value) in &values {
+        child.env(name, OsStr::from_bytes(unsafe { value.as_slice() }));
+    }
+
+    // exec() replaces this process without running destructors, so clean up
+    // explicitly first: persist any repository / configuration changes, and
+    // zero the decrypted values.
+    drop(values);
+    drop(repository);
+    drop(handle);
+
+    // This only returns if executing the command failed.
+    let error = child.exec();
+    bail!(
+        "failed to execute '{}': {}",
+        command[0].to_string_lossy(),
+        error
+    );
+}
+
+pub(crate) fn env(
+    repository: Option<PathBuf>,
+    format: EnvFormat,
+    path_prefix: String,
+) -> Result<()> {
+    let _handle = crate::init_with_configuration().unwrap();
+    let repository = get_repository_path(repository)?;
+    let repository = Repository::new(&repository, false, None)?;
+    let prefix = repository.path(path_prefix)?;
+    let values = read_subtree(&repository, &prefix)?;
+
+    // Check everything first, so we don't print partial output.
+    let mut lines = Vec::with_capacity(values.len());
+    for (name, value) in &values {
+        let value = match std::str::from_utf8(unsafe { value.as_slice() }) {
+            Ok(v) => v,
+            Err(_) => bail!("the value for '{}' is binary data, not text", name),
+        };
+        lines.push(format_variable(name, value, format));
+    }
+    for line in lines {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+pub(crate) fn render(
+    repository: Option<PathBuf>,
+    template: PathBuf,
+    output: Option<PathBuf>,
+    force: bool,
+) -> Result<()> {
+    let _handle = crate::init_with_configuration().unwrap();
+    // Parse the template first, so syntax errors are reported before unlocking.
+    let template = Template::parse(&fs::read_to_string(&template)?)?;
+    let repository = get_repository_path(repository)?;
+    let repository = Repository::new(&repository, false, None)?;
+    let rendered = template.render(&repository)?;
+
+    match output {
+        None => output_secret(&rendered, InputEncoding::Verbatim, OutputMethod::Stdout),
+        Some(output) => output_secret_to_file(
+            &rendered,
+            InputEncoding::Verbatim,
+            output,
+            force,
+            /*require_tmpfs=*/ false,
+        ),
+    }
+}
+
+pub(crate) fn git_credential(repository: Option<PathBuf>, operation: String) -> Result<()> {
+    let _handle = crate::init_with_configuration().unwrap();
+    let scheme = configuration::get()?.get_git_credential_scheme()?;
+    // Read the whole request up front, so a master password prompt doesn't
+    // compete with it for stdin (and falls back to the terminal instead).
+    let request = Request::read(io::stdin().lock())?;
+    if ![
+        GIT_CREDENTIAL_GET,
+        GIT_CREDENTIAL_STORE,
+        GIT_CREDENTIAL_ERASE,
+    ]
+    .contains(&operation.as_str())
+    {
+        // Git may add new operations; helpers are expected to ignore any they
+        // don't understand.
+        return Ok(());
+    }
+
+    let repository = get_repository_path(repository)?;
+    let mut repository = Repository::new(&repository, false, None)?;
+    if operation == GIT_CREDENTIAL_GET {
+        if let Some((username, password)) = credential::get(&repository, &scheme, &request)? {
+            credential::write_response(io::stdout().lock(), &username, &password)?;
+        }
+    } else if operation == GIT_CREDENTIAL_STORE {
+        credential::store(&mut repository, &scheme, &request)?;
+    } else {
+        credential::erase(&mut repository, &scheme, &request)?;
+    }
+    Ok(())
+}
+
+fn read_server_url() -> Result<String> {
+    let mut server_url = String::new();
+    io::stdin().read_line(&mut server_url)?;
+    let server_url = server_url.trim();
+    if server_url.is_empty() {
+        bail!("no server URL given");
+    }
+    Ok(server_url.to_owned())
+}
+
+enum DockerCredentialRequest {
+    Get(String),
+    Store(Credentials),
+    Erase(String),
+    List,
+}
+
+fn docker_credential_impl(repository: Option<PathBuf>, operation: &str) -> Result<()> {
+    let prefix = configuration::get()?
+        .get_docker_credential_prefix()
+        .to_owned();
+    // Read the request before unlocking the repository, so a master password
+    // prompt doesn't compete with it for stdin (see `util::password_prompt`).
+    let request = if operation == DOCKER_CREDENTIAL_GET {
+        DockerCredentialRequest::Get(read_server_url()?)
+    } else if operation == DOCKER_CREDENTIAL_STORE {
+        DockerCredentialRequest::Store(serde_json::from_reader(io::stdin())?)
+    } else if operation == DOCKER_CREDENTIAL_ERASE {
+        DockerCredentialRequest::Erase(read_server_url()?)
+    } else if operation == DOCKER_CREDENTIAL_LIST {
+        DockerCredentialRequest::List
+    } else {
+        bail!("unknown credential helper operation '{}'", operation);
+    };
+
+    let repository = get_repository_path(repository)?;
+    let mut repository = Repository::new(&repository, false, None)?;
+    match request {
+        DockerCredentialRequest::Get(server_url) => println!(
+            "{}",
+            serde_json::to_string(&docker::get(&repository, &prefix, &server_url)?)?
+        ),
+        DockerCredentialRequest::Store(credentials) => {
+            docker::store(&mut repository, &prefix, &credentials)?
+        }
+        DockerCredentialRequest::Erase(server_url) => {
+            docker::erase(&mut repository, &prefix, &server_url)?
+        }
+        DockerCredentialRequest::List => println!(
+            "{}",
+            serde_json::to_string(&docker::list(&repository, &prefix)?)?
+        ),
+    }
+    Ok(())
+}
+
+pub(crate) fn docker_credential(repository: Option<PathBuf>, operation: String) -> Result<()> {
+    let handle = crate::init_with_configuration().unwrap();
+    // Docker expects errors to be written to stdout, with a nonzero exit
+    // status. In particular, it recognizes `docker::NOT_FOUND_MESSAGE`.
+    if let Err(e) = docker_credential_impl(repository, &operation) {
+        println!("{}", e);
+        drop(handle);
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
 pub(crate) fn get(
     repository: Option<PathBuf>,
-    binary: bool,
+    output_encoding: OutputEncodingArgs,
+    previous: bool,
+    #[cfg(feature = "wifiqr")] qr_file: Option<PathBuf>,
     output_method: OutputMethod,
+    destination: OutputDestinationArgs,
     path: String,
 ) -> Result<()> {
     let _handle = crate::init_with_configuration().unwrap();
     let repository = get_repository_path(repository)?;
     let repository = Repository::new(&repository, false, None)?;
     let path = repository.path(path)?;
-    output_secret(
-        &repository.read_decrypt(&path)?,
-        match binary {
-            false => InputEncoding::Auto,
-            true => InputEncoding::Binary,
-        },
-        output_method,
-    )?;
+    let mut stored = match previous {
+        false => repository.read_decrypt(&path)?,
+        true => repository.read_previous(&path)?,
+    };
+    if Derivation::is_derivation(&stored) {
+        stored = repository.derive_password(&Derivation::from_secret(&stored)?)?;
+    }
+    let encoding = match (output_encoding.binary, output_encoding.encoding) {
+        (_, Some(output_encoding)) => {
+            stored = encoding::encode(&stored, output_encoding)?;
+            InputEncoding::Verbatim
+        }
+        (false, None) => InputEncoding::Auto,
+        (true, None) => InputEncoding::Binary,
+    };
+
+    #[cfg(feature = "wifiqr")]
+    if let Some(qr_file) = qr_file {
+        let display = encode_for_display(&stored, encoding, /*supports_binary=*/ false)?;
+        return crate::wifiqr::write_qr_code(
+            unsafe { display.as_slice() },
+            crate::wifiqr::ErrorCorrection::default(),
+            qr_file,
+            /*overwrite=*/ false,
+        );
+    }
+    if let Some(output_file) = destination.output_file {
+        return output_secret_to_file(
+            &stored,
+            encoding,
+            output_file,
+            destination.force,
+            destination.tmpfs,
+        );
+    }
+    if let Some(command) = destination.pipe_to {
+        return output_secret_to_command(&stored, encoding, &command);
+    }
+    output_secret(&stored, encoding, output_method)?;
+    Ok(())
+}
+
+/// Return the settings to generate a new password for the given path with. If
+/// no generator arguments were given, the generation profile for the path is
+/// used.
+fn settings_for_path(
+    repository: &Repository,
+    path: &RepositoryPath,
+    args: &StoreGeneratedArgs,
+) -> Result<GeneratorSettings> {
+    let config = repository.get_crypto_configuration();
+    let path = path.to_str()?;
+    let settings = match config.find_generation_profile(path) {
+        Some(profile) if args.generate.is_default() => {
+            let mut settings = profile.settings.clone();
+            if let Some(length) = args.password_length {
+                settings.length = length;
+            }
+            settings
+        }
+        _ => args.generate.to_settings(
+            args.password_length
+                .unwrap_or(pwgen::RECOMMENDED_MINIMUM_PASSWORD_LENGTH),
+        ),
+    };
+    Ok(settings)
+}
+
+fn generate_for_path(
+    repository: &Repository,
+    path: &RepositoryPath,
+    args: &StoreGeneratedArgs,
+) -> Result<Secret> {
+    settings_for_path(repository, path, args)?.generate()
+}
+
+fn store_generated(
+    repository: &mut Repository,
+    path: &RepositoryPath,
+    args: &StoreGeneratedArgs,
+) -> Result<()> {
+    let generated = generate_for_path(repository, path, args)?;
+    let output = match args.output_method {
+        None => None,
+        Some(_) => Some(generated.try_clone()?),
+    };
+    repository.write_encrypt(path, generated, None)?;
+
+    if let (Some(output), Some(method)) = (output, args.output_method) {
+        output_secret(&output, InputEncoding::Auto, method)?;
+    }
     Ok(())
 }
 
@@ -118,6 +617,9 @@ pub(crate) fn set(
     repository: Option<PathBuf>,
     key_file: Option<PathBuf>,
     multiline: bool,
+    input_encoding: Option<SecretEncoding>,
+    generate: bool,
+    generated: StoreGeneratedArgs,
     path: String,
 ) -> Result<()> {
     let _handle = crate::init_with_configuration().unwrap();
@@ -128,23 +630,241 @@ pub(crate) fn set(
     if key_file.is_some() && multiline {
         bail!("the 'key_file' and 'multiline' options are mutually exclusive");
     }
+    if !generate && !generated.is_default() {
+        bail!("password generation options require 'generate'");
+    }
 
-    if let Some(key_file) = key_file {
+    if generate {
+        store_generated(&mut repository, &path, &generated)?;
+    } else if let Some(key_file) = key_file {
         // The user wants to set the password using a key file.
-        repository.write_encrypt(&path, util::secret::load_file(&key_file)?, None)?;
+        let mut key = util::secret::load_file(&key_file)?;
+        if let Some(input_encoding) = input_encoding {
+            key = encoding::decode(&key, input_encoding)?;
+        }
+        repository.write_encrypt(&path, key, None)?;
     } else {
         // The user wants to set the password, but no key file was given, so prompt for
         // the password interactively.
-        repository.write_encrypt(
-            &path,
-            match multiline {
-                false => password_prompt(NEW_PASSWORD_PROMPT, true)?,
-                true => multiline_password_prompt(MULTILINE_PASSWORD_PROMPT)?,
-            },
-            None,
-        )?;
+        let mut password = match multiline {
+            false => password_prompt(NEW_PASSWORD_PROMPT, true)?,
+            true => multiline_password_prompt(MULTILINE_PASSWORD_PROMPT)?,
+        };
+        if let Some(input_encoding) = input_encoding {
+            // Encoded input is typically key material, not a memorized
+            // password, so there's no point checking its strength.
+            password = encoding::decode(&password, input_encoding)?;
+        } else if !multiline && !confirm_password_strength(&password)? {
+            return Ok(());
+        }
+        repository.write_encrypt(&path, password, None)?;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn regenerate(
+    repository: Option<PathBuf>,
+    generated: StoreGeneratedArgs,
+    path: String,
+) -> Result<()> {
+    let _handle = crate::init_with_configuration().unwrap();
+    let repository = get_repository_path(repository)?;
+    let mut repository = Repository::new(&repository, false, None)?;
+    let path = repository.path(path)?;
+
+    if !path.absolute_path().exists() {
+        bail!(
+            "no stored password at path '{}'",
+            path.relative_path().display()
+        );
+    }
+    store_generated(&mut repository, &path, &generated)
+}
+
+pub(crate) fn edit(repository: Option<PathBuf>, path: String) -> Result<()> {
+    let _handle = crate::init_with_configuration().unwrap();
+    let repository = get_repository_path(repository)?;
+    let mut repository = Repository::new(&repository, false, None)?;
+    let path = repository.path(path)?;
+
+    if !path.absolute_path().exists() {
+        bail!(
+            "no stored password at path '{}'",
+            path.relative_path().display()
+        );
+    }
+    let stored = repository.read_decrypt(&path)?;
+    if Derivation::is_derivation(&stored) {
+        bail!(
+            "'{}' is a derived password; use 'derive' to change its parameters",
+            path.relative_path().display()
+        );
+    }
+
+    match editor::edit_secret(&stored)? {
+        None => eprintln!("No changes made."),
+        Some(edited) => repository.write_encrypt(&path, edited, None)?,
+    }
+    Ok(())
+}
+
+pub(crate) fn derive(
+    repository: Option<PathBuf>,
+    site: String,
+    login: String,
+    counter: u64,
+    generated: StoreGeneratedArgs,
+    path: String,
+) -> Result<()> {
+    let _handle = crate::init_with_configuration().unwrap();
+    let repository = get_repository_path(repository)?;
+    let mut repository = Repository::new(&repository, false, None)?;
+    let path = repository.path(path)?;
+
+    let derivation = Derivation {
+        site,
+        login,
+        counter,
+        settings: settings_for_path(&repository, &path, &generated)?,
+    };
+    let derived = repository.write_derivation(&path, &derivation)?;
+
+    if let Some(method) = generated.output_method {
+        output_secret(&derived, InputEncoding::Auto, method)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn rotate(
+    repository: Option<PathBuf>,
+    confirm: bool,
+    abort: bool,
+    generated: StoreGeneratedArgs,
+    path: String,
+) -> Result<()> {
+    let _handle = crate::init_with_configuration().unwrap();
+    let repository = get_repository_path(repository)?;
+    let mut repository = Repository::new(&repository, false, None)?;
+    let path = repository.path(path)?;
+
+    if (confirm || abort) && !generated.is_default() {
+        bail!("password generation options can't be used with 'confirm' or 'abort'");
+    }
+    if confirm {
+        return repository.confirm_rotation(&path);
+    } else if abort {
+        return repository.abort_rotation(&path);
+    }
+
+    let stored = repository.read_decrypt(&path)?;
+    let (new, derived) = if Derivation::is_derivation(&stored) {
+        if !generated.generate.is_default() || generated.password_length.is_some() {
+            bail!("derived entries keep their generation settings when rotated");
+        }
+        let mut derivation = Derivation::from_secret(&stored)?;
+        derivation.counter += 1;
+        let derived = repository.derive_password(&derivation)?;
+        (derivation.to_secret()?, derived)
+    } else {
+        let generated = generate_for_path(&repository, &path, &generated)?;
+        let output = generated.try_clone()?;
+        (generated, output)
+    };
+    repository.rotate(&path, new)?;
+    eprintln!(
+        "Rotated '{}'. Once the change is complete, run 'rotate --confirm' (or 'rotate --abort').",
+        path.relative_path().display()
+    );
+
+    if let Some(method) = generated.output_method {
+        output_secret(&derived, InputEncoding::Auto, method)?;
     }
+    Ok(())
+}
+
+pub(crate) fn profile_set(
+    repository: Option<PathBuf>,
+    prefix: String,
+    password_length: usize,
+    args: GenerateArgs,
+) -> Result<()> {
+    let _handle = crate::init_with_configuration().unwrap();
+    let repository = get_repository_path(repository)?;
+    let repository = Repository::new(&repository, false, None)?;
+    let settings = args.to_settings(password_length);
+    // Make sure these settings actually work, before saving them.
+    settings.generate()?;
 
+    let mut config = repository.get_crypto_configuration();
+    config.set_generation_profile(prefix, settings);
+    repository.set_crypto_configuration(config);
+    Ok(())
+}
+
+pub(crate) fn profile_rm(repository: Option<PathBuf>, prefix: String) -> Result<()> {
+    let _handle = crate::init_with_configuration().unwrap();
+    let repository = get_repository_path(repository)?;
+    let repository = Repository::new(&repository, false, None)?;
+    let mut config = repository.get_crypto_configuration();
+    if !config.remove_generation_profile(&prefix) {
+        bail!("no generation profile found for '{}'", prefix);
+    }
+    repository.set_crypto_configuration(config);
+    Ok(())
+}
+
+pub(crate) fn profile_ls(repository: Option<PathBuf>) -> Result<()> {
+    let _handle = crate::init_with_configuration().unwrap();
+    let repository = get_repository_path(repository)?;
+    let repository = Repository::new(&repository, false, None)?;
+    for profile in repository
+        .get_crypto_configuration()
+        .get_generation_profiles()
+    {
+        println!(
+            "{}: {}",
+            profile.path_prefix,
+            serde_json::to_string(&profile.settings)?
+        );
+    }
+    Ok(())
+}
+
+pub(crate) fn otp(
+    repository: Option<PathBuf>,
+    add: bool,
+    output_method: OutputMethod,
+    path: String,
+) -> Result<()> {
+    let _handle = crate::init_with_configuration().unwrap();
+    let repository = get_repository_path(repository)?;
+    let mut repository = Repository::new(&repository, false, None)?;
+    let path = repository.path(path)?;
+
+    if add {
+        // Parse the URI before storing it, both to validate it and to store it
+        // in a canonical form.
+        let otp = Otp::from_secret(&password_prompt(OTP_URI_PROMPT, /*confirm=*/ false)?)?;
+        repository.write_encrypt(&path, otp.to_uri()?, None)?;
+        return Ok(());
+    }
+
+    let stored = repository.read_decrypt(&path)?;
+    if !Otp::is_otp(&stored) {
+        bail!("'{}' is not an OTP entry", path.relative_path().display());
+    }
+    let mut otp = Otp::from_secret(&stored)?;
+    let code = otp.next_code(SystemTime::now())?;
+    if let OtpKind::Hotp { .. } = otp.kind {
+        // Save the incremented counter *before* displaying the code, so we
+        // never display the same HOTP code twice.
+        repository.write_encrypt(&path, otp.to_uri()?, None)?;
+    }
+
+    let mut code_secret = Secret::with_len(code.len())?;
+    unsafe { code_secret.as_mut_slice() }.copy_from_slice(code.as_bytes());
+    output_secret(&code_secret, InputEncoding::Auto, output_method)?;
     Ok(())
 }
 
@@ -157,20 +877,30 @@ pub(crate) fn rm(repository: Option<PathBuf>, path: String) -> Result<()> {
     Ok(())
 }
 
-pub(crate) fn generate(password_length: usize, args: GenerateArgs) -> Result<()> {
+pub(crate) fn generate(
+    password_length: usize,
+    args: GenerateArgs,
+    passphrase: PassphraseArgs,
+) -> Result<()> {
     let _handle = crate::init_with_configuration().unwrap();
-    let charsets = args.to_charsets();
-    let custom_exclude: Vec<char> = args.custom_exclude.map_or(vec![], |x| x.chars().collect());
 
-    output_secret(
-        &pwgen::generate_password(
-            password_length,
-            charsets.as_slice(),
-            custom_exclude.as_slice(),
-        )?,
-        InputEncoding::Auto,
-        OutputMethod::Stdout,
-    )?;
+    if let Some(options) = passphrase.to_options() {
+        let generated = pwgen::generate_passphrase(&options)?;
+        // Report this on stderr, so the passphrase alone can be piped elsewhere.
+        eprintln!("Entropy: {:.1} bits", pwgen::passphrase_entropy(&options));
+        output_secret(&generated, InputEncoding::Auto, OutputMethod::Stdout)?;
+        return Ok(());
+    }
+
+    let generated = args.generate(password_length)?;
+    let estimate = strength::estimate_secret(&generated);
+    eprintln!(
+        "Estimated entropy: {:.1} bits (strength {}/{})",
+        estimate.guesses_log2,
+        estimate.score,
+        strength::MAX_SCORE
+    );
+    output_secret(&generated, InputEncoding::Auto, OutputMethod::Stdout)?;
 
     Ok(())
 }
@@ -198,3 +928,74 @@ pub(crate) fn import(repository: Option<PathBuf>, input: PathBuf) -> Result<()>
 
     Ok(())
 }
+
+pub(crate) fn audit(
+    repository: Option<PathBuf>,
+    max_age_days: u64,
+    breach_db: Option<PathBuf>,
+    json: bool,
+) -> Result<()> {
+    let _handle = crate::init_with_configuration().unwrap();
+    let repository = get_repository_path(repository)?;
+    let repository = Repository::new(&repository, false, None)?;
+    let config = configuration::get()?;
+    let mut breach_db = match breach_db.or(config.breach_db.clone()) {
+        Some(path) => Some(BreachDatabase::open(path)?),
+        None => None,
+    };
+    let max_age = match max_age_days.checked_mul(24 * 60 * 60) {
+        None => bail!("the maximum age of {} days is too large", max_age_days),
+        Some(secs) => Duration::from_secs(secs),
+    };
+    let report = audit::audit(
+        &repository,
+        config.get_min_password_strength(),
+        max_age,
+        breach_db.as_mut(),
+    )?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    if report.is_clean() {
+        println!("No problems found.");
+        return Ok(());
+    }
+    if !report.weak.is_empty() {
+        println!("Weak passwords:");
+        for entry in &report.weak {
+            println!(
+                "  {} (strength {}/{}) {}",
+                entry.path,
+                entry.score,
+                strength::MAX_SCORE,
+                entry.warning.as_deref().unwrap_or("")
+            );
+        }
+    }
+    if !report.breached.is_empty() {
+        println!("Breached passwords:");
+        for entry in &report.breached {
+            println!("  {} (seen {} times)", entry.path, entry.count);
+        }
+    }
+    if !report.reused.is_empty() {
+        println!("Reused passwords:");
+        for group in &report.reused {
+            println!("  [{}] {}", group.fingerprint, group.paths.join(", "));
+        }
+    }
+    if !report.stale.is_empty() {
+        println!("Passwords not changed in {} days:", max_age_days);
+        for entry in &report.stale {
+            println!(
+                "  {} (last changed {}, {} days ago)",
+                entry.path, entry.last_changed, entry.age_days
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -15,11 +15,20 @@
 mod impls;
 pub(crate) mod util;
 
-use crate::crypto::pwgen::{CharacterSet, RECOMMENDED_MINIMUM_PASSWORD_LENGTH};
-use crate::output::OutputMethod;
+use crate::crypto::pwgen::{
+    Capitalization, CharacterSet, GeneratorSettings, PassphraseOptions, PasswordPolicy,
+    RECOMMENDED_MINIMUM_PASSWORD_LENGTH,
+};
+use crate::output::encoding::SecretEncoding;
+use crate::output::{ClipboardSelection, OutputMethod};
+use crate::repository::env::{EnvBinding, EnvFormat};
 use anyhow::Result;
+use bdrck::crypto::secret::Secret;
 use clap::{Args, Parser, Subcommand};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
 use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Args, Clone)]
 pub(crate) struct RepositoryArgs {
@@ -34,7 +43,7 @@ struct PathArgs {
     path: String,
 }
 
-#[derive(Args)]
+#[derive(Args, Default, PartialEq)]
 pub(crate) struct GenerateArgs {
     #[arg(short = 'A', long)]
     /// Exclude letters from the password.
@@ -51,6 +60,49 @@ pub(crate) struct GenerateArgs {
     #[arg(short = 'X', long)]
     /// Exclude a custom set of characters.
     pub(crate) custom_exclude: Option<String>,
+
+    #[arg(long, default_value_t = 0)]
+    /// The minimum number of letters the password must contain.
+    pub(crate) min_letters: usize,
+
+    #[arg(long, default_value_t = 0)]
+    /// The minimum number of numbers the password must contain.
+    pub(crate) min_numbers: usize,
+
+    #[arg(long, default_value_t = 0)]
+    /// The minimum number of symbols the password must contain.
+    pub(crate) min_symbols: usize,
+
+    #[arg(long)]
+    /// The maximum number of times the same character may repeat in a row.
+    pub(crate) max_repeat: Option<usize>,
+
+    #[arg(long)]
+    /// Exclude characters which are easily confused, like 0/O and l/1.
+    pub(crate) no_ambiguous: bool,
+
+    #[arg(value_enum, long)]
+    /// Require the password to start with a character from this set.
+    pub(crate) first: Option<CharacterSet>,
+
+    #[arg(
+        short = 'p',
+        long,
+        conflicts_with_all = [
+            "exclude_letters",
+            "exclude_numbers",
+            "exclude_symbols",
+            "min_letters",
+            "min_numbers",
+            "min_symbols",
+            "max_repeat",
+            "first",
+        ]
+    )]
+    /// Generate a password matching this pattern, e.g. 'L{4}-D{4}'. In patterns, L/l/U are
+    /// letters (any/lower/upper case), D digits, H hex digits, S symbols, A alphanumerics, and *
+    /// any character; {n} repeats, and other characters (or any after a '\') are literals.
+    pub(crate) pattern: Option<String>,
 }
 
 impl GenerateArgs {
@@ -67,6 +119,189 @@ impl GenerateArgs {
         }
         charsets
     }
+
+    /// Returns true if none of these arguments were given on the command line.
+    pub(crate) fn is_default(&self) -> bool {
+        *self == GenerateArgs::default()
+    }
+
+    pub(crate) fn to_settings(&self, length: usize) -> GeneratorSettings {
+        GeneratorSettings {
+            length,
+            charsets: self.to_charsets(),
+            exclude: self
+                .custom_exclude
+                .as_ref()
+                .map_or(vec![], |x| x.chars().collect()),
+            policy: self.to_policy(),
+            pattern: self.pattern.clone(),
+        }
+    }
+
+    /// Generate a password according to these arguments: either matching the
+    /// given pattern, or of the given length satisfying the given policy.
+    pub(crate) fn generate(&self, length: usize) -> Result<Secret> {
+        self.to_settings(length).generate()
+    }
+
+    pub(crate) fn to_policy(&self) -> PasswordPolicy {
+        let mut min_counts = BTreeMap::new();
+        min_counts.insert(CharacterSet::Letters, self.min_letters);
+        min_counts.insert(CharacterSet::Numbers, self.min_numbers);
+        min_counts.insert(CharacterSet::Symbols, self.min_symbols);
+        PasswordPolicy {
+            min_counts,
+            max_repeat: self.max_repeat,
+            exclude_ambiguous: self.no_ambiguous,
+            first: self.first,
+        }
+    }
+}
+
+#[derive(Args)]
+pub(crate) struct StoreGeneratedArgs {
+    #[arg(short = 'l', long)]
+    /// The length of the password to generate. By default, the path's generation profile is used.
+    pub(crate) password_length: Option<usize>,
+
+    #[command(flatten)]
+    pub(crate) generate: GenerateArgs,
+
+    #[arg(value_enum, short = 'o', long)]
+    /// Also output the generated password, e.g. to the clipboard.
+    pub(crate) output_method: Option<OutputMethod>,
+}
+
+impl StoreGeneratedArgs {
+    /// Returns true if none of these arguments were given on the command line.
+    pub(crate) fn is_default(&self) -> bool {
+        self.password_length.is_none() && self.generate.is_default() && self.output_method.is_none()
+    }
+}
+
+#[derive(Args)]
+pub(crate) struct OutputEncodingArgs {
+    #[arg(short = 'b', long)]
+    /// Treat the saved password or key as binary data.
+    pub(crate) binary: bool,
+
+    #[arg(value_enum, short = 'e', long, conflicts_with = "binary")]
+    /// Encode the saved password or key this way before outputting it,
+    /// instead of only base64-encoding binary data where necessary.
+    pub(crate) encoding: Option<SecretEncoding>,
+}
+
+#[derive(Args)]
+pub(crate) struct OutputDestinationArgs {
+    #[arg(long, conflicts_with = "pipe_to")]
+    /// Write the secret to this file (readable only by you) instead.
+    pub(crate) output_file: Option<PathBuf>,
+
+    #[arg(long, requires = "output_file")]
+    /// Overwrite the output file if it already exists.
+    pub(crate) force: bool,
+
+    #[arg(long, requires = "output_file")]
+    /// Refuse to write the output file unless it is on a tmpfs (in memory).
+    pub(crate) tmpfs: bool,
+
+    #[arg(long)]
+    /// Run this shell command, writing the secret to its stdin instead.
+    pub(crate) pipe_to: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum ProfileCommands {
+    /// Set the password generation settings for paths starting with a prefix.
+    Set {
+        #[command(flatten)]
+        repository: RepositoryArgs,
+
+        #[arg(short = 'l', long, default_value_t = RECOMMENDED_MINIMUM_PASSWORD_LENGTH)]
+        /// The length of the password to generate.
+        password_length: usize,
+
+        #[command(flatten)]
+        args: GenerateArgs,
+
+        /// The path prefix this profile applies to, e.g. 'bank/'.
+        prefix: String,
+    },
+
+    /// Remove the generation profile for a path prefix.
+    Rm {
+        #[command(flatten)]
+        repository: RepositoryArgs,
+
+        /// The path prefix whose profile should be removed.
+        prefix: String,
+    },
+
+    /// List all generation profiles.
+    Ls {
+        #[command(flatten)]
+        repository: RepositoryArgs,
+    },
+}
+
+#[derive(Args)]
+pub(crate) struct PassphraseArgs {
+    #[arg(short = 'w', long)]
+    /// Generate a passphrase of this many words, instead of random characters.
+    pub(crate) words: Option<usize>,
+
+    #[arg(long, default_value = "-", requires = "words")]
+    /// The separator to put between passphrase words.
+    pub(crate) separator: String,
+
+    #[arg(value_enum, long, default_value_t = Capitalization::default(), requires = "words")]
+    /// How to capitalize passphrase words.
+    pub(crate) capitalize: Capitalization,
+
+    #[arg(long, requires = "words")]
+    /// Append a random digit to one of the passphrase's words.
+    pub(crate) digit: bool,
+}
+
+impl PassphraseArgs {
+    /// Returns the passphrase options to use, or None if the user wants a
+    /// normal password instead.
+    pub(crate) fn to_options(&self) -> Option<PassphraseOptions> {
+        self.words.map(|words| PassphraseOptions {
+            words,
+            separator: self.separator.clone(),
+            capitalization: self.capitalize,
+            insert_digit: self.digit,
+        })
+    }
+}
+
+#[derive(Subcommand)]
+enum RecoveryCommands {
+    /// Generate a new recovery key, and split it into shares to distribute.
+    Split {
+        #[command(flatten)]
+        repository: RepositoryArgs,
+
+        #[arg(short = 'n', long, default_value_t = 5)]
+        /// The total number of shares to generate.
+        shares: u8,
+
+        #[arg(short = 't', long, default_value_t = 3)]
+        /// The number of shares needed to recover the repository.
+        threshold: u8,
+
+        #[cfg(feature = "wifiqr")]
+        #[arg(long)]
+        /// Also write each share as a QR code image into this directory.
+        qr_dir: Option<PathBuf>,
+    },
+
+    /// Unlock a repository with recovery shares, and add a new master password.
+    Unlock {
+        #[command(flatten)]
+        repository: RepositoryArgs,
+    },
 }
 
 #[derive(Subcommand)]
@@ -91,12 +326,56 @@ enum Commands {
     AddKey {
         #[command(flatten)]
         repository: RepositoryArgs,
+
+        #[arg(short = 'k', long)]
+        /// Derive the new key from the contents of this file, instead of a password.
+        key_file: Option<PathBuf>,
+
+        #[arg(short = 'l', long)]
+        /// A label to identify the new key by, e.g. in 'keys' or 'rm-key'.
+        label: Option<String>,
     },
 
     /// Remove an existing master key from an existing repository.
     RmKey {
         #[command(flatten)]
         repository: RepositoryArgs,
+
+        #[arg(short = 'k', long, conflicts_with = "label")]
+        /// Remove the key derived from the contents of this file, instead of a password.
+        key_file: Option<PathBuf>,
+
+        #[arg(short = 'l', long)]
+        /// Remove the key with this label, instead of a password.
+        label: Option<String>,
+    },
+
+    /// Change the master password of an existing repository.
+    Passwd {
+        #[command(flatten)]
+        repository: RepositoryArgs,
+    },
+
+    /// List the master keys which can unlock an existing repository.
+    Keys {
+        #[command(flatten)]
+        repository: RepositoryArgs,
+    },
+
+    /// Require both a password and a PIV device to unlock an existing repository.
+    TwoFactor {
+        #[command(flatten)]
+        repository: RepositoryArgs,
+
+        #[arg(long)]
+        /// Stop requiring a PIV device, so the password alone unlocks the repository.
+        disable: bool,
+    },
+
+    /// Manage a recovery key, which is split into shares held by different people.
+    Recovery {
+        #[command(subcommand)]
+        command: RecoveryCommands,
     },
 
     #[cfg(feature = "piv")]
@@ -121,19 +400,88 @@ enum Commands {
         path_prefix: String,
     },
 
+    /// Run a command, with stored passwords added to its environment.
+    Exec {
+        #[command(flatten)]
+        repository: RepositoryArgs,
+
+        #[arg(short = 'e', long = "env", value_name = "NAME=PATH", required = true)]
+        /// Set the environment variable NAME to the password stored at PATH.
+        /// May be given multiple times.
+        env: Vec<EnvBinding>,
+
+        #[arg(last = true, required = true)]
+        /// The command to run, and its arguments.
+        command: Vec<OsString>,
+    },
+
+    /// Print the passwords under a path prefix as environment variable
+    /// assignments. Names are derived from the paths relative to the prefix,
+    /// e.g. 'db/password' becomes DB_PASSWORD.
+    Env {
+        #[command(flatten)]
+        repository: RepositoryArgs,
+
+        #[arg(value_enum, short = 'f', long, default_value_t = EnvFormat::Shell)]
+        /// How to format the variable assignments.
+        format: EnvFormat,
+
+        #[arg(default_value = "")]
+        /// The saved password path prefix, relative to the repository's root.
+        path_prefix: String,
+    },
+
+    /// Render a template file, replacing placeholders like
+    /// '{{ pwm "db/prod" }}' or '{{ pwm "db/prod" field="user" }}' with the
+    /// stored passwords they refer to.
+    Render {
+        #[command(flatten)]
+        repository: RepositoryArgs,
+
+        #[arg(short = 'o', long)]
+        /// Write the rendered output to this file (readable only by you),
+        /// instead of stdout.
+        output: Option<PathBuf>,
+
+        #[arg(short = 'f', long, requires = "output")]
+        /// Overwrite the output file if it already exists.
+        force: bool,
+
+        /// The template file to render.
+        template: PathBuf,
+    },
+
     /// Retrieve a password or key from a pwm repository.
     Get {
         #[command(flatten)]
         repository: RepositoryArgs,
 
-        #[arg(short = 'b', long)]
-        /// Treat the saved password or key as binary data.
-        binary: bool,
-
-        #[arg(value_enum, short = 'o', long, default_value_t = OutputMethod::default())]
+        #[command(flatten)]
+        encoding: OutputEncodingArgs,
+
+        #[arg(short = 'p', long)]
+        /// Retrieve the previous value of a password which is being rotated.
+        previous: bool,
+
+        #[cfg(feature = "wifiqr")]
+        #[arg(long, conflicts_with_all = ["output_method", "output_file", "pipe_to"])]
+        /// Instead of outputting the secret, write it as a QR code image to
+        /// this path. Format autodetected from file extension (PNG or SVG).
+        qr_file: Option<PathBuf>,
+
+        #[arg(
+            value_enum,
+            short = 'o',
+            long,
+            default_value_t = OutputMethod::default(),
+            conflicts_with_all = ["output_file", "pipe_to"]
+        )]
         /// How to output the retrieved secret.
         output_method: OutputMethod,
 
+        #[command(flatten)]
+        destination: OutputDestinationArgs,
+
         #[command(flatten)]
         path: PathArgs,
     },
@@ -151,10 +499,144 @@ enum Commands {
         /// Read multiple lines of input data, until 'EOF'.
         multiline: bool,
 
+        #[arg(value_enum, short = 'e', long)]
+        /// The encoding of the input data. It is decoded before being stored,
+        /// e.g. to store a binary key which was copied as base64 text.
+        encoding: Option<SecretEncoding>,
+
+        #[arg(short = 'g', long, conflicts_with_all = ["key_file", "multiline", "encoding"])]
+        /// Generate a new random password, instead of prompting for one.
+        generate: bool,
+
+        #[command(flatten)]
+        generated: StoreGeneratedArgs,
+
         #[command(flatten)]
         path: PathArgs,
     },
 
+    /// Replace a stored password with a newly generated one.
+    Regenerate {
+        #[command(flatten)]
+        repository: RepositoryArgs,
+
+        #[command(flatten)]
+        generated: StoreGeneratedArgs,
+
+        #[command(flatten)]
+        path: PathArgs,
+    },
+
+    /// Edit a stored password or key with $VISUAL or $EDITOR. The secret is
+    /// only ever written to a private temporary file on a tmpfs.
+    Edit {
+        #[command(flatten)]
+        repository: RepositoryArgs,
+
+        #[command(flatten)]
+        path: PathArgs,
+    },
+
+    /// Store a derived password entry. Rather than a password, only the given
+    /// parameters are stored, and the password is derived from a secret kept
+    /// in the repository whenever it is retrieved with 'get'.
+    Derive {
+        #[command(flatten)]
+        repository: RepositoryArgs,
+
+        #[arg(short = 's', long)]
+        /// The site the password is for, e.g. 'example.com'.
+        site: String,
+
+        #[arg(short = 'u', long, default_value = "")]
+        /// The login (e.g. username or email address) the password is for.
+        login: String,
+
+        #[arg(short = 'c', long, default_value_t = 0)]
+        /// The initial counter value. Bumping this yields a new password.
+        counter: u64,
+
+        #[command(flatten)]
+        generated: StoreGeneratedArgs,
+
+        #[command(flatten)]
+        path: PathArgs,
+    },
+
+    /// Replace the password at the given path with a newly generated one (or,
+    /// for derived entries, bump the counter), keeping the previous value
+    /// available with 'get --previous' until the rotation is confirmed.
+    Rotate {
+        #[command(flatten)]
+        repository: RepositoryArgs,
+
+        #[arg(long, conflicts_with = "abort")]
+        /// Finish an in-progress rotation, discarding the previous value.
+        confirm: bool,
+
+        #[arg(long)]
+        /// Roll back an in-progress rotation, restoring the previous value.
+        abort: bool,
+
+        #[command(flatten)]
+        generated: StoreGeneratedArgs,
+
+        #[command(flatten)]
+        path: PathArgs,
+    },
+
+    /// Manage the password generation profiles used by 'set --generate' and 'regenerate'.
+    Profile {
+        #[command(subcommand)]
+        command: ProfileCommands,
+    },
+
+    /// Print the current one-time password code for a stored OTP entry.
+    Otp {
+        #[command(flatten)]
+        repository: RepositoryArgs,
+
+        #[arg(short = 'a', long)]
+        /// Instead of printing a code, store a new OTP entry from an 'otpauth://' URI.
+        add: bool,
+
+        #[arg(value_enum, short = 'o', long, default_value_t = OutputMethod::default())]
+        /// How to output the generated code.
+        output_method: OutputMethod,
+
+        #[command(flatten)]
+        path: PathArgs,
+    },
+
+    /// Act as a git credential helper, storing credentials in a pwm
+    /// repository. To use it, configure e.g.:
+    /// git config --global credential.helper '!pwm git-credential'
+    ///
+    /// Credentials are stored at paths given by the 'git_credential_scheme'
+    /// configuration value, by default 'git/{protocol}/{host}/{username}'.
+    GitCredential {
+        #[command(flatten)]
+        repository: RepositoryArgs,
+
+        /// The operation requested by git: 'get', 'store' or 'erase'.
+        operation: String,
+    },
+
+    /// Act as a Docker credential helper, storing registry credentials in a
+    /// pwm repository. Docker runs this via the 'docker-credential-pwm'
+    /// binary, if "credsStore": "pwm" is set in ~/.docker/config.json.
+    ///
+    /// Credentials are stored under the path prefix given by the
+    /// 'docker_credential_prefix' configuration value, by default 'docker'.
+    DockerCredential {
+        #[command(flatten)]
+        repository: RepositoryArgs,
+
+        /// The operation requested by Docker: 'get', 'store', 'erase' or
+        /// 'list'.
+        operation: String,
+    },
+
     /// Remove a password or key from a pwm repository.
     Rm {
         #[command(flatten)]
@@ -172,6 +654,9 @@ enum Commands {
 
         #[command(flatten)]
         args: GenerateArgs,
+
+        #[command(flatten)]
+        passphrase: PassphraseArgs,
     },
 
     #[cfg(feature = "wifiqr")]
@@ -193,6 +678,52 @@ enum Commands {
         /// The input file to import from.
         input: PathBuf,
     },
+
+    #[cfg(feature = "clipboard")]
+    #[command(hide = true)]
+    /// Used internally to hold and then restore clipboard contents in the
+    /// background. The secret to copy is read from stdin.
+    ClipboardDaemon {
+        #[arg(value_enum, long)]
+        selection: ClipboardSelection,
+
+        #[arg(long)]
+        /// How long to hold the secret on the clipboard, in seconds.
+        timeout: u64,
+    },
+
+    #[command(hide = true)]
+    /// Used internally to clear the terminal's clipboard in the background,
+    /// after copying a secret to it with OSC 52.
+    Osc52Clear {
+        #[arg(value_enum, long)]
+        selection: ClipboardSelection,
+
+        #[arg(long)]
+        /// How long to wait before clearing the clipboard, in seconds.
+        timeout: u64,
+    },
+
+    /// Report stored passwords which are weak, reused, or haven't been changed
+    /// in a long time.
+    Audit {
+        #[command(flatten)]
+        repository: RepositoryArgs,
+
+        #[arg(short = 'd', long, default_value_t = 365)]
+        /// Report passwords which haven't been changed in this many days.
+        max_age_days: u64,
+
+        #[arg(short = 'b', long)]
+        /// A sorted breached password hash file (in Have I Been Pwned's
+        /// SHA-1 format) to check passwords against. Defaults to the
+        /// configured 'breach_db', if any.
+        breach_db: Option<PathBuf>,
+
+        #[arg(short = 'j', long)]
+        /// Print the report as JSON, instead of in a human-readable format.
+        json: bool,
+    },
 }
 
 #[derive(Parser)]
@@ -206,8 +737,40 @@ impl Cli {
         match self.command {
             Commands::Config { key, set } => impls::config(key, set),
             Commands::Init { repository } => impls::init(repository.repository),
-            Commands::AddKey { repository } => impls::addkey(repository.repository),
-            Commands::RmKey { repository } => impls::rmkey(repository.repository),
+            Commands::AddKey {
+                repository,
+                key_file,
+                label,
+            } => impls::addkey(repository.repository, key_file, label),
+            Commands::RmKey {
+                repository,
+                key_file,
+                label,
+            } => impls::rmkey(repository.repository, key_file, label),
+            Commands::Passwd { repository } => impls::passwd(repository.repository),
+            Commands::Keys { repository } => impls::keys(repository.repository),
+            Commands::TwoFactor {
+                repository,
+                disable,
+            } => impls::twofactor(repository.repository, disable),
+            Commands::Recovery { command } => match command {
+                RecoveryCommands::Split {
+                    repository,
+                    shares,
+                    threshold,
+                    #[cfg(feature = "wifiqr")]
+                    qr_dir,
+                } => impls::recovery_split(
+                    repository.repository,
+                    shares,
+                    threshold,
+                    #[cfg(feature = "wifiqr")]
+                    qr_dir,
+                ),
+                RecoveryCommands::Unlock { repository } => {
+                    impls::recovery_unlock(repository.repository)
+                }
+            },
             #[cfg(feature = "piv")]
             Commands::SetupPiv(args) => crate::piv::impls::setuppiv(args),
             #[cfg(feature = "piv")]
@@ -218,27 +781,137 @@ impl Cli {
                 repository,
                 path_prefix,
             } => impls::ls(repository.repository, path_prefix),
+            Commands::Exec {
+                repository,
+                env,
+                command,
+            } => impls::exec(repository.repository, env, command),
+            Commands::Env {
+                repository,
+                format,
+                path_prefix,
+            } => impls::env(repository.repository, format, path_prefix),
+            Commands::Render {
+                repository,
+                output,
+                force,
+                template,
+            } => impls::render(repository.repository, template, output, force),
             Commands::Get {
                 repository,
-                binary,
+                encoding,
+                previous,
+                #[cfg(feature = "wifiqr")]
+                qr_file,
                 output_method,
+                destination,
                 path,
-            } => impls::get(repository.repository, binary, output_method, path.path),
+            } => impls::get(
+                repository.repository,
+                encoding,
+                previous,
+                #[cfg(feature = "wifiqr")]
+                qr_file,
+                output_method,
+                destination,
+                path.path,
+            ),
             Commands::Set {
                 repository,
                 key_file,
                 multiline,
+                encoding,
+                generate,
+                generated,
                 path,
-            } => impls::set(repository.repository, key_file, multiline, path.path),
+            } => impls::set(
+                repository.repository,
+                key_file,
+                multiline,
+                encoding,
+                generate,
+                generated,
+                path.path,
+            ),
+            Commands::Regenerate {
+                repository,
+                generated,
+                path,
+            } => impls::regenerate(repository.repository, generated, path.path),
+            Commands::Edit { repository, path } => impls::edit(repository.repository, path.path),
+            Commands::Derive {
+                repository,
+                site,
+                login,
+                counter,
+                generated,
+                path,
+            } => impls::derive(
+                repository.repository,
+                site,
+                login,
+                counter,
+                generated,
+                path.path,
+            ),
+            Commands::Rotate {
+                repository,
+                confirm,
+                abort,
+                generated,
+                path,
+            } => impls::rotate(repository.repository, confirm, abort, generated, path.path),
+            Commands::Profile { command } => match command {
+                ProfileCommands::Set {
+                    repository,
+                    password_length,
+                    args,
+                    prefix,
+                } => impls::profile_set(repository.repository, prefix, password_length, args),
+                ProfileCommands::Rm { repository, prefix } => {
+                    impls::profile_rm(repository.repository, prefix)
+                }
+                ProfileCommands::Ls { repository } => impls::profile_ls(repository.repository),
+            },
+            Commands::Otp {
+                repository,
+                add,
+                output_method,
+                path,
+            } => impls::otp(repository.repository, add, output_method, path.path),
+            Commands::GitCredential {
+                repository,
+                operation,
+            } => impls::git_credential(repository.repository, operation),
+            Commands::DockerCredential {
+                repository,
+                operation,
+            } => impls::docker_credential(repository.repository, operation),
             Commands::Rm { repository, path } => impls::rm(repository.repository, path.path),
             Commands::Generate {
                 password_length,
                 args,
-            } => impls::generate(password_length, args),
+                passphrase,
+            } => impls::generate(password_length, args, passphrase),
             #[cfg(feature = "wifiqr")]
             Commands::Wifiqr(args) => crate::wifiqr::wifiqr_command(args),
             Commands::Export { repository } => impls::export(repository.repository),
             Commands::Import { repository, input } => impls::import(repository.repository, input),
+            #[cfg(feature = "clipboard")]
+            Commands::ClipboardDaemon { selection, timeout } => {
+                crate::init()?;
+                crate::output::run_clipboard_daemon(selection, Duration::from_secs(timeout))
+            }
+            Commands::Osc52Clear { selection, timeout } => {
+                crate::init()?;
+                crate::output::run_osc52_clear(selection, Duration::from_secs(timeout))
+            }
+            Commands::Audit {
+                repository,
+                max_age_days,
+                breach_db,
+                json,
+            } => impls::audit(repository.repository, max_age_days, breach_db, json),
         }
     }
 }
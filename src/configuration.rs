@@ -12,13 +12,17 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::output::ClipboardSelection;
 #[cfg(feature = "piv")]
 use crate::piv;
+use crate::repository::credential::PathScheme;
+use crate::repository::docker;
 use anyhow::{anyhow, bail, Error, Result};
 use bdrck::configuration as bdrck_config;
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tracing::error;
 
 static IDENTIFIER_APPLICATION: &'static str = "pwm";
@@ -33,12 +37,74 @@ static IDENTIFIER: Lazy<bdrck_config::Identifier> = Lazy::new(|| bdrck_config::I
 });
 
 pub static DEFAULT_REPOSITORY_KEY: &'static str = "default_repository";
+pub static MIN_PASSWORD_STRENGTH_KEY: &str = "min_password_strength";
+pub static BREACH_DB_KEY: &str = "breach_db";
+pub static CLIPBOARD_TIMEOUT_KEY: &str = "clipboard_timeout";
+pub static CLIPBOARD_SELECTION_KEY: &str = "clipboard_selection";
+pub static GIT_CREDENTIAL_SCHEME_KEY: &str = "git_credential_scheme";
+pub static DOCKER_CREDENTIAL_PREFIX_KEY: &str = "docker_credential_prefix";
+
+/// The minimum strength score (see `crypto::strength`) below which we warn
+/// about new passwords, unless the user has configured something else.
+pub const DEFAULT_MIN_PASSWORD_STRENGTH: u8 = 3;
+
+/// How long secrets copied to the clipboard stay there by default, in seconds.
+pub const DEFAULT_CLIPBOARD_TIMEOUT_SECS: u64 = 45;
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Configuration {
     pub default_repository: Option<PathBuf>,
     #[cfg(feature = "piv")]
     pub piv: Option<piv::Configuration>,
+    #[serde(default)]
+    pub min_password_strength: Option<u8>,
+    /// A local breached password hash corpus to check new passwords against.
+    #[serde(default)]
+    pub breach_db: Option<PathBuf>,
+    /// How long secrets copied to the clipboard stay there, in seconds.
+    #[serde(default)]
+    pub clipboard_timeout: Option<u64>,
+    #[serde(default)]
+    pub clipboard_selection: Option<ClipboardSelection>,
+    /// How 'git-credential' maps credentials to repository paths.
+    #[serde(default)]
+    pub git_credential_scheme: Option<String>,
+    /// The path prefix under which 'docker-credential' stores credentials.
+    #[serde(default)]
+    pub docker_credential_prefix: Option<String>,
+}
+
+impl Configuration {
+    /// Return the minimum acceptable password strength score, in the range
+    /// 0-4, below which new passwords should trigger a warning.
+    pub fn get_min_password_strength(&self) -> u8 {
+        self.min_password_strength
+            .unwrap_or(DEFAULT_MIN_PASSWORD_STRENGTH)
+    }
+
+    pub fn get_clipboard_timeout(&self) -> Duration {
+        Duration::from_secs(
+            self.clipboard_timeout
+                .unwrap_or(DEFAULT_CLIPBOARD_TIMEOUT_SECS),
+        )
+    }
+
+    pub fn get_clipboard_selection(&self) -> ClipboardSelection {
+        self.clipboard_selection.unwrap_or_default()
+    }
+
+    pub fn get_git_credential_scheme(&self) -> Result<PathScheme> {
+        match &self.git_credential_scheme {
+            None => Ok(PathScheme::default()),
+            Some(s) => s.parse(),
+        }
+    }
+
+    pub fn get_docker_credential_prefix(&self) -> &str {
+        self.docker_credential_prefix
+            .as_deref()
+            .unwrap_or(docker::DEFAULT_PREFIX)
+    }
 }
 
 pub struct SingletonHandle;
@@ -79,6 +145,52 @@ pub fn set(key: &str, value: &str) -> Result<()> {
             let mut config = instance.get().clone();
             if key == DEFAULT_REPOSITORY_KEY {
                 config.default_repository = Some(value.into());
+            } else if key == BREACH_DB_KEY {
+                config.breach_db = match value.is_empty() {
+                    false => Some(value.into()),
+                    true => None,
+                };
+            } else if key == CLIPBOARD_TIMEOUT_KEY {
+                match value.parse::<u64>() {
+                    Ok(v) => config.clipboard_timeout = Some(v),
+                    Err(_) => {
+                        return Some(anyhow!(
+                            "{} must be a number of seconds",
+                            CLIPBOARD_TIMEOUT_KEY
+                        ))
+                    }
+                }
+            } else if key == CLIPBOARD_SELECTION_KEY {
+                match value.parse::<ClipboardSelection>() {
+                    Ok(v) => config.clipboard_selection = Some(v),
+                    Err(e) => return Some(e),
+                }
+            } else if key == GIT_CREDENTIAL_SCHEME_KEY {
+                config.git_credential_scheme = match value.is_empty() {
+                    false => match value.parse::<PathScheme>() {
+                        Ok(_) => Some(value.to_owned()),
+                        Err(e) => return Some(e),
+                    },
+                    true => None,
+                };
+            } else if key == DOCKER_CREDENTIAL_PREFIX_KEY {
+                config.docker_credential_prefix = match value.is_empty() {
+                    false => Some(value.to_owned()),
+                    true => None,
+                };
+            } else if key == MIN_PASSWORD_STRENGTH_KEY {
+                match value.parse::<u8>() {
+                    Ok(v) if v <= crate::crypto::strength::MAX_SCORE => {
+                        config.min_password_strength = Some(v)
+                    }
+                    _ => {
+                        return Some(anyhow!(
+                            "{} must be an integer between 0 and {}",
+                            MIN_PASSWORD_STRENGTH_KEY,
+                            crate::crypto::strength::MAX_SCORE
+                        ))
+                    }
+                }
             } else {
                 return Some(anyhow!("invalid configuration key '{}'", key));
             }
@@ -107,6 +219,24 @@ pub fn get_value_as_str(key: &str) -> Result<String> {
             },
             None => String::new(),
         })
+    } else if key == BREACH_DB_KEY {
+        Ok(match config.breach_db {
+            Some(v) => match v.as_path().to_str() {
+                None => bail!("{} is not a valid UTF-8 string", BREACH_DB_KEY),
+                Some(v) => v.to_owned(),
+            },
+            None => String::new(),
+        })
+    } else if key == CLIPBOARD_TIMEOUT_KEY {
+        Ok(config.get_clipboard_timeout().as_secs().to_string())
+    } else if key == CLIPBOARD_SELECTION_KEY {
+        Ok(config.get_clipboard_selection().to_string())
+    } else if key == GIT_CREDENTIAL_SCHEME_KEY {
+        Ok(config.get_git_credential_scheme()?.to_string())
+    } else if key == DOCKER_CREDENTIAL_PREFIX_KEY {
+        Ok(config.get_docker_credential_prefix().to_owned())
+    } else if key == MIN_PASSWORD_STRENGTH_KEY {
+        Ok(config.get_min_password_strength().to_string())
     } else {
         bail!("invalid configuration key '{}'", key);
     }
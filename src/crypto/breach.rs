@@ -0,0 +1,113 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Offline lookups in a corpus of breached password hashes, in the format
+//! distributed by Have I Been Pwned: one `SHA1HASH:COUNT` line per password,
+//! with hashes in uppercase hexadecimal and lines sorted by hash. The corpus
+//! is far too large to load into memory, so we binary search it on disk.
+
+use anyhow::{bail, Result};
+use bdrck::crypto::secret::Secret;
+use sha1::{Digest, Sha1};
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::Path;
+
+/// Once the search range is this small (in bytes), just scan it linearly.
+const LINEAR_SCAN_THRESHOLD: u64 = 4096;
+
+/// The length of a hex-encoded SHA-1 hash.
+const HASH_LENGTH: usize = 40;
+
+pub struct BreachDatabase {
+    reader: BufReader<File>,
+    len: u64,
+}
+
+impl BreachDatabase {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        Ok(BreachDatabase {
+            reader: BufReader::new(file),
+            len,
+        })
+    }
+
+    /// Read the line starting at the current position, returning its hash and
+    /// count. Returns None at the end of the file.
+    fn read_entry(&mut self) -> Result<Option<(String, u64)>> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        let (hash, count) = match line.split_once(':') {
+            Some((hash, count)) if hash.len() == HASH_LENGTH => (hash, count),
+            _ => bail!("invalid breach database line '{}'", line),
+        };
+        Ok(Some((hash.to_ascii_uppercase(), count.parse()?)))
+    }
+
+    /// Return the offset of the first line which starts at or after `offset`.
+    fn next_line_start(&mut self, offset: u64) -> Result<u64> {
+        if offset == 0 {
+            return Ok(0);
+        }
+        self.reader.seek(SeekFrom::Start(offset - 1))?;
+        let mut skipped = Vec::new();
+        Ok(offset - 1 + self.reader.read_until(b'\n', &mut skipped)? as u64)
+    }
+
+    /// Look up the given uppercase hex SHA-1 hash, returning the number of
+    /// times it was seen in breaches, or None if it wasn't.
+    pub fn lookup_hash(&mut self, hash: &str) -> Result<Option<u64>> {
+        let hash = hash.to_ascii_uppercase();
+
+        // Invariant: `lo` is the start of a line whose hash is less than the
+        // target (or the start of the file), and no line at or after `hi`
+        // matches.
+        let mut lo: u64 = 0;
+        let mut hi: u64 = self.len;
+        while hi - lo > LINEAR_SCAN_THRESHOLD {
+            let start = self.next_line_start(lo + (hi - lo) / 2)?;
+            if start >= hi {
+                break;
+            }
+            match self.read_entry()? {
+                None => hi = start,
+                Some((h, _)) if h.as_str() < hash.as_str() => lo = start,
+                Some(_) => hi = start,
+            }
+        }
+
+        self.reader.seek(SeekFrom::Start(lo))?;
+        while let Some((h, count)) = self.read_entry()? {
+            match h.cmp(&hash) {
+                Ordering::Less => continue,
+                Ordering::Equal => return Ok(Some(count)),
+                Ordering::Greater => break,
+            }
+        }
+        Ok(None)
+    }
+
+    /// Look up the given password, returning the number of times it was seen
+    /// in breaches, or None if it wasn't.
+    pub fn lookup(&mut self, password: &Secret) -> Result<Option<u64>> {
+        let hash = Sha1::digest(unsafe { password.as_slice() });
+        self.lookup_hash(&data_encoding::HEXUPPER.encode(&hash))
+    }
+}
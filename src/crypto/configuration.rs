@@ -13,20 +13,89 @@
 // limitations under the License.
 
 use crate::crypto::key::{KeyError, PwmKey};
-use crate::util::unwrap_password_or_prompt;
-use anyhow::Result;
+use crate::crypto::pwgen::GeneratorSettings;
+use crate::util::{self, unwrap_password_or_prompt};
+use anyhow::{bail, Result};
 use bdrck::configuration as bdrck_config;
 use bdrck::crypto::digest::*;
 use bdrck::crypto::key::*;
 use bdrck::crypto::secret::Secret;
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[cfg(not(feature = "piv"))]
-fn deserialize_piv_keys_panic<'de, D: serde::Deserializer<'de>, T>(
-    _: D,
-) -> std::result::Result<T, D::Error> {
-    panic!("PIV feature is disabled; refusing to load PIV configuration");
+fn serialize_piv_keys_placeholder<S: serde::Serializer>(
+    _: &std::marker::PhantomData<()>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.collect_seq(std::iter::empty::<()>())
+}
+
+#[cfg(not(feature = "piv"))]
+fn deserialize_piv_keys_panic<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<std::marker::PhantomData<()>, D::Error> {
+    let keys: Vec<serde::de::IgnoredAny> = Deserialize::deserialize(deserializer)?;
+    if !keys.is_empty() {
+        panic!("PIV feature is disabled; refusing to load PIV configuration");
+    }
+    Ok(std::marker::PhantomData)
+}
+
+/// A key file which has been added to a repository as a wrapping key. The
+/// file's contents are used to derive the key, so we only record where to find
+/// it and the digest of the key it produced.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct KeyFileAssociation {
+    pub(crate) path: PathBuf,
+    pub(crate) wrapping_key_digest: Digest,
+}
+
+/// Descriptive information about a wrapping key, which the key store itself
+/// doesn't record.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct KeyMetadata {
+    pub(crate) wrapping_key_digest: Digest,
+    pub(crate) label: Option<String>,
+    /// When this key was added, in seconds since the UNIX epoch.
+    pub(crate) added: u64,
+}
+
+/// The kinds of wrapping keys a repository can have.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KeyType {
+    Password,
+    /// A password combined with a PIV device.
+    TwoFactor,
+    Piv,
+    KeyFile,
+    Recovery,
+}
+
+impl fmt::Display for KeyType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                KeyType::Password => "password",
+                KeyType::TwoFactor => "password+piv",
+                KeyType::Piv => "piv",
+                KeyType::KeyFile => "key file",
+                KeyType::Recovery => "recovery",
+            }
+        )
+    }
+}
+
+/// Settings used to generate passwords for all paths starting with a prefix,
+/// so e.g. each website's password rules only need to be specified once.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GenerationProfile {
+    pub path_prefix: String,
+    pub settings: GeneratorSettings,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -41,15 +110,38 @@ pub struct Configuration {
     piv_keys: Vec<crate::piv::util::PivKeyAssociation>,
 
     #[cfg(not(feature = "piv"))]
-    // We must default in order to load structures which omit this (all should).
+    // We must default in order to load structures which omit this.
     #[serde(default)]
-    // Don't write this field out when serializing this structure (it's just a
-    // placeholder).
-    #[serde(skip_serializing)]
-    // If we actually find a structure with this field, instead of deserializing
-    // it, just panic instead (it's not supported without the PIV feature).
+    // Write this field out as an empty list, so any fields which follow it
+    // keep the same position regardless of which features are enabled.
+    #[serde(serialize_with = "serialize_piv_keys_placeholder")]
+    // If we actually find a structure with PIV keys in this field, instead of
+    // deserializing it, just panic instead (it's not supported without the
+    // PIV feature).
     #[serde(deserialize_with = "deserialize_piv_keys_panic")]
     piv_keys: std::marker::PhantomData<()>,
+
+    // Default to an empty Vec if the structure didn't previously have this.
+    #[serde(default)]
+    key_files: Vec<KeyFileAssociation>,
+
+    // If set, this repository can only be unlocked with a password *and* a
+    // PIV device together.
+    #[serde(default)]
+    require_two_factor: bool,
+
+    // The digest of the wrapping key derived from this repository's recovery
+    // secret (which is split into shares), if one has been generated.
+    #[serde(default)]
+    recovery_key_digest: Option<Digest>,
+
+    // Default to an empty Vec if the structure didn't previously have this.
+    #[serde(default)]
+    key_metadata: Vec<KeyMetadata>,
+
+    // Default to an empty Vec if the structure didn't previously have this.
+    #[serde(default)]
+    generation_profiles: Vec<GenerationProfile>,
 }
 
 impl Configuration {
@@ -63,6 +155,12 @@ impl Configuration {
             piv_keys: Vec::new(),
             #[cfg(not(feature = "piv"))]
             piv_keys: std::marker::PhantomData,
+
+            key_files: Vec::new(),
+            require_two_factor: false,
+            recovery_key_digest: None,
+            key_metadata: Vec::new(),
+            generation_profiles: Vec::new(),
         }
     }
 
@@ -83,16 +181,167 @@ impl Configuration {
         self.piv_keys.as_slice()
     }
 
-    #[cfg(feature = "piv")]
-    pub(crate) fn set_piv_keys(&mut self, keys: Vec<crate::piv::util::PivKeyAssociation>) {
-        self.piv_keys = keys;
-    }
-
     #[cfg(feature = "piv")]
     pub(crate) fn add_piv_key(&mut self, assoc: crate::piv::util::PivKeyAssociation) {
         self.piv_keys.push(assoc);
     }
 
+    /// Point the PIV device association(s) for one wrapping key at another.
+    /// This is used when a PIV device's key is combined with (or separated
+    /// from) a password for two-factor unlocking.
+    pub(crate) fn rename_piv_key(&mut self, from: &Digest, to: Digest) {
+        #[cfg(feature = "piv")]
+        for k in self.piv_keys.iter_mut() {
+            if k.wrapping_key_digest == *from {
+                k.wrapping_key_digest = to.clone();
+            }
+        }
+        #[cfg(not(feature = "piv"))]
+        let _ = (from, to);
+    }
+
+    pub(crate) fn get_key_files(&self) -> &[KeyFileAssociation] {
+        self.key_files.as_slice()
+    }
+
+    pub(crate) fn add_key_file(&mut self, assoc: KeyFileAssociation) {
+        self.key_files.push(assoc);
+    }
+
+    pub fn requires_two_factor(&self) -> bool {
+        self.require_two_factor
+    }
+
+    pub(crate) fn set_requires_two_factor(&mut self, required: bool) {
+        self.require_two_factor = required;
+    }
+
+    pub(crate) fn get_recovery_key_digest(&self) -> Option<&Digest> {
+        self.recovery_key_digest.as_ref()
+    }
+
+    pub(crate) fn set_recovery_key_digest(&mut self, digest: Option<Digest>) {
+        self.recovery_key_digest = digest;
+    }
+
+    pub(crate) fn get_key_metadata(&self, digest: &Digest) -> Option<&KeyMetadata> {
+        self.key_metadata
+            .iter()
+            .find(|m| m.wrapping_key_digest == *digest)
+    }
+
+    /// Record that the key with the given digest was just added, with the
+    /// given (optional) label. Labels must be unique within a repository.
+    pub(crate) fn add_key_metadata(&mut self, digest: Digest, label: Option<String>) -> Result<()> {
+        if let Some(label) = label.as_ref() {
+            if self.find_key_by_label(label).is_some() {
+                bail!("a key with the label '{}' already exists", label);
+            }
+        }
+
+        self.key_metadata
+            .retain(|m| m.wrapping_key_digest != digest);
+        self.key_metadata.push(KeyMetadata {
+            wrapping_key_digest: digest,
+            label,
+            added: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        });
+        Ok(())
+    }
+
+    /// Transfer the metadata (label, date added) for one key to another. This
+    /// is used when a key is replaced with an equivalent one.
+    pub(crate) fn rename_key_metadata(&mut self, from: &Digest, to: Digest) {
+        for m in self.key_metadata.iter_mut() {
+            if m.wrapping_key_digest == *from {
+                m.wrapping_key_digest = to.clone();
+            }
+        }
+    }
+
+    pub(crate) fn find_key_by_label(&self, label: &str) -> Option<&Digest> {
+        self.key_metadata
+            .iter()
+            .find(|m| m.label.as_deref() == Some(label))
+            .map(|m| &m.wrapping_key_digest)
+    }
+
+    /// Remove all information we have about the key with the given digest,
+    /// after it has been removed from the key store.
+    pub(crate) fn forget_key(&mut self, digest: &Digest) {
+        #[cfg(feature = "piv")]
+        self.piv_keys.retain(|k| k.wrapping_key_digest != *digest);
+        self.key_files.retain(|k| k.wrapping_key_digest != *digest);
+        if self.recovery_key_digest.as_ref() == Some(digest) {
+            self.recovery_key_digest = None;
+        }
+        self.key_metadata
+            .retain(|m| m.wrapping_key_digest != *digest);
+    }
+
+    /// Return what kind of key the key with the given digest is, based on the
+    /// associations we've recorded. Any key we don't know anything else about
+    /// must be a password.
+    pub fn get_key_type(&self, digest: &Digest) -> KeyType {
+        #[cfg(feature = "piv")]
+        if self
+            .piv_keys
+            .iter()
+            .any(|k| k.wrapping_key_digest == *digest)
+        {
+            return match self.require_two_factor {
+                false => KeyType::Piv,
+                true => KeyType::TwoFactor,
+            };
+        }
+        if self
+            .key_files
+            .iter()
+            .any(|k| k.wrapping_key_digest == *digest)
+        {
+            return KeyType::KeyFile;
+        }
+        if self.recovery_key_digest.as_ref() == Some(digest) {
+            return KeyType::Recovery;
+        }
+        match self.require_two_factor {
+            false => KeyType::Password,
+            true => KeyType::TwoFactor,
+        }
+    }
+
+    pub fn get_generation_profiles(&self) -> &[GenerationProfile] {
+        self.generation_profiles.as_slice()
+    }
+
+    /// Add a generation profile, replacing any existing profile with the same
+    /// prefix.
+    pub fn set_generation_profile(&mut self, path_prefix: String, settings: GeneratorSettings) {
+        self.remove_generation_profile(&path_prefix);
+        self.generation_profiles.push(GenerationProfile {
+            path_prefix,
+            settings,
+        });
+    }
+
+    /// Remove the generation profile with the given prefix, returning whether
+    /// or not one was found.
+    pub fn remove_generation_profile(&mut self, path_prefix: &str) -> bool {
+        let original_len = self.generation_profiles.len();
+        self.generation_profiles
+            .retain(|p| p.path_prefix != path_prefix);
+        original_len != self.generation_profiles.len()
+    }
+
+    /// Return the generation profile which applies to the given path, if any.
+    /// If several profiles' prefixes match, the longest (most specific) wins.
+    pub fn find_generation_profile(&self, path: &str) -> Option<&GenerationProfile> {
+        self.generation_profiles
+            .iter()
+            .filter(|p| path.starts_with(p.path_prefix.as_str()))
+            .max_by_key(|p| p.path_prefix.len())
+    }
+
     pub fn get_password_key(
         &self,
         password: Option<Secret>,
@@ -103,6 +352,18 @@ impl Configuration {
         let key = Key::new_password(&password, &self.salt, self.ops_limit, self.mem_limit)?;
         Ok(PwmKey::from(key))
     }
+
+    /// Derive a wrapping key from the contents of the given key file. This
+    /// uses the same key derivation parameters as password keys, so the file
+    /// can contain arbitrary (binary) data.
+    pub fn get_key_file_key<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<impl AbstractKey<Error = KeyError>> {
+        let contents = util::secret::load_file(path)?;
+        let key = Key::new_password(&contents, &self.salt, self.ops_limit, self.mem_limit)?;
+        Ok(PwmKey::from(key))
+    }
 }
 
 impl Default for Configuration {
@@ -0,0 +1,120 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::crypto::pwgen::GeneratorSettings;
+use crate::crypto::rng::DeterministicGenerator;
+use anyhow::{bail, Result};
+use bdrck::crypto::secret::Secret;
+use hmac::digest::KeyInit;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+/// Derivation entries are stored as this header, followed by the entry's
+/// parameters as JSON.
+static DERIVATION_HEADER: &str = "pwm-derivation:";
+
+/// Domain separation for the derivation KDF. This must never change, or every
+/// derived password would change along with it.
+static DERIVATION_CONTEXT: &str = "pwm site password derivation v1";
+
+/// The length of the key derived from the master key, i.e. SHA-256's output.
+const DERIVATION_KEY_LENGTH: usize = 32;
+
+/// Derive the key used for password derivation from the repository's master
+/// key, with HKDF-SHA256 (RFC 5869): no salt, `DERIVATION_CONTEXT` as the
+/// info, and a single block of output.
+fn derivation_key(master_key: &Secret) -> Result<Secret> {
+    let mut extract = <Hmac<Sha256> as KeyInit>::new_from_slice(&[0; DERIVATION_KEY_LENGTH])?;
+    extract.update(unsafe { master_key.as_slice() });
+    let mut prk = extract.finalize().into_bytes();
+
+    let mut expand = <Hmac<Sha256> as KeyInit>::new_from_slice(prk.as_slice())?;
+    prk.as_mut_slice().fill(0);
+    expand.update(DERIVATION_CONTEXT.as_bytes());
+    expand.update(&[1]);
+    let mut okm = expand.finalize().into_bytes();
+
+    let mut key = Secret::with_len(DERIVATION_KEY_LENGTH)?;
+    unsafe { key.as_mut_slice() }.copy_from_slice(okm.as_slice());
+    okm.as_mut_slice().fill(0);
+    Ok(key)
+}
+
+/// A stateless, LessPass-style password entry. Rather than storing a password,
+/// we store only the parameters needed to derive it from the repository's
+/// master key. Changing any parameter (e.g., bumping the counter) yields a
+/// completely different password.
+///
+/// Passwords are derived as follows, and this must never change:
+///
+/// 1. The derivation key is HKDF-SHA256 of the master key's raw bytes, with no
+///    salt and `DERIVATION_CONTEXT` as the info.
+/// 2. The seed is HMAC-SHA256, keyed with the derivation key, of the site and
+///    login (each prefixed with its length as a big-endian u64), then the
+///    counter as a big-endian u64.
+/// 3. The seed keys a `DeterministicGenerator`, whose output is
+///    HMAC-SHA256(seed, block counter) for block counters 0, 1, 2, ...
+/// 4. Each character is chosen from the settings' alphabet with
+///    `rng::uniform_index`, i.e. by rejection sampling that output. Candidates
+///    which don't satisfy the password policy are discarded, and generation
+///    continues from the same stream.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Derivation {
+    pub site: String,
+    pub login: String,
+    pub counter: u64,
+    pub settings: GeneratorSettings,
+}
+
+impl Derivation {
+    /// Returns true if the given stored data looks like a derivation entry.
+    pub fn is_derivation(secret: &Secret) -> bool {
+        unsafe { secret.as_slice() }.starts_with(DERIVATION_HEADER.as_bytes())
+    }
+
+    pub fn from_secret(secret: &Secret) -> Result<Derivation> {
+        let data = unsafe { secret.as_slice() };
+        if !Self::is_derivation(secret) {
+            bail!("stored data is not a derived password entry");
+        }
+        Ok(serde_json::from_slice(&data[DERIVATION_HEADER.len()..])?)
+    }
+
+    pub fn to_secret(&self) -> Result<Secret> {
+        let mut data = DERIVATION_HEADER.as_bytes().to_vec();
+        serde_json::to_writer(&mut data, self)?;
+        let mut secret = Secret::with_len(data.len())?;
+        unsafe { secret.as_mut_slice() }.copy_from_slice(data.as_slice());
+        Ok(secret)
+    }
+
+    /// Derive this entry's password from the given key material, which should
+    /// be the repository's raw master key.
+    pub fn derive(&self, master_key: &Secret) -> Result<Secret> {
+        let key = derivation_key(master_key)?;
+        let mut mac = <Hmac<Sha256> as KeyInit>::new_from_slice(unsafe { key.as_slice() })?;
+        // Length-prefix the variable length fields, so e.g. site "ab" + login
+        // "c" is distinct from site "a" + login "bc".
+        for field in [self.site.as_bytes(), self.login.as_bytes()] {
+            mac.update(&(field.len() as u64).to_be_bytes());
+            mac.update(field);
+        }
+        mac.update(&self.counter.to_be_bytes());
+
+        let seed = mac.finalize().into_bytes();
+        self.settings
+            .generate_with_rng(&mut DeterministicGenerator::new(&seed))
+    }
+}
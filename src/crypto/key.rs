@@ -12,8 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use anyhow::Error;
-use bdrck::crypto::digest::Digest;
+use anyhow::{anyhow, Error};
+use bdrck::crypto::digest::{Digest, DIGEST_BYTES};
 use bdrck::crypto::key::{AbstractKey, Nonce};
 use bdrck::crypto::secret::Secret;
 use std::fmt;
@@ -93,3 +93,176 @@ impl<E: Into<Error>, K: AbstractKey<Error = E>> From<K> for PwmKey<E, K> {
         PwmKey::<E, K>(k)
     }
 }
+
+fn to_key_error<E: Into<Error>>(e: E) -> KeyError {
+    KeyError::from(e.into())
+}
+
+/// A CompositeKey is a wrapping key which requires *both* of two other keys.
+/// Data is encrypted with the first key, and the resulting ciphertext is then
+/// encrypted again with the second key, so neither key is sufficient on its
+/// own to decrypt anything.
+pub struct CompositeKey<A: AbstractKey<Error = KeyError>, B: AbstractKey<Error = KeyError>> {
+    first: A,
+    second: B,
+}
+
+impl<A: AbstractKey<Error = KeyError>, B: AbstractKey<Error = KeyError>> CompositeKey<A, B> {
+    pub fn new(first: A, second: B) -> Self {
+        CompositeKey { first, second }
+    }
+}
+
+impl<A: AbstractKey<Error = KeyError>, B: AbstractKey<Error = KeyError>> AbstractKey
+    for CompositeKey<A, B>
+{
+    type Error = KeyError;
+
+    fn get_digest(&self) -> Digest {
+        // Digest doesn't expose its raw bytes, so hash the serialized pair instead.
+        let digests = rmp_serde::to_vec(&(self.first.get_digest(), self.second.get_digest()))
+            .expect("serializing digests failed");
+        Digest::from_bytes(digests.as_slice())
+    }
+
+    fn serialize(&self) -> KeyResult<Secret> {
+        // Composite keys are only ever used as wrapping keys, which are never
+        // serialized. Refuse, rather than writing out both sub-keys together.
+        Err(KeyError::from(anyhow!(
+            "composite keys cannot be serialized"
+        )))
+    }
+
+    fn deserialize(_: Secret) -> KeyResult<Self> {
+        Err(KeyError::from(anyhow!(
+            "composite keys cannot be deserialized"
+        )))
+    }
+
+    fn encrypt(
+        &self,
+        plaintext: &Secret,
+        nonce: Option<Nonce>,
+    ) -> KeyResult<(Option<Nonce>, Vec<u8>)> {
+        let intermediate = self.first.encrypt(plaintext, None)?;
+        let packed = rmp_serde::to_vec(&intermediate).map_err(to_key_error)?;
+        let mut packed_secret = Secret::with_len(packed.len()).map_err(to_key_error)?;
+        unsafe {
+            packed_secret
+                .as_mut_slice()
+                .copy_from_slice(packed.as_slice());
+        }
+        self.second.encrypt(&packed_secret, nonce)
+    }
+
+    fn decrypt(&self, nonce: Option<&Nonce>, ciphertext: &[u8]) -> KeyResult<Secret> {
+        let packed = self.second.decrypt(nonce, ciphertext)?;
+        let (intermediate_nonce, intermediate): (Option<Nonce>, Vec<u8>) =
+            rmp_serde::from_slice(unsafe { packed.as_slice() }).map_err(to_key_error)?;
+        self.first
+            .decrypt(intermediate_nonce.as_ref(), intermediate.as_slice())
+    }
+}
+
+/// A KeyRef lets a borrowed key be used anywhere an owned key is expected,
+/// e.g. to build several CompositeKeys sharing the same (expensive to read)
+/// PIV key.
+pub struct KeyRef<'a, K: AbstractKey<Error = KeyError>>(&'a K);
+
+impl<'a, K: AbstractKey<Error = KeyError>> KeyRef<'a, K> {
+    pub fn new(key: &'a K) -> Self {
+        KeyRef(key)
+    }
+}
+
+impl<'a, K: AbstractKey<Error = KeyError>> AbstractKey for KeyRef<'a, K> {
+    type Error = KeyError;
+
+    fn get_digest(&self) -> Digest {
+        self.0.get_digest()
+    }
+
+    fn serialize(&self) -> KeyResult<Secret> {
+        self.0.serialize()
+    }
+
+    fn deserialize(_: Secret) -> KeyResult<Self> {
+        Err(KeyError::from(anyhow!(
+            "borrowed keys cannot be deserialized"
+        )))
+    }
+
+    fn encrypt(
+        &self,
+        plaintext: &Secret,
+        nonce: Option<Nonce>,
+    ) -> KeyResult<(Option<Nonce>, Vec<u8>)> {
+        self.0.encrypt(plaintext, nonce)
+    }
+
+    fn decrypt(&self, nonce: Option<&Nonce>, ciphertext: &[u8]) -> KeyResult<Secret> {
+        self.0.decrypt(nonce, ciphertext)
+    }
+}
+
+/// A DigestKey stands in for a wrapping key we no longer have, but whose digest
+/// we remember. It can't encrypt or decrypt anything, but it's sufficient to
+/// identify (and e.g. remove) the real key in a key store.
+pub struct DigestKey(Digest);
+
+impl DigestKey {
+    pub fn new(digest: Digest) -> Self {
+        DigestKey(digest)
+    }
+}
+
+impl AbstractKey for DigestKey {
+    type Error = KeyError;
+
+    fn get_digest(&self) -> Digest {
+        self.0.clone()
+    }
+
+    fn serialize(&self) -> KeyResult<Secret> {
+        Err(KeyError::from(anyhow!(
+            "digest-only keys cannot be serialized"
+        )))
+    }
+
+    fn deserialize(_: Secret) -> KeyResult<Self> {
+        Err(KeyError::from(anyhow!(
+            "digest-only keys cannot be deserialized"
+        )))
+    }
+
+    fn encrypt(&self, _: &Secret, _: Option<Nonce>) -> KeyResult<(Option<Nonce>, Vec<u8>)> {
+        Err(KeyError::from(anyhow!("digest-only keys cannot encrypt")))
+    }
+
+    fn decrypt(&self, _: Option<&Nonce>, _: &[u8]) -> KeyResult<Secret> {
+        Err(KeyError::from(anyhow!("digest-only keys cannot decrypt")))
+    }
+}
+
+/// The number of leading digest bytes included in a fingerprint.
+const FINGERPRINT_BYTES: usize = 8;
+
+/// Return the raw bytes of the given digest.
+pub fn digest_bytes(digest: &Digest) -> [u8; DIGEST_BYTES] {
+    // Digest doesn't expose its raw bytes, but it serializes as a plain byte
+    // sequence, so we can recover them that way.
+    let bytes: Vec<u8> = rmp_serde::from_slice(
+        rmp_serde::to_vec(digest)
+            .expect("serializing digest failed")
+            .as_slice(),
+    )
+    .expect("deserializing digest failed");
+    bytes
+        .try_into()
+        .expect("serialized digest has the wrong length")
+}
+
+/// Return a short, human-readable hex fingerprint identifying the given digest.
+pub fn digest_fingerprint(digest: &Digest) -> String {
+    data_encoding::HEXLOWER.encode(&digest_bytes(digest)[..FINGERPRINT_BYTES])
+}
@@ -12,8 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod breach;
 pub mod configuration;
+pub mod derive;
 pub mod key;
+pub mod otp;
 pub mod padding;
 pub mod pwgen;
 pub mod rng;
+pub mod shamir;
+pub mod strength;
+pub mod wordlist;
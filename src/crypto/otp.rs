@@ -0,0 +1,299 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, bail, Result};
+use bdrck::crypto::secret::Secret;
+use data_encoding::BASE32_NOPAD;
+use hmac::digest::KeyInit;
+use hmac::{Hmac, Mac};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+use url::form_urlencoded;
+
+static OTPAUTH_SCHEME: &str = "otpauth";
+
+/// Characters which must be escaped in an otpauth URI's label.
+const LABEL_ESCAPE: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+const DEFAULT_DIGITS: u32 = 6;
+const DEFAULT_PERIOD: u64 = 30;
+
+/// The HMAC hash function used to generate codes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Algorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Algorithm::Sha1 => "SHA1",
+                Algorithm::Sha256 => "SHA256",
+                Algorithm::Sha512 => "SHA512",
+            }
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OtpKind {
+    /// Time-based codes (RFC 6238), which change every `period` seconds.
+    Totp { period: u64 },
+    /// Counter-based codes (RFC 4226). The counter must be incremented (and
+    /// saved) every time a code is generated.
+    Hotp { counter: u64 },
+}
+
+/// A one-time password generator, as described by an `otpauth://` URI. This
+/// is how OTP entries are stored in a repository, so they are also readable
+/// (and importable elsewhere) with a normal `get`.
+pub struct Otp {
+    pub kind: OtpKind,
+    /// The URI's label, typically "Issuer:account".
+    pub label: String,
+    pub issuer: Option<String>,
+    pub secret: Secret,
+    pub algorithm: Algorithm,
+    pub digits: u32,
+}
+
+/// Decode a (percent-encoded, as it appears in a URI) base32 OTP secret. This
+/// is done directly into `Secret`s, so no copies of the secret are left behind
+/// in ordinary memory.
+fn decode_secret(encoded: &str) -> Result<Secret> {
+    // Authenticators are lenient about case, whitespace, and padding, since
+    // secrets are often typed in by hand.
+    let mut normalized = Secret::with_len(encoded.len())?;
+    let mut len = 0;
+    for c in percent_decode_str(encoded) {
+        if c.is_ascii_whitespace() || c == b'+' || c == b'=' {
+            continue;
+        }
+        unsafe { normalized.as_mut_slice()[len] = c.to_ascii_uppercase() };
+        len += 1;
+    }
+    let normalized = &unsafe { normalized.as_slice() }[..len];
+
+    let invalid = || anyhow!("invalid OTP secret, expected base32 data");
+    let mut secret = Secret::with_len(BASE32_NOPAD.decode_len(len).map_err(|_| invalid())?)?;
+    BASE32_NOPAD
+        .decode_mut(normalized, unsafe { secret.as_mut_slice() })
+        .map_err(|_| invalid())?;
+    Ok(secret)
+}
+
+/// Decode an ordinary (non-secret) URI query component.
+fn decode_component(encoded: &str) -> Result<String> {
+    Ok(percent_decode_str(&encoded.replace('+', " "))
+        .decode_utf8()?
+        .into_owned())
+}
+
+fn hmac<M: Mac + KeyInit>(key: &[u8], message: &[u8]) -> Result<Vec<u8>> {
+    let mut mac = match <M as KeyInit>::new_from_slice(key) {
+        Ok(m) => m,
+        Err(_) => bail!("invalid OTP secret length"),
+    };
+    mac.update(message);
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+impl Otp {
+    /// Returns true if the given stored secret looks like an OTP entry.
+    pub fn is_otp(secret: &Secret) -> bool {
+        unsafe { secret.as_slice() }.starts_with(format!("{}://", OTPAUTH_SCHEME).as_bytes())
+    }
+
+    /// Parse an `otpauth://` URI, as exported by most authenticator apps or
+    /// encoded in the QR codes services display during MFA setup.
+    pub fn from_uri(uri: &str) -> Result<Otp> {
+        // We parse the URI by hand, rather than with the url crate, so the
+        // secret is never copied anywhere but into a `Secret`.
+        let rest = match uri.trim().split_once("://") {
+            Some((scheme, rest)) if scheme.eq_ignore_ascii_case(OTPAUTH_SCHEME) => rest,
+            _ => bail!("invalid OTP URI, expected '{}' scheme", OTPAUTH_SCHEME),
+        };
+        let rest = rest.split('#').next().unwrap_or_default();
+        let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let (otp_type, label) = path.split_once('/').unwrap_or((path, ""));
+
+        let mut secret: Option<Secret> = None;
+        let mut issuer: Option<String> = None;
+        let mut algorithm = Algorithm::Sha1;
+        let mut digits = DEFAULT_DIGITS;
+        let mut period = DEFAULT_PERIOD;
+        let mut counter: Option<u64> = None;
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let key = decode_component(key)?;
+            if key == "secret" {
+                secret = Some(decode_secret(value)?);
+                continue;
+            }
+            let value = decode_component(value)?;
+            match key.as_str() {
+                "issuer" => issuer = Some(value),
+                "algorithm" => {
+                    algorithm = match value.to_uppercase().as_str() {
+                        "SHA1" => Algorithm::Sha1,
+                        "SHA256" => Algorithm::Sha256,
+                        "SHA512" => Algorithm::Sha512,
+                        _ => bail!("unsupported OTP algorithm '{}'", value),
+                    }
+                }
+                "digits" => digits = value.parse()?,
+                "period" => period = value.parse()?,
+                "counter" => counter = Some(value.parse()?),
+                // Ignore any other parameters (e.g. "image"), like other
+                // authenticators do.
+                _ => {}
+            }
+        }
+
+        let kind = match otp_type {
+            "totp" => {
+                if period == 0 {
+                    bail!("invalid OTP period 0");
+                }
+                OtpKind::Totp { period }
+            }
+            "hotp" => match counter {
+                None => bail!("HOTP URIs must include a counter"),
+                Some(counter) => OtpKind::Hotp { counter },
+            },
+            _ => bail!("invalid OTP URI, expected 'totp' or 'hotp' type"),
+        };
+        let secret = match secret {
+            None => bail!("invalid OTP URI, no secret found"),
+            Some(s) => s,
+        };
+        if !(6..=8).contains(&digits) {
+            bail!("unsupported number of OTP digits {}", digits);
+        }
+
+        Ok(Otp {
+            kind,
+            label: percent_decode_str(label).decode_utf8()?.into_owned(),
+            issuer,
+            secret,
+            algorithm,
+            digits,
+        })
+    }
+
+    pub fn from_secret(secret: &Secret) -> Result<Otp> {
+        Self::from_uri(std::str::from_utf8(unsafe { secret.as_slice() })?)
+    }
+
+    /// Encode this generator as a canonical `otpauth://` URI.
+    pub fn to_uri(&self) -> Result<Secret> {
+        // Only the secret is sensitive, so the rest of the URI is built
+        // normally, and the secret is encoded directly into the result.
+        let prefix = format!(
+            "{}://{}/{}?secret=",
+            OTPAUTH_SCHEME,
+            match self.kind {
+                OtpKind::Totp { .. } => "totp",
+                OtpKind::Hotp { .. } => "hotp",
+            },
+            utf8_percent_encode(&self.label, LABEL_ESCAPE)
+        );
+        let mut suffix = String::new();
+        if let Some(issuer) = self.issuer.as_ref() {
+            suffix.push_str("&issuer=");
+            suffix.extend(form_urlencoded::byte_serialize(issuer.as_bytes()));
+        }
+        suffix.push_str(&format!(
+            "&algorithm={}&digits={}",
+            self.algorithm, self.digits
+        ));
+        suffix.push_str(&match self.kind {
+            OtpKind::Totp { period } => format!("&period={}", period),
+            OtpKind::Hotp { counter } => format!("&counter={}", counter),
+        });
+
+        let secret = unsafe { self.secret.as_slice() };
+        let encoded_len = BASE32_NOPAD.encode_len(secret.len());
+        let mut uri = Secret::with_len(prefix.len() + encoded_len + suffix.len())?;
+        let buffer = unsafe { uri.as_mut_slice() };
+        let (start, rest) = buffer.split_at_mut(prefix.len());
+        start.copy_from_slice(prefix.as_bytes());
+        let (encoded, end) = rest.split_at_mut(encoded_len);
+        BASE32_NOPAD.encode_mut(secret, encoded);
+        end.copy_from_slice(suffix.as_bytes());
+        Ok(uri)
+    }
+
+    /// Compute the HOTP value (RFC 4226) for the given counter. TOTP is just
+    /// HOTP with a counter derived from the current time.
+    pub fn code_for_counter(&self, counter: u64) -> Result<String> {
+        let key = unsafe { self.secret.as_slice() };
+        let message = counter.to_be_bytes();
+        let mac = match self.algorithm {
+            Algorithm::Sha1 => hmac::<Hmac<sha1::Sha1>>(key, &message)?,
+            Algorithm::Sha256 => hmac::<Hmac<sha2::Sha256>>(key, &message)?,
+            Algorithm::Sha512 => hmac::<Hmac<sha2::Sha512>>(key, &message)?,
+        };
+
+        // "Dynamic truncation", per RFC 4226 section 5.3.
+        let offset = (mac[mac.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([
+            mac[offset] & 0x7f,
+            mac[offset + 1],
+            mac[offset + 2],
+            mac[offset + 3],
+        ]);
+        Ok(format!(
+            "{:0width$}",
+            binary % 10_u32.pow(self.digits),
+            width = self.digits as usize
+        ))
+    }
+
+    /// Generate the next code. For TOTP this is the code for the given time;
+    /// for HOTP, the counter is incremented, so the updated generator must be
+    /// saved afterwards.
+    pub fn next_code(&mut self, now: SystemTime) -> Result<String> {
+        match self.kind {
+            OtpKind::Totp { period } => {
+                let seconds = now.duration_since(UNIX_EPOCH)?.as_secs();
+                self.code_for_counter(seconds / period)
+            }
+            OtpKind::Hotp { counter } => {
+                let code = self.code_for_counter(counter)?;
+                self.kind = OtpKind::Hotp {
+                    counter: counter + 1,
+                };
+                Ok(code)
+            }
+        }
+    }
+}
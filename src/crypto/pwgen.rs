@@ -12,14 +12,19 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::crypto::rng::Generator;
+use crate::crypto::rng::{uniform_index, Generator};
+use crate::crypto::wordlist::WORDLIST;
 use anyhow::{bail, Result};
 use bdrck::crypto::secret::Secret;
+use clap::ValueEnum;
 use once_cell::sync::Lazy;
 use rand::{Rng, RngCore};
-use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 
-#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(
+    Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize, ValueEnum,
+)]
 pub enum CharacterSet {
     Letters,
     Numbers,
@@ -55,17 +60,363 @@ static CHARACTER_SET: Lazy<HashMap<CharacterSet, Vec<u8>>> = Lazy::new(|| {
 
 pub const RECOMMENDED_MINIMUM_PASSWORD_LENGTH: usize = 16;
 
+/// Characters which are easily confused with one another in many fonts.
+pub const AMBIGUOUS_CHARACTERS: &[char] = &['0', 'O', 'o', '1', 'l', 'I', '|'];
+
+/// The maximum number of candidate passwords to generate, looking for one which
+/// satisfies a policy's `max_repeat`, before giving up.
+const MAX_POLICY_ATTEMPTS: usize = 100_000;
+
+/// The lowest acceptable probability of a candidate password satisfying a
+/// policy's `max_repeat`. Below this, we'd likely exhaust
+/// `MAX_POLICY_ATTEMPTS`, so the policy is rejected up front.
+const MIN_REPEAT_SUCCESS_RATE: f64 = 0.001;
+
+/// The number of uniformly random candidates to try, looking for one which
+/// meets a policy's minimum counts, before choosing the counts explicitly
+/// instead. Most policies are met by most passwords, and this is much cheaper.
+const QUICK_POLICY_ATTEMPTS: usize = 16;
+
+/// The longest password we'll generate, so a typo in a length or pattern
+/// produces an error instead of exhausting memory.
+pub const MAX_PASSWORD_LENGTH: usize = 4096;
+
+/// Additional constraints generated passwords must satisfy, e.g. to comply with
+/// websites' password rules.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct PasswordPolicy {
+    /// The minimum number of characters from each character set.
+    pub min_counts: BTreeMap<CharacterSet, usize>,
+    /// The maximum number of times the same character may be repeated in a row.
+    pub max_repeat: Option<usize>,
+    /// If set, characters in `AMBIGUOUS_CHARACTERS` are never used.
+    pub exclude_ambiguous: bool,
+    /// If set, the first character must come from this character set.
+    pub first: Option<CharacterSet>,
+}
+
+/// Return which character set the given character belongs to. This matches
+/// how `CHARACTER_SET` is constructed.
+fn character_set_of(c: u8) -> Option<CharacterSet> {
+    if c.is_ascii_alphabetic() {
+        Some(CharacterSet::Letters)
+    } else if c.is_ascii_digit() {
+        Some(CharacterSet::Numbers)
+    } else if c.is_ascii_graphic() {
+        Some(CharacterSet::Symbols)
+    } else {
+        None
+    }
+}
+
+/// Split the given characters up by character set.
+fn group_by_character_set(chars: &[u8]) -> BTreeMap<CharacterSet, Vec<u8>> {
+    let mut groups: BTreeMap<CharacterSet, Vec<u8>> = BTreeMap::new();
+    for c in chars {
+        if let Some(cs) = character_set_of(*c) {
+            groups.entry(cs).or_default().push(*c);
+        }
+    }
+    groups
+}
+
+/// Return the probability that `length` characters, chosen uniformly at random
+/// from `alphabet` characters, never repeat the same character more than
+/// `max_repeat` times in a row.
+fn repeat_success_rate(length: usize, alphabet: usize, max_repeat: usize) -> f64 {
+    if length <= max_repeat {
+        return 1.0;
+    }
+    let same = 1.0 / alphabet as f64;
+    // runs[i] is the probability that the prefix so far is acceptable, and ends
+    // in a run of exactly i + 1 identical characters.
+    let mut runs: VecDeque<f64> = VecDeque::from(vec![0.0; max_repeat]);
+    runs[0] = 1.0;
+    for _ in 1..length {
+        let total: f64 = runs.iter().sum();
+        runs.pop_back();
+        for p in runs.iter_mut() {
+            *p *= same;
+        }
+        runs.push_front(total * (1.0 - same));
+    }
+    runs.iter().sum()
+}
+
+/// Return a number uniformly distributed in [0, 1).
+fn unit_interval<R: RngCore + ?Sized>(generator: &mut R) -> f64 {
+    (generator.next_u64() >> 11) as f64 / (1_u64 << 53) as f64
+}
+
+/// Call `f` with every way of splitting `extra` between `parts` buckets.
+fn for_each_split<F: FnMut(&[usize])>(
+    extra: usize,
+    parts: usize,
+    split: &mut Vec<usize>,
+    f: &mut F,
+) {
+    if split.len() + 1 == parts {
+        split.push(extra);
+        f(split);
+        split.pop();
+        return;
+    }
+    for e in 0..=extra {
+        split.push(e);
+        for_each_split(extra - e, parts, split, f);
+        split.pop();
+    }
+}
+
+/// Choose how many of `length` characters, chosen uniformly at random from
+/// character sets of the given sizes, come from each set, conditioned on each
+/// count meeting the given minimum. The weights are computed in floating
+/// point, so each outcome's probability is only exact to within rounding error
+/// (a relative error on the order of 2^-50).
+fn sample_counts<R: RngCore + ?Sized>(
+    length: usize,
+    sizes: &[usize],
+    minimums: &[usize],
+    generator: &mut R,
+) -> Vec<usize> {
+    let total: usize = sizes.iter().sum();
+    let extra = length - minimums.iter().sum::<usize>();
+    let mut ln_factorial = vec![0.0; length + 1];
+    for n in 1..=length {
+        ln_factorial[n] = ln_factorial[n - 1] + (n as f64).ln();
+    }
+    let ln_p: Vec<f64> = sizes
+        .iter()
+        .map(|s| (*s as f64 / total as f64).ln())
+        .collect();
+    // The (log) multinomial probability of the counts, less the constant
+    // ln(length!) term.
+    let ln_weight = |split: &[usize]| -> f64 {
+        split
+            .iter()
+            .zip(minimums.iter())
+            .zip(ln_p.iter())
+            .map(|((e, m), lp)| (e + m) as f64 * lp - ln_factorial[e + m])
+            .sum()
+    };
+
+    let mut max = f64::NEG_INFINITY;
+    let mut sum = 0.0;
+    for_each_split(extra, sizes.len(), &mut Vec::new(), &mut |split| {
+        let w = ln_weight(split);
+        if w > max {
+            sum = sum * (max - w).exp() + 1.0;
+            max = w;
+        } else {
+            sum += (w - max).exp();
+        }
+    });
+
+    let mut target = sum * unit_interval(generator);
+    let mut chosen: Vec<usize> = Vec::new();
+    let mut done = false;
+    for_each_split(extra, sizes.len(), &mut Vec::new(), &mut |split| {
+        // If rounding leaves a tiny remainder, we fall through to the last split.
+        if !done {
+            chosen.clear();
+            chosen.extend_from_slice(split);
+            target -= (ln_weight(split) - max).exp();
+            done = target < 0.0;
+        }
+    });
+    chosen
+        .iter()
+        .zip(minimums.iter())
+        .map(|(e, m)| e + m)
+        .collect()
+}
+
+impl PasswordPolicy {
+    /// Check that it's possible to satisfy this policy, so we fail early
+    /// instead of retrying forever.
+    fn validate(&self, length: usize, chars: &[u8]) -> Result<()> {
+        let available: HashSet<CharacterSet> =
+            chars.iter().filter_map(|c| character_set_of(*c)).collect();
+        for (cs, count) in self.min_counts.iter() {
+            if *count > 0 && !available.contains(cs) {
+                bail!(
+                    "the password policy requires {:?}, but none are available",
+                    cs
+                );
+            }
+        }
+        if let Some(cs) = self.first {
+            if !available.contains(&cs) {
+                bail!(
+                    "the password policy requires a leading {:?} character, but none are available",
+                    cs
+                );
+            }
+        }
+        let first_extra = match self.first {
+            Some(cs) if self.min_counts.get(&cs).copied().unwrap_or(0) == 0 => 1,
+            _ => 0,
+        };
+        if self.min_counts.values().sum::<usize>() + first_extra > length {
+            bail!(
+                "the password policy requires more than {} characters",
+                length
+            );
+        }
+        if let Some(max_repeat) = self.max_repeat {
+            if max_repeat == 0 {
+                bail!("the maximum number of repeated characters must be at least 1");
+            }
+            if repeat_success_rate(length, chars.len(), max_repeat) < MIN_REPEAT_SUCCESS_RATE {
+                bail!(
+                    "the maximum number of repeated characters is too low for a {} character password; try allowing more repeats or more characters",
+                    length
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Fill `password` with random characters satisfying this policy's
+    /// minimum counts and first character set (but not necessarily its
+    /// `max_repeat`). Every such password is equally likely.
+    fn fill<R: RngCore + ?Sized>(&self, password: &mut [u8], chars: &[u8], generator: &mut R) {
+        let groups = group_by_character_set(chars);
+        let mut min_counts = self.min_counts.clone();
+        let mut rest = password;
+
+        // A uniformly random password with the right first character is just
+        // that character, followed by an independent uniformly random password.
+        if let Some(cs) = self.first {
+            let group = groups.get(&cs).unwrap();
+            rest[0] = group[uniform_index(generator, group.len())];
+            if let Some(min) = min_counts.get_mut(&cs) {
+                *min = min.saturating_sub(1);
+            }
+            rest = &mut rest[1..];
+        }
+
+        for _ in 0..QUICK_POLICY_ATTEMPTS {
+            for c in rest.iter_mut() {
+                *c = chars[uniform_index(generator, chars.len())];
+            }
+            let meets_minimums = min_counts.iter().all(|(cs, min)| {
+                rest.iter()
+                    .filter(|c| character_set_of(**c) == Some(*cs))
+                    .count()
+                    >= *min
+            });
+            if meets_minimums {
+                return;
+            }
+        }
+
+        // Choose how many characters come from each set, then where those
+        // characters go, then the characters themselves. The password is used
+        // as scratch space for each position's set, to avoid leaving copies
+        // of its structure around.
+        let groups: Vec<(&CharacterSet, &Vec<u8>)> = groups.iter().collect();
+        let sizes: Vec<usize> = groups.iter().map(|(_, g)| g.len()).collect();
+        let minimums: Vec<usize> = groups
+            .iter()
+            .map(|(cs, _)| min_counts.get(cs).copied().unwrap_or(0))
+            .collect();
+        let counts = sample_counts(rest.len(), &sizes, &minimums, generator);
+        let mut position = 0;
+        for (group, count) in counts.into_iter().enumerate() {
+            for c in rest[position..position + count].iter_mut() {
+                *c = group as u8;
+            }
+            position += count;
+        }
+        for i in (1..rest.len()).rev() {
+            let j = uniform_index(generator, i + 1);
+            rest.swap(i, j);
+        }
+        for c in rest.iter_mut() {
+            let group = groups[*c as usize].1;
+            *c = group[uniform_index(generator, group.len())];
+        }
+    }
+
+    pub fn is_satisfied_by(&self, password: &[u8]) -> bool {
+        if let Some(cs) = self.first {
+            if password.first().and_then(|c| character_set_of(*c)) != Some(cs) {
+                return false;
+            }
+        }
+
+        if let Some(max_repeat) = self.max_repeat {
+            let mut run = 0;
+            for (i, c) in password.iter().enumerate() {
+                run = match i > 0 && password[i - 1] == *c {
+                    false => 1,
+                    true => run + 1,
+                };
+                if run > max_repeat {
+                    return false;
+                }
+            }
+        }
+
+        let mut counts: HashMap<CharacterSet, usize> = HashMap::new();
+        for c in password {
+            if let Some(cs) = character_set_of(*c) {
+                *counts.entry(cs).or_insert(0) += 1;
+            }
+        }
+        self.min_counts
+            .iter()
+            .all(|(cs, min)| counts.get(cs).copied().unwrap_or(0) >= *min)
+    }
+}
+
 pub fn generate_password(
     length: usize,
     charsets: &[CharacterSet],
     exclude: &[char],
+) -> Result<Secret> {
+    generate_password_with_policy(length, charsets, exclude, &PasswordPolicy::default())
+}
+
+/// Generate a password which satisfies the given policy. Candidates are
+/// generated uniformly at random from those meeting the policy's minimum
+/// counts and first character set, and those which repeat characters too
+/// often are rejected, so every acceptable password is equally likely.
+pub fn generate_password_with_policy(
+    length: usize,
+    charsets: &[CharacterSet],
+    exclude: &[char],
+    policy: &PasswordPolicy,
+) -> Result<Secret> {
+    generate_password_with_rng(length, charsets, exclude, policy, &mut Generator)
+}
+
+/// Like `generate_password_with_policy`, but using the given source of
+/// randomness.
+pub fn generate_password_with_rng<R: Rng>(
+    length: usize,
+    charsets: &[CharacterSet],
+    exclude: &[char],
+    policy: &PasswordPolicy,
+    generator: &mut R,
 ) -> Result<Secret> {
     if length == 0 {
         bail!("refusing to generate a password of length 0");
     }
+    if length > MAX_PASSWORD_LENGTH {
+        bail!(
+            "refusing to generate a password longer than {} characters",
+            MAX_PASSWORD_LENGTH
+        );
+    }
 
     let exclude: HashSet<u8> = exclude
         .iter()
+        .chain(match policy.exclude_ambiguous {
+            false => [].iter(),
+            true => AMBIGUOUS_CHARACTERS.iter(),
+        })
         .filter_map(|c| {
             if c.is_ascii() {
                 let mut buf = [0; 1];
@@ -88,17 +439,296 @@ pub fn generate_password(
     if chars.is_empty() {
         bail!("cannot generate passwords from an empty character set");
     }
+    policy.validate(length, &chars)?;
 
-    let mut generator = Generator;
     let mut result = Secret::with_len(length)?;
 
-    for i in 0..length {
-        unsafe { result.as_mut_slice()[i] = chars[generator.gen_range(0..chars.len())] };
+    for _ in 0..MAX_POLICY_ATTEMPTS {
+        policy.fill(unsafe { result.as_mut_slice() }, &chars, generator);
+        if policy.is_satisfied_by(unsafe { result.as_slice() }) {
+            return Ok(result);
+        }
     }
 
+    bail!("failed to generate a password satisfying the policy; try a longer password or a less strict policy")
+}
+
+/// One position in a parsed password pattern.
+enum PatternElement {
+    /// A character chosen uniformly at random from the given alphabet.
+    Random(Vec<u8>),
+    /// A fixed character, encoded as UTF-8.
+    Literal(String),
+}
+
+fn pattern_class(code: char) -> Option<Vec<u8>> {
+    let letters = || CHARACTER_SET.get(&CharacterSet::Letters).unwrap().iter();
+    let numbers = || CHARACTER_SET.get(&CharacterSet::Numbers).unwrap().iter();
+    let symbols = || CHARACTER_SET.get(&CharacterSet::Symbols).unwrap().iter();
+    Some(match code {
+        'L' => letters().cloned().collect(),
+        'l' => letters()
+            .filter(|c| c.is_ascii_lowercase())
+            .cloned()
+            .collect(),
+        'U' => letters()
+            .filter(|c| c.is_ascii_uppercase())
+            .cloned()
+            .collect(),
+        'D' => numbers().cloned().collect(),
+        'H' => numbers().cloned().chain(b'a'..=b'f').collect(),
+        'S' => symbols().cloned().collect(),
+        'A' => letters().chain(numbers()).cloned().collect(),
+        '*' => letters()
+            .chain(numbers())
+            .chain(symbols())
+            .cloned()
+            .collect(),
+        _ => return None,
+    })
+}
+
+/// Parse a password pattern. Each of the following characters is replaced by
+/// a random character from a class:
+///
+/// - `L`: a letter, `l`: a lowercase letter, `U`: an uppercase letter
+/// - `D`: a digit, `H`: a lowercase hex digit
+/// - `S`: a symbol
+/// - `A`: a letter or digit, `*`: a letter, digit, or symbol
+///
+/// Any element can be followed by `{n}` to repeat it n times, up to a total of
+/// `MAX_PASSWORD_LENGTH` characters. A backslash
+/// escapes the next character, and all other characters are literals.
+fn parse_pattern(pattern: &str, exclude: &HashSet<u8>) -> Result<Vec<PatternElement>> {
+    let mut elements: Vec<PatternElement> = Vec::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        let element = if c == '\\' {
+            match chars.next() {
+                None => bail!("invalid pattern '{}', trailing '\\'", pattern),
+                Some(escaped) => PatternElement::Literal(escaped.to_string()),
+            }
+        } else if let Some(class) = pattern_class(c) {
+            let class: Vec<u8> = class.into_iter().filter(|c| !exclude.contains(c)).collect();
+            if class.is_empty() {
+                bail!("all of the characters for '{}' were excluded", c);
+            }
+            PatternElement::Random(class)
+        } else if c == '{' || c == '}' {
+            bail!("invalid pattern '{}', unexpected '{}'", pattern, c);
+        } else {
+            PatternElement::Literal(c.to_string())
+        };
+
+        let mut count: usize = 1;
+        if chars.peek() == Some(&'{') {
+            chars.next();
+            let mut digits = String::new();
+            loop {
+                match chars.next() {
+                    None => bail!("invalid pattern '{}', unterminated '{{'", pattern),
+                    Some('}') => break,
+                    Some(d) => digits.push(d),
+                }
+            }
+            count = match digits.parse() {
+                Ok(n) => n,
+                Err(_) => bail!("invalid pattern '{}', bad count '{}'", pattern, digits),
+            };
+        }
+        if count > MAX_PASSWORD_LENGTH - elements.len() {
+            bail!(
+                "invalid pattern '{}', passwords are limited to {} characters",
+                pattern,
+                MAX_PASSWORD_LENGTH
+            );
+        }
+        for _ in 0..count {
+            elements.push(match &element {
+                PatternElement::Random(class) => PatternElement::Random(class.clone()),
+                PatternElement::Literal(s) => PatternElement::Literal(s.clone()),
+            });
+        }
+    }
+
+    if elements.is_empty() {
+        bail!("refusing to generate a password from an empty pattern");
+    }
+    Ok(elements)
+}
+
+/// Generate a password matching the given pattern (see `parse_pattern` for
+/// the syntax). Each random position is chosen uniformly and independently.
+pub fn generate_from_pattern(pattern: &str, exclude: &[char]) -> Result<Secret> {
+    generate_from_pattern_with_rng(pattern, exclude, &mut Generator)
+}
+
+/// Like `generate_from_pattern`, but using the given source of randomness.
+pub fn generate_from_pattern_with_rng<R: Rng>(
+    pattern: &str,
+    exclude: &[char],
+    generator: &mut R,
+) -> Result<Secret> {
+    let exclude: HashSet<u8> = exclude
+        .iter()
+        .filter(|c| c.is_ascii())
+        .map(|c| *c as u8)
+        .collect();
+    let elements = parse_pattern(pattern, &exclude)?;
+
+    let mut result = Secret::new();
+    for element in elements.iter() {
+        match element {
+            PatternElement::Random(class) => {
+                let c = class[uniform_index(generator, class.len())];
+                append_str(&mut result, char::from(c).encode_utf8(&mut [0; 1]))?;
+            }
+            PatternElement::Literal(s) => append_str(&mut result, s)?,
+        }
+    }
     Ok(result)
 }
 
+/// A complete description of how to generate a password, so it can be saved
+/// (e.g. in a generation profile) and reused later.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GeneratorSettings {
+    /// The password length. Ignored if a pattern is given.
+    pub length: usize,
+    pub charsets: Vec<CharacterSet>,
+    pub exclude: Vec<char>,
+    pub policy: PasswordPolicy,
+    /// If set, generate a password matching this pattern instead (see
+    /// `generate_from_pattern`).
+    pub pattern: Option<String>,
+}
+
+impl GeneratorSettings {
+    pub fn generate(&self) -> Result<Secret> {
+        self.generate_with_rng(&mut Generator)
+    }
+
+    /// Generate a password using the given source of randomness. Given the
+    /// same settings and the same (deterministic) generator, the result is
+    /// always the same.
+    pub fn generate_with_rng<R: Rng>(&self, generator: &mut R) -> Result<Secret> {
+        match self.pattern.as_ref() {
+            None => generate_password_with_rng(
+                self.length,
+                &self.charsets,
+                &self.exclude,
+                &self.policy,
+                generator,
+            ),
+            Some(pattern) => {
+                let mut exclude = self.exclude.clone();
+                if self.policy.exclude_ambiguous {
+                    exclude.extend_from_slice(AMBIGUOUS_CHARACTERS);
+                }
+                generate_from_pattern_with_rng(pattern, &exclude, generator)
+            }
+        }
+    }
+}
+
+pub const RECOMMENDED_MINIMUM_PASSPHRASE_WORDS: usize = 6;
+
+/// How to capitalize the words in a generated passphrase.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum Capitalization {
+    /// Leave all words in lowercase.
+    #[default]
+    None,
+    /// Capitalize the first letter of each word.
+    First,
+    /// Capitalize every letter of each word.
+    All,
+    /// Capitalize the first letter of each word with probability 1/2.
+    Random,
+}
+
+#[derive(Clone, Debug)]
+pub struct PassphraseOptions {
+    pub words: usize,
+    pub separator: String,
+    pub capitalization: Capitalization,
+    /// If set, a random digit is appended to one randomly chosen word.
+    pub insert_digit: bool,
+}
+
+impl Default for PassphraseOptions {
+    fn default() -> Self {
+        PassphraseOptions {
+            words: RECOMMENDED_MINIMUM_PASSPHRASE_WORDS,
+            separator: "-".to_owned(),
+            capitalization: Capitalization::None,
+            insert_digit: false,
+        }
+    }
+}
+
+fn append_str(secret: &mut Secret, s: &str) -> Result<()> {
+    let old_len = secret.len();
+    secret.resize(old_len + s.len())?;
+    unsafe {
+        secret.as_mut_slice()[old_len..].copy_from_slice(s.as_bytes());
+    }
+    Ok(())
+}
+
+/// Generate a passphrase of randomly chosen words from `wordlist::WORDLIST`.
+pub fn generate_passphrase(options: &PassphraseOptions) -> Result<Secret> {
+    if options.words == 0 {
+        bail!("refusing to generate a passphrase with 0 words");
+    }
+
+    let mut generator = Generator;
+    let digit_word = generator.gen_range(0..options.words);
+    let mut result = Secret::new();
+    for i in 0..options.words {
+        if i > 0 {
+            append_str(&mut result, &options.separator)?;
+        }
+
+        let word = WORDLIST[generator.gen_range(0..WORDLIST.len())];
+        let capitalize_first = match options.capitalization {
+            Capitalization::None => false,
+            Capitalization::First => true,
+            Capitalization::All => true,
+            Capitalization::Random => generator.gen_bool(0.5),
+        };
+        let start = result.len();
+        append_str(&mut result, word)?;
+        let bytes = unsafe { &mut result.as_mut_slice()[start..] };
+        if options.capitalization == Capitalization::All {
+            bytes.make_ascii_uppercase();
+        } else if capitalize_first {
+            bytes[0].make_ascii_uppercase();
+        }
+
+        if options.insert_digit && i == digit_word {
+            let digit = char::from(b'0' + generator.gen_range(0..10_u8));
+            append_str(&mut result, digit.encode_utf8(&mut [0; 1]))?;
+        }
+    }
+
+    Ok(result)
+}
+
+/// Return the number of bits of entropy in passphrases generated with the
+/// given options, assuming an attacker knows the word list and the options.
+pub fn passphrase_entropy(options: &PassphraseOptions) -> f64 {
+    let words = options.words as f64;
+    let mut bits = words * (WORDLIST.len() as f64).log2();
+    if options.capitalization == Capitalization::Random {
+        bits += words;
+    }
+    if options.insert_digit {
+        bits += 10_f64.log2() + words.log2();
+    }
+    bits
+}
+
 pub fn generate_hex(byte_length: usize) -> String {
     let mut generator = Generator;
     let mut bytes = vec![0_u8; byte_length];
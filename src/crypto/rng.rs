@@ -14,7 +14,10 @@
 
 use bdrck::crypto::util::randombytes_into;
 use byteorder::{LittleEndian, ReadBytesExt};
+use hmac::digest::KeyInit;
+use hmac::{Hmac, Mac};
 use rand::{self, RngCore};
+use sha2::Sha256;
 use std::io::Cursor;
 
 /// This structure implements the `Rng` trait from the `rand` crate using
@@ -43,3 +46,83 @@ impl RngCore for Generator {
         Ok(self.fill_bytes(dest))
     }
 }
+
+/// The number of bytes DeterministicGenerator produces per block.
+const DETERMINISTIC_BLOCK_LENGTH: usize = 32;
+
+/// DeterministicGenerator is a cryptographically secure, but *deterministic*,
+/// random number generator: given the same seed, it always produces the same
+/// stream of bytes. The stream is HMAC-SHA256(seed, block counter) for
+/// successive counter values, so it is unpredictable without the seed.
+pub struct DeterministicGenerator {
+    mac: Hmac<Sha256>,
+    counter: u64,
+    block: [u8; DETERMINISTIC_BLOCK_LENGTH],
+    position: usize,
+}
+
+impl DeterministicGenerator {
+    pub fn new(seed: &[u8]) -> Self {
+        DeterministicGenerator {
+            mac: <Hmac<Sha256> as KeyInit>::new_from_slice(seed)
+                .expect("HMAC accepts keys of any length"),
+            counter: 0,
+            block: [0; DETERMINISTIC_BLOCK_LENGTH],
+            position: DETERMINISTIC_BLOCK_LENGTH,
+        }
+    }
+}
+
+impl RngCore for DeterministicGenerator {
+    fn next_u32(&mut self) -> u32 {
+        let mut buf = [0_u8; 4];
+        self.fill_bytes(&mut buf);
+        u32::from_le_bytes(buf)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut buf = [0_u8; 8];
+        self.fill_bytes(&mut buf);
+        u64::from_le_bytes(buf)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for byte in dest.iter_mut() {
+            if self.position == DETERMINISTIC_BLOCK_LENGTH {
+                let mut mac = self.mac.clone();
+                mac.update(&self.counter.to_be_bytes());
+                self.block.copy_from_slice(&mac.finalize().into_bytes());
+                self.counter += 1;
+                self.position = 0;
+            }
+            *byte = self.block[self.position];
+            self.position += 1;
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// Return an integer chosen uniformly at random from `0..n`, by rejection
+/// sampling the generator's output: we read 32-bit little-endian words,
+/// discarding any which are at least the largest multiple of `n` not
+/// exceeding 2^32, and return the first remaining word modulo `n`.
+///
+/// Unlike `Rng::gen_range`, whose algorithm is an implementation detail of
+/// `rand` which may change between versions, this routine is fixed. So, the
+/// output it produces from a DeterministicGenerator never changes, which is
+/// what keeps derived passwords stable.
+pub fn uniform_index<R: RngCore + ?Sized>(generator: &mut R, n: usize) -> usize {
+    assert!(n > 0 && n <= u32::MAX as usize, "invalid range size {}", n);
+    let n = n as u64;
+    let limit = (1_u64 << 32) - (1_u64 << 32) % n;
+    loop {
+        let x = generator.next_u32() as u64;
+        if x < limit {
+            return (x % n) as usize;
+        }
+    }
+}
@@ -0,0 +1,207 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::crypto::rng::Generator;
+use crate::crypto::wordlist::{decode_mnemonic, encode_mnemonic};
+use anyhow::{bail, Result};
+use bdrck::crypto::secret::Secret;
+use rand::RngCore;
+use std::collections::HashSet;
+
+/// Multiply two elements of GF(2^8), using the same reducing polynomial as AES
+/// (x^8 + x^4 + x^3 + x + 1). This avoids lookup tables, so its timing doesn't
+/// depend on the (secret) inputs.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product: u8 = 0;
+    for _ in 0..8 {
+        product ^= a & 0_u8.wrapping_sub(b & 1);
+        let carry = a >> 7;
+        a <<= 1;
+        a ^= 0x1b & 0_u8.wrapping_sub(carry);
+        b >>= 1;
+    }
+    product
+}
+
+/// Return the multiplicative inverse of a nonzero element of GF(2^8), computed
+/// as a^254.
+fn gf_inv(a: u8) -> u8 {
+    let mut result: u8 = 1;
+    let mut base = a;
+    let mut exponent: u8 = 254;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = gf_mul(result, base);
+        }
+        base = gf_mul(base, base);
+        exponent >>= 1;
+    }
+    result
+}
+
+/// A single share of a secret which was split with `split`. Any `threshold`
+/// shares with distinct indices can be `combine`d to recover the secret.
+pub struct Share {
+    /// The x-coordinate this share was evaluated at. Always nonzero.
+    pub index: u8,
+    /// The number of shares needed to recover the secret.
+    pub threshold: u8,
+    pub data: Secret,
+}
+
+/// The number of bytes a share's mnemonic encodes in addition to its data:
+/// the index, the threshold, and a two-byte checksum.
+const SHARE_OVERHEAD: usize = 4;
+
+/// A simple Fletcher-16 checksum. This only guards against mistyped or
+/// mismatched words, not against deliberate tampering.
+fn checksum(data: &[u8]) -> [u8; 2] {
+    let mut a: u16 = 0;
+    let mut b: u16 = 0;
+    for byte in data {
+        a = (a + *byte as u16) % 255;
+        b = (b + a) % 255;
+    }
+    [b as u8, a as u8]
+}
+
+impl Share {
+    /// Encode this share as a human-readable list of words, suitable for
+    /// printing or writing down.
+    pub fn to_mnemonic(&self) -> Result<String> {
+        let mut bytes = Secret::with_len(self.data.len() + SHARE_OVERHEAD)?;
+        {
+            let bytes = unsafe { bytes.as_mut_slice() };
+            let end = bytes.len() - 2;
+            bytes[0] = self.index;
+            bytes[1] = self.threshold;
+            bytes[2..end].copy_from_slice(unsafe { self.data.as_slice() });
+            let sum = checksum(&bytes[..end]);
+            bytes[end..].copy_from_slice(&sum);
+        }
+        Ok(encode_mnemonic(unsafe { bytes.as_slice() }).join(" "))
+    }
+
+    /// Decode a share previously encoded with `to_mnemonic`. The length of the
+    /// original secret must be known in advance.
+    pub fn from_mnemonic(mnemonic: &str, secret_len: usize) -> Result<Share> {
+        let words: Vec<&str> = mnemonic.split_whitespace().collect();
+        let bytes = decode_mnemonic(&words, secret_len + SHARE_OVERHEAD)?;
+        let end = bytes.len() - 2;
+        if checksum(&bytes[..end]) != bytes[end..] {
+            bail!("invalid share checksum; check for typos");
+        }
+        if bytes[0] == 0 || bytes[1] == 0 {
+            bail!("invalid share index or threshold");
+        }
+
+        let mut data = Secret::with_len(secret_len)?;
+        unsafe { data.as_mut_slice() }.copy_from_slice(&bytes[2..end]);
+        Ok(Share {
+            index: bytes[0],
+            threshold: bytes[1],
+            data,
+        })
+    }
+}
+
+/// Split the given secret into `shares` shares, any `threshold` of which can
+/// be used to recover it, using Shamir's secret sharing over GF(2^8).
+pub fn split(secret: &Secret, shares: u8, threshold: u8) -> Result<Vec<Share>> {
+    if threshold == 0 {
+        bail!("the share threshold must be at least 1");
+    }
+    if shares < threshold {
+        bail!(
+            "cannot require {} shares when only {} are generated",
+            threshold,
+            shares
+        );
+    }
+
+    // Each byte of the secret is the constant term of its own random
+    // polynomial of degree threshold - 1.
+    let degree = threshold as usize - 1;
+    let mut coefficients = Secret::with_len(degree * secret.len())?;
+    let mut generator = Generator;
+    generator.fill_bytes(unsafe { coefficients.as_mut_slice() });
+
+    let mut result = Vec::with_capacity(shares as usize);
+    for index in 1..=shares {
+        let mut data = Secret::with_len(secret.len())?;
+        for (i, byte) in unsafe { data.as_mut_slice() }.iter_mut().enumerate() {
+            // Evaluate the polynomial at x = index using Horner's method.
+            let mut y: u8 = 0;
+            for c in (0..degree).rev() {
+                y = gf_mul(y, index) ^ unsafe { coefficients.as_slice() }[c * secret.len() + i];
+            }
+            *byte = gf_mul(y, index) ^ unsafe { secret.as_slice() }[i];
+        }
+        result.push(Share {
+            index,
+            threshold,
+            data,
+        });
+    }
+    Ok(result)
+}
+
+/// Recover a secret from shares previously produced by `split`. At least
+/// `threshold` shares with distinct indices must be provided.
+pub fn combine(shares: &[Share]) -> Result<Secret> {
+    let first = match shares.first() {
+        None => bail!("no shares provided"),
+        Some(s) => s,
+    };
+
+    let mut indices = HashSet::new();
+    for share in shares {
+        if share.index == 0 {
+            bail!("invalid share index 0");
+        }
+        if share.threshold != first.threshold || share.data.len() != first.data.len() {
+            bail!("the provided shares are not from the same secret");
+        }
+        if !indices.insert(share.index) {
+            bail!("share {} was provided more than once", share.index);
+        }
+    }
+    if shares.len() < first.threshold as usize {
+        bail!(
+            "{} shares are required to recover the secret, but only {} were provided",
+            first.threshold,
+            shares.len()
+        );
+    }
+
+    // Lagrange interpolation, evaluated at x = 0.
+    let shares = &shares[..first.threshold as usize];
+    let mut secret = Secret::with_len(first.data.len())?;
+    for (m, share) in shares.iter().enumerate() {
+        let mut basis: u8 = 1;
+        for (l, other) in shares.iter().enumerate() {
+            if l != m {
+                basis = gf_mul(
+                    basis,
+                    gf_mul(other.index, gf_inv(other.index ^ share.index)),
+                );
+            }
+        }
+
+        for (i, byte) in unsafe { secret.as_mut_slice() }.iter_mut().enumerate() {
+            *byte ^= gf_mul(basis, unsafe { share.data.as_slice() }[i]);
+        }
+    }
+    Ok(secret)
+}
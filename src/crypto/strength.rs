@@ -0,0 +1,545 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A password strength estimator, loosely modeled after Dropbox's zxcvbn.
+//!
+//! Rather than counting character classes, we look for the patterns real
+//! attackers try first (common passwords, dictionary words, keyboard walks,
+//! repeats, sequences and dates), and estimate how many guesses an attacker
+//! trying those patterns would need. Anything not covered by a pattern is
+//! assumed to be brute forced.
+
+use bdrck::crypto::secret::Secret;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+
+/// The highest possible strength score.
+pub const MAX_SCORE: u8 = 4;
+
+/// The (log2) guess counts at which each successive score is reached. These
+/// match zxcvbn's thresholds of 10^3, 10^6, 10^8 and 10^10 guesses.
+const SCORE_THRESHOLDS: [f64; MAX_SCORE as usize] = [9.97, 19.93, 26.58, 33.22];
+
+/// The maximum length of a substring we'll bother looking up in dictionaries.
+const MAX_WORD_LENGTH: usize = 20;
+
+/// The number of leading characters of a password we examine. Anything longer
+/// is estimated as if it were truncated, which can only underestimate its
+/// strength, but keeps the estimator fast on arbitrarily large input.
+const MAX_ESTIMATED_LENGTH: usize = 256;
+
+/// The minimum length of a match for most pattern kinds.
+const MIN_MATCH_LENGTH: usize = 3;
+
+/// The number of years we assume an attacker tries when guessing dates.
+const YEAR_SPACE: f64 = 120.0;
+const MIN_YEAR: u32 = 1910;
+const MAX_YEAR: u32 = 2029;
+
+/// A list of very commonly used passwords, most common first.
+static COMMON_PASSWORDS_DATA: &str = include_str!("common_passwords.txt");
+
+static COMMON_PASSWORDS: Lazy<HashMap<&str, usize>> = Lazy::new(|| {
+    COMMON_PASSWORDS_DATA
+        .lines()
+        .enumerate()
+        .map(|(i, p)| (p, i + 1))
+        .collect()
+});
+
+/// Ordinary words. We reuse the mnemonic word list, since it's a reasonable
+/// sample of common English words; they're ranked after common passwords.
+static WORDS: Lazy<HashMap<&str, usize>> = Lazy::new(|| {
+    super::wordlist::WORDLIST
+        .iter()
+        .enumerate()
+        .map(|(i, w)| (*w, COMMON_PASSWORDS.len() + i + 1))
+        .collect()
+});
+
+/// Rows of a US QWERTY keyboard, unshifted and shifted.
+static KEYBOARD_ROWS: &[&str] = &[
+    "`1234567890-=",
+    "qwertyuiop[]\\",
+    "asdfghjkl;'",
+    "zxcvbnm,./",
+    "~!@#$%^&*()_+",
+    "QWERTYUIOP{}|",
+    "ASDFGHJKL:\"",
+    "ZXCVBNM<>?",
+];
+
+/// The number of keys an attacker might start a keyboard walk on.
+const KEYBOARD_STARTING_POSITIONS: f64 = 94.0;
+
+/// Common "l33t speak" substitutions, mapping the substitute to the letter.
+static L33T_SUBSTITUTIONS: &[(char, char)] = &[
+    ('4', 'a'),
+    ('@', 'a'),
+    ('8', 'b'),
+    ('(', 'c'),
+    ('3', 'e'),
+    ('6', 'g'),
+    ('1', 'i'),
+    ('!', 'i'),
+    ('|', 'l'),
+    ('0', 'o'),
+    ('$', 's'),
+    ('5', 's'),
+    ('7', 't'),
+    ('+', 't'),
+    ('2', 'z'),
+];
+
+/// The kinds of patterns the estimator recognizes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PatternKind {
+    CommonPassword,
+    Dictionary,
+    Keyboard,
+    Repeat,
+    Sequence,
+    Date,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Match {
+    start: usize,
+    end: usize,
+    kind: PatternKind,
+    guesses_log2: f64,
+}
+
+/// The result of estimating a password's strength.
+#[derive(Clone, Debug)]
+pub struct Estimate {
+    /// The base-2 logarithm of the estimated number of guesses needed to find
+    /// the password; i.e., its estimated entropy in bits.
+    pub guesses_log2: f64,
+    /// A score from 0 (trivially guessable) to MAX_SCORE (very unguessable).
+    pub score: u8,
+    /// The patterns which were found in the password.
+    pub patterns: Vec<PatternKind>,
+}
+
+impl Estimate {
+    /// Return a short explanation of what makes this password weak, if
+    /// anything in particular does.
+    pub fn warning(&self) -> Option<&'static str> {
+        if self.score >= MAX_SCORE {
+            return None;
+        }
+        let has = |k: PatternKind| self.patterns.contains(&k);
+        Some(if has(PatternKind::CommonPassword) {
+            "This is a very commonly used password."
+        } else if has(PatternKind::Dictionary) {
+            "Dictionary words are easy to guess, even with substitutions like '@' for 'a'."
+        } else if has(PatternKind::Keyboard) {
+            "Straight rows of keys are easy to guess."
+        } else if has(PatternKind::Repeat) {
+            "Repeats like 'aaa' or 'abcabc' are easy to guess."
+        } else if has(PatternKind::Sequence) {
+            "Sequences like 'abc' or '6543' are easy to guess."
+        } else if has(PatternKind::Date) {
+            "Dates are often easy to guess."
+        } else {
+            "Short passwords are easy to guess."
+        })
+    }
+}
+
+fn log2_binomial(n: usize, k: usize) -> f64 {
+    let k = k.min(n - k);
+    (0..k)
+        .map(|i| ((n - i) as f64).log2() - ((i + 1) as f64).log2())
+        .sum()
+}
+
+/// Return the cardinality of the class of characters `c` belongs to, which is
+/// what we assume an attacker brute forcing it would need to try.
+fn brute_force_cardinality(c: char) -> f64 {
+    if c.is_ascii_lowercase() || c.is_ascii_uppercase() {
+        26.0
+    } else if c.is_ascii_digit() {
+        10.0
+    } else if c.is_ascii() {
+        33.0
+    } else {
+        100.0
+    }
+}
+
+/// Return the extra (log2) guesses needed to account for the capitalization
+/// of a dictionary word.
+fn capitalization_log2(word: &[char]) -> f64 {
+    let upper = word.iter().filter(|c| c.is_uppercase()).count();
+    let lower = word.iter().filter(|c| c.is_lowercase()).count();
+    if upper == 0 {
+        0.0
+    } else if lower == 0 || (upper == 1 && word[0].is_uppercase()) {
+        // ALL CAPS and Capitalized are the first variations anyone tries.
+        1.0
+    } else {
+        let n = upper + lower;
+        let variations: f64 = (1..=upper.min(lower))
+            .map(|k| log2_binomial(n, k).exp2())
+            .sum();
+        variations.log2()
+    }
+}
+
+fn dictionary_matches(chars: &[char], matches: &mut Vec<Match>) {
+    for start in 0..chars.len() {
+        for end in (start + MIN_MATCH_LENGTH)..=chars.len().min(start + MAX_WORD_LENGTH) {
+            let slice = &chars[start..end];
+            let mut substituted = 0;
+            let normalized: String = slice
+                .iter()
+                .map(|c| {
+                    let c = c.to_ascii_lowercase();
+                    match L33T_SUBSTITUTIONS.iter().find(|(from, _)| *from == c) {
+                        Some((_, to)) => {
+                            substituted += 1;
+                            *to
+                        }
+                        None => c,
+                    }
+                })
+                .collect();
+            let lowered: String = slice.iter().map(|c| c.to_ascii_lowercase()).collect();
+            let reversed: String = lowered.chars().rev().collect();
+
+            let candidates = [(lowered.as_str(), 0.0), (reversed.as_str(), 1.0)]
+                .into_iter()
+                .chain((substituted > 0).then_some((normalized.as_str(), substituted as f64)));
+            for (word, extra_log2) in candidates {
+                let (kind, rank) = match COMMON_PASSWORDS.get(word) {
+                    Some(rank) => (PatternKind::CommonPassword, *rank),
+                    None => match WORDS.get(word) {
+                        Some(rank) => (PatternKind::Dictionary, *rank),
+                        None => continue,
+                    },
+                };
+                matches.push(Match {
+                    start,
+                    end,
+                    kind,
+                    guesses_log2: (rank as f64).log2() + capitalization_log2(slice) + extra_log2,
+                });
+            }
+        }
+    }
+}
+
+fn keyboard_position(c: char) -> Option<(usize, usize)> {
+    KEYBOARD_ROWS
+        .iter()
+        .enumerate()
+        .find_map(|(row, keys)| keys.chars().position(|k| k == c).map(|col| (row % 4, col)))
+}
+
+fn keyboard_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let positions: Vec<Option<(usize, usize)>> =
+        chars.iter().map(|c| keyboard_position(*c)).collect();
+    let mut start = 0;
+    while start < chars.len() {
+        let mut end = start + 1;
+        let mut direction: Option<isize> = None;
+        while end < chars.len() {
+            let step = match (positions[end - 1], positions[end]) {
+                (Some((ra, ca)), Some((rb, cb))) if ra == rb => cb as isize - ca as isize,
+                _ => break,
+            };
+            if step.abs() != 1 || direction.is_some_and(|d| d != step) {
+                break;
+            }
+            direction = Some(step);
+            end += 1;
+        }
+        let len = end - start;
+        if len >= MIN_MATCH_LENGTH {
+            let shifted = chars[start..end]
+                .iter()
+                .any(|c| KEYBOARD_ROWS[4..].iter().any(|row| row.contains(*c)));
+            matches.push(Match {
+                start,
+                end,
+                kind: PatternKind::Keyboard,
+                // Starting key, direction, and length.
+                guesses_log2: (KEYBOARD_STARTING_POSITIONS * 2.0 * len as f64).log2()
+                    + if shifted { 1.0 } else { 0.0 },
+            });
+            start = end;
+        } else {
+            start += 1;
+        }
+    }
+}
+
+fn sequence_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let same_class = |a: char, b: char| {
+        (a.is_ascii_lowercase() && b.is_ascii_lowercase())
+            || (a.is_ascii_uppercase() && b.is_ascii_uppercase())
+            || (a.is_ascii_digit() && b.is_ascii_digit())
+    };
+    let mut start = 0;
+    while start + 1 < chars.len() {
+        let delta = chars[start + 1] as i64 - chars[start] as i64;
+        let mut end = start + 1;
+        while end < chars.len()
+            && same_class(chars[start], chars[end])
+            && chars[end] as i64 - chars[end - 1] as i64 == delta
+            && delta != 0
+            && delta.abs() <= 5
+        {
+            end += 1;
+        }
+        let len = end - start;
+        if len >= MIN_MATCH_LENGTH {
+            let first = chars[start];
+            let base: f64 = if matches!(first, 'a' | 'A' | 'z' | 'Z' | '0' | '1' | '9') {
+                4.0
+            } else if first.is_ascii_digit() {
+                10.0
+            } else {
+                26.0
+            };
+            let descending = if delta < 0 { 2.0 } else { 1.0 };
+            matches.push(Match {
+                start,
+                end,
+                kind: PatternKind::Sequence,
+                guesses_log2: (base * descending * delta.abs() as f64 * len as f64).log2(),
+            });
+            start = end - 1;
+        } else {
+            start += 1;
+        }
+    }
+}
+
+/// Return the length of the shortest block which `chars` starts with repeats
+/// of, covering at least MIN_MATCH_LENGTH characters, if there is one. This
+/// takes a single pass computing the KMP failure function, so it's linear in
+/// the length of `chars`.
+fn shortest_repeated_block(chars: &[char]) -> Option<usize> {
+    // border[i] is the length of the longest proper prefix of chars[..=i]
+    // which is also a suffix of it.
+    let mut border = vec![0; chars.len()];
+    for i in 1..chars.len() {
+        let mut k = border[i - 1];
+        while k > 0 && chars[i] != chars[k] {
+            k = border[k - 1];
+        }
+        if chars[i] == chars[k] {
+            k += 1;
+        }
+        border[i] = k;
+
+        // If the shortest period of chars[..=i] is exactly half its length,
+        // it's a block repeated twice.
+        let len = i + 1;
+        let period = len - k;
+        if 2 * period == len
+            && (len >= MIN_MATCH_LENGTH || chars.get(len..len + period) == Some(&chars[..period]))
+        {
+            return Some(period);
+        }
+    }
+    None
+}
+
+fn repeat_matches(chars: &[char], matches: &mut Vec<Match>) {
+    for start in 0..chars.len() {
+        let rest = &chars[start..];
+        let block_len = match shortest_repeated_block(rest) {
+            None => continue,
+            Some(block_len) => block_len,
+        };
+        let block = &rest[..block_len];
+        let repeats = rest
+            .chunks_exact(block_len)
+            .take_while(|c| *c == block)
+            .count();
+
+        // Estimate the block with the other patterns only, rather than
+        // recursing.
+        let block_log2 = if block_len == 1 {
+            brute_force_cardinality(block[0]).log2()
+        } else {
+            cheapest_cover(block, &base_matches(block)).guesses_log2
+        };
+        matches.push(Match {
+            start,
+            end: start + repeats * block_len,
+            kind: PatternKind::Repeat,
+            guesses_log2: block_log2 + (repeats as f64).log2(),
+        });
+    }
+}
+
+fn parse_digits(chars: &[char]) -> Option<u32> {
+    if chars.is_empty() || !chars.iter().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    chars.iter().collect::<String>().parse().ok()
+}
+
+fn is_year(year: u32, digits: usize) -> bool {
+    match digits {
+        2 => true,
+        4 => (MIN_YEAR..=MAX_YEAR).contains(&year),
+        _ => false,
+    }
+}
+
+/// Return whether the three parts look like a day, month and year in some
+/// common order.
+fn is_date(parts: &[&[char]]) -> bool {
+    let values: Option<Vec<u32>> = parts.iter().map(|p| parse_digits(p)).collect();
+    let values = match values {
+        Some(v) => v,
+        None => return false,
+    };
+    let is_day_month = |a: u32, b: u32| {
+        (1..=31).contains(&a) && (1..=12).contains(&b)
+            || (1..=12).contains(&a) && (1..=31).contains(&b)
+    };
+    (is_year(values[2], parts[2].len())
+        && parts[0].len() <= 2
+        && is_day_month(values[0], values[1]))
+        || (is_year(values[0], parts[0].len())
+            && parts[2].len() <= 2
+            && is_day_month(values[1], values[2]))
+}
+
+/// Return every way to split `chars` into three non-empty parts of at most
+/// four characters each.
+fn splits(chars: &[char]) -> impl Iterator<Item = [&[char]; 3]> + '_ {
+    (1..chars.len().min(5)).flat_map(move |a| {
+        ((a + 1)..chars.len().min(a + 5))
+            .filter(move |b| chars.len() - b <= 4)
+            .map(move |b| [&chars[..a], &chars[a..b], &chars[b..]])
+    })
+}
+
+fn date_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let date_log2 = (365.0 * YEAR_SPACE).log2();
+    for start in 0..chars.len() {
+        for end in (start + 4)..=chars.len().min(start + 10) {
+            let slice = &chars[start..end];
+            let guesses_log2 = if let Some(year) = parse_digits(slice).filter(|_| slice.len() == 4)
+            {
+                if is_year(year, 4) {
+                    Some(YEAR_SPACE.log2())
+                } else {
+                    splits(slice).any(|p| is_date(&p)).then_some(date_log2)
+                }
+            } else if parse_digits(slice).is_some() {
+                splits(slice).any(|p| is_date(&p)).then_some(date_log2)
+            } else {
+                // Dates with separators, like 1989-03-13 or 3/13/89.
+                let separator = slice.iter().find(|c| !c.is_ascii_digit());
+                separator.and_then(|sep| {
+                    let parts: Vec<&[char]> = slice.split(|c| c == sep).collect();
+                    (parts.len() == 3 && is_date(&parts)).then_some(date_log2 + 2.0)
+                })
+            };
+            if let Some(guesses_log2) = guesses_log2 {
+                matches.push(Match {
+                    start,
+                    end,
+                    kind: PatternKind::Date,
+                    guesses_log2,
+                });
+            }
+        }
+    }
+}
+
+/// Return the matches for every kind of pattern except repeats.
+fn base_matches(chars: &[char]) -> Vec<Match> {
+    let mut matches = Vec::new();
+    dictionary_matches(chars, &mut matches);
+    keyboard_matches(chars, &mut matches);
+    sequence_matches(chars, &mut matches);
+    date_matches(chars, &mut matches);
+    matches
+}
+
+fn estimate_chars(chars: &[char]) -> Estimate {
+    let chars = &chars[..chars.len().min(MAX_ESTIMATED_LENGTH)];
+    let mut matches = base_matches(chars);
+    repeat_matches(chars, &mut matches);
+    cheapest_cover(chars, &matches)
+}
+
+fn cheapest_cover(chars: &[char], matches: &[Match]) -> Estimate {
+    // Find the cheapest way to cover the password with matches, brute
+    // forcing whatever's left over. best[i] is the cheapest way to guess the
+    // first i characters, and how we got there.
+    let mut best: Vec<(f64, Option<Match>)> = vec![(0.0, None); chars.len() + 1];
+    for end in 1..=chars.len() {
+        best[end] = (
+            best[end - 1].0 + brute_force_cardinality(chars[end - 1]).log2(),
+            None,
+        );
+        for m in matches.iter().filter(|m| m.end == end) {
+            // Each additional pattern costs a bit: the attacker must also
+            // guess how the patterns were combined.
+            let cost = best[m.start].0 + m.guesses_log2 + if m.start > 0 { 1.0 } else { 0.0 };
+            if cost < best[end].0 {
+                best[end] = (cost, Some(*m));
+            }
+        }
+    }
+
+    let mut patterns = Vec::new();
+    let mut i = chars.len();
+    while i > 0 {
+        match best[i].1 {
+            Some(m) => {
+                patterns.push(m.kind);
+                i = m.start;
+            }
+            None => i -= 1,
+        }
+    }
+    patterns.reverse();
+
+    let guesses_log2 = best[chars.len()].0;
+    Estimate {
+        guesses_log2,
+        score: SCORE_THRESHOLDS
+            .iter()
+            .filter(|t| guesses_log2 >= **t)
+            .count() as u8,
+        patterns,
+    }
+}
+
+/// Estimate the strength of the given password.
+pub fn estimate(password: &str) -> Estimate {
+    let chars: Vec<char> = password.chars().collect();
+    estimate_chars(&chars)
+}
+
+/// Estimate the strength of the given secret, which is generally expected to
+/// (but need not) contain UTF-8 text.
+pub fn estimate_secret(password: &Secret) -> Estimate {
+    let chars: Vec<char> = String::from_utf8_lossy(unsafe { password.as_slice() })
+        .chars()
+        .collect();
+    estimate_chars(&chars)
+}
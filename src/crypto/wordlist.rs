@@ -0,0 +1,89 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{bail, Result};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+
+/// A list of 2048 short, distinct, common English words. This is the BIP-0039
+/// English word list, which is in the public domain. Every word is uniquely
+/// identified by its first four letters.
+static WORDLIST_DATA: &str = include_str!("wordlist.txt");
+
+pub static WORDLIST: Lazy<Vec<&'static str>> = Lazy::new(|| WORDLIST_DATA.lines().collect());
+
+static WORD_INDICES: Lazy<HashMap<&'static str, usize>> = Lazy::new(|| {
+    WORDLIST
+        .iter()
+        .enumerate()
+        .map(|(i, word)| (*word, i))
+        .collect()
+});
+
+/// The number of bits of data each word in a mnemonic encodes.
+const BITS_PER_WORD: usize = 11;
+
+/// Return the number of words needed to encode `len` bytes of data.
+pub fn mnemonic_len(len: usize) -> usize {
+    (len * 8).div_ceil(BITS_PER_WORD)
+}
+
+/// Encode arbitrary bytes as a list of words from `WORDLIST`. Each word
+/// encodes 11 bits; the final word is padded with zero bits if necessary.
+pub fn encode_mnemonic(data: &[u8]) -> Vec<&'static str> {
+    let mut words = Vec::with_capacity(mnemonic_len(data.len()));
+    let mut accumulator: u32 = 0;
+    let mut bits: usize = 0;
+    for byte in data {
+        accumulator = (accumulator << 8) | *byte as u32;
+        bits += 8;
+        while bits >= BITS_PER_WORD {
+            bits -= BITS_PER_WORD;
+            words.push(WORDLIST[((accumulator >> bits) & 0x7ff) as usize]);
+        }
+    }
+    if bits > 0 {
+        words.push(WORDLIST[((accumulator << (BITS_PER_WORD - bits)) & 0x7ff) as usize]);
+    }
+    words
+}
+
+/// Decode a list of words previously produced by `encode_mnemonic` back into
+/// `len` bytes of data. Words are matched case-insensitively.
+pub fn decode_mnemonic<S: AsRef<str>>(words: &[S], len: usize) -> Result<Vec<u8>> {
+    if words.len() != mnemonic_len(len) {
+        bail!(
+            "expected {} words, but got {}",
+            mnemonic_len(len),
+            words.len()
+        );
+    }
+
+    let mut data = Vec::with_capacity(len);
+    let mut accumulator: u32 = 0;
+    let mut bits: usize = 0;
+    for word in words {
+        let index = match WORD_INDICES.get(word.as_ref().to_lowercase().as_str()) {
+            None => bail!("unrecognized word '{}'", word.as_ref()),
+            Some(i) => *i,
+        };
+        accumulator = (accumulator << BITS_PER_WORD) | index as u32;
+        bits += BITS_PER_WORD;
+        while bits >= 8 && data.len() < len {
+            bits -= 8;
+            data.push((accumulator >> bits) as u8);
+        }
+    }
+    Ok(data)
+}
@@ -0,0 +1,50 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Docker runs credential helpers as `docker-credential-<name> <operation>`,
+//! so this just forwards to `pwm docker-credential <operation>`.
+
+#![deny(
+    anonymous_parameters,
+    trivial_casts,
+    trivial_numeric_casts,
+    unused_extern_crates,
+    unused_import_braces
+)]
+#![warn(bare_trait_objects, unreachable_pub, unused_qualifications)]
+
+use anyhow::Result;
+use clap::Parser;
+use std::env;
+use std::ffi::OsString;
+use std::io;
+use tracing_subscriber::{filter::LevelFilter, prelude::*, EnvFilter};
+
+fn main() -> Result<()> {
+    // Docker parses our stdout, so any logging has to go to stderr instead.
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_writer(io::stderr))
+        .with(
+            EnvFilter::builder()
+                .with_default_directive(LevelFilter::WARN.into())
+                .from_env()
+                .unwrap(),
+        )
+        .init();
+
+    let args = [OsString::from("pwm"), OsString::from("docker-credential")]
+        .into_iter()
+        .chain(env::args_os().skip(1));
+    pwm_lib::cli::Cli::parse_from(args).execute_command()
+}
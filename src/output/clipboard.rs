@@ -12,22 +12,179 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::output::{encode_for_display, InputEncoding, OutputHandler};
+//! Clipboard output. Copying a secret spawns a detached background process
+//! (`pwm clipboard-daemon`, re-executing ourselves) which owns the clipboard
+//! contents, waits for the configured timeout, and then restores whatever
+//! was on the clipboard before, but only if the clipboard still holds our
+//! secret (i.e., the user hasn't copied something else in the meantime).
+//! The secret is handed to the daemon over a pipe, never on its command line.
+
+use crate::configuration;
+use crate::output::{encode_for_display, ClipboardSelection, InputEncoding, OutputHandler};
+use crate::util::secret::read_to_secret;
 use anyhow::{bail, Result};
 use bdrck::crypto::secret::Secret;
-use clipboard::{self, ClipboardProvider};
-use once_cell::sync::Lazy;
+use clipboard::x11_clipboard::{Clipboard, Primary, X11ClipboardContext};
+use clipboard::ClipboardProvider;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::fd::AsFd;
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
 use std::thread::sleep;
 use std::time::Duration;
 use tracing::info;
+use zeroize::Zeroizing;
+
+/// The command line to run the background clearer with.
+static DAEMON_COMMAND: &str = "clipboard-daemon";
+
+/// The daemon reports whether it set the clipboard on a stdout line starting
+/// with this prefix, so it can't be confused with any log output.
+static DAEMON_STATUS_PREFIX: &str = "clipboard-daemon-status: ";
+static DAEMON_SUCCESS: &str = "ok";
+
+/// The environment variable which is set if we're running under Wayland.
+static WAYLAND_DISPLAY_VARIABLE: &str = "WAYLAND_DISPLAY";
+
+/// Abstracts over the various ways we can access the system clipboard.
+trait ClipboardBackend {
+    /// Return the current clipboard contents, or None if it is empty or holds
+    /// something other than text.
+    fn get(&mut self) -> Option<String>;
+    fn set(&mut self, contents: &str) -> Result<()>;
+    fn clear(&mut self) -> Result<()>;
+}
+
+/// An X11 backend. Note that under X11, the clipboard contents are served by
+/// the process which set them, so they only outlive that process if a
+/// clipboard manager takes them over.
+struct X11Backend<S: clipboard::x11_clipboard::Selection>(X11ClipboardContext<S>);
+
+impl<S: clipboard::x11_clipboard::Selection> X11Backend<S> {
+    fn new() -> Result<Self> {
+        match X11ClipboardContext::<S>::new() {
+            Ok(cp) => Ok(X11Backend(cp)),
+            Err(_) => bail!("failed to get clipboard context"),
+        }
+    }
+}
+
+impl<S: clipboard::x11_clipboard::Selection> ClipboardBackend for X11Backend<S> {
+    fn get(&mut self) -> Option<String> {
+        self.0.get_contents().ok().filter(|c| !c.is_empty())
+    }
+
+    fn set(&mut self, contents: &str) -> Result<()> {
+        match self.0.set_contents(contents.to_owned()) {
+            Ok(_) => Ok(()),
+            Err(_) => bail!("failed to set clipboard contents"),
+        }
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        self.set("")
+    }
+}
+
+/// A Wayland backend, using the `wl-copy` and `wl-paste` tools from
+/// wl-clipboard. These use the data-control protocol where the compositor
+/// supports it, so they work without a focused window.
+struct WaylandBackend {
+    primary: bool,
+}
+
+impl WaylandBackend {
+    fn command(&self, program: &str) -> Command {
+        let mut command = Command::new(program);
+        if self.primary {
+            command.arg("--primary");
+        }
+        command
+    }
+}
 
-static CLIPBOARD_TIMEOUT: Lazy<Duration> = Lazy::new(|| Duration::new(45, 0));
+impl ClipboardBackend for WaylandBackend {
+    fn get(&mut self) -> Option<String> {
+        let output = self
+            .command("wl-paste")
+            .args(["--no-newline", "--type", "text"])
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        if !output.status.success() || output.stdout.is_empty() {
+            return None;
+        }
+        String::from_utf8(output.stdout).ok()
+    }
 
-fn set_contents_string<CP: ClipboardProvider>(cp: &mut CP, contents: String) -> Result<()> {
-    match cp.set_contents(contents) {
-        Ok(_) => Ok(()),
-        Err(_) => bail!("failed to set clipboard contents"),
+    fn set(&mut self, contents: &str) -> Result<()> {
+        let mut child = self
+            .command("wl-copy")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()?;
+        child.stdin.take().unwrap().write_all(contents.as_bytes())?;
+        if !child.wait()?.success() {
+            bail!("failed to set clipboard contents");
+        }
+        Ok(())
     }
+
+    fn clear(&mut self) -> Result<()> {
+        if !self.command("wl-copy").arg("--clear").status()?.success() {
+            bail!("failed to clear clipboard contents");
+        }
+        Ok(())
+    }
+}
+
+fn get_backend(selection: ClipboardSelection) -> Result<Box<dyn ClipboardBackend>> {
+    let primary = selection == ClipboardSelection::Primary;
+    Ok(if env::var_os(WAYLAND_DISPLAY_VARIABLE).is_some() {
+        Box::new(WaylandBackend { primary })
+    } else if primary {
+        Box::new(X11Backend::<Primary>::new()?)
+    } else {
+        Box::new(X11Backend::<Clipboard>::new()?)
+    })
+}
+
+/// Run the background half of clipboard output: read the secret from stdin,
+/// copy it to the clipboard, and restore the previous contents after the
+/// timeout. Whether copying succeeded is reported on stdout, as a status line:
+/// either `DAEMON_SUCCESS`, or an error message.
+pub(crate) fn run_daemon(selection: ClipboardSelection, timeout: Duration) -> Result<()> {
+    // Read from stdin's file descriptor directly, since `io::Stdin`'s buffer
+    // would keep a copy of the secret.
+    let mut stdin = File::from(io::stdin().as_fd().try_clone_to_owned()?);
+    let secret = read_to_secret(&mut stdin)?;
+    let contents = std::str::from_utf8(unsafe { secret.as_slice() })?;
+
+    let result = get_backend(selection).and_then(|mut backend| {
+        // The previous contents may well be sensitive too.
+        let previous = backend.get().map(Zeroizing::new);
+        backend.set(contents)?;
+        Ok((backend, previous))
+    });
+    let mut stdout = io::stdout();
+    match result.as_ref() {
+        Ok(_) => writeln!(stdout, "{}{}", DAEMON_STATUS_PREFIX, DAEMON_SUCCESS)?,
+        Err(e) => writeln!(stdout, "{}{:#}", DAEMON_STATUS_PREFIX, e)?,
+    }
+    stdout.flush()?;
+    let (mut backend, previous) = result?;
+    sleep(timeout);
+
+    let current = backend.get().map(Zeroizing::new);
+    if current.as_deref().map(String::as_str) == Some(contents) {
+        match previous {
+            Some(previous) => backend.set(&previous)?,
+            None => backend.clear()?,
+        }
+    }
+    Ok(())
 }
 
 pub(crate) struct ClipboardOutputHandler;
@@ -38,25 +195,51 @@ impl OutputHandler for ClipboardOutputHandler {
     /// valid UTF-8-encoded string, then the clipboard will be populated with a
     /// base-64 encoded version of the data.
     fn handle(&self, secret: &Secret, encoding: InputEncoding) -> Result<()> {
-        let mut cp: clipboard::x11_clipboard::X11ClipboardContext<
-            clipboard::x11_clipboard::Clipboard,
-        > = match clipboard::x11_clipboard::X11ClipboardContext::new() {
-            Ok(cp) => cp,
-            Err(_) => bail!("failed to get clipboard context"),
-        };
-
+        let config = configuration::get()?;
+        let timeout = config.get_clipboard_timeout();
         let display = encode_for_display(secret, encoding, /*supports_binary=*/ false)?;
-        set_contents_string(
-            &mut cp,
-            std::str::from_utf8(unsafe { display.as_slice() })?.to_owned(),
-        )?;
+
+        let mut child = Command::new(env::current_exe()?)
+            .arg(DAEMON_COMMAND)
+            .arg("--selection")
+            .arg(config.get_clipboard_selection().to_string())
+            .arg("--timeout")
+            .arg(timeout.as_secs().to_string())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            // Detach from our process group, so e.g. ^C in the terminal
+            // doesn't stop the clearer.
+            .process_group(0)
+            .spawn()?;
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(unsafe { display.as_slice() })?;
+
+        // Wait for the daemon to tell us whether it actually managed to set
+        // the clipboard. After that, it's left running in the background.
+        let status = BufReader::new(child.stdout.take().unwrap())
+            .lines()
+            .map_while(|line| line.ok())
+            .find_map(|line| line.strip_prefix(DAEMON_STATUS_PREFIX).map(str::to_owned));
+        match status.as_deref() {
+            Some(s) if s == DAEMON_SUCCESS => {}
+            Some(error) => {
+                child.wait()?;
+                bail!("failed to copy to the clipboard: {}", error);
+            }
+            None => {
+                child.wait()?;
+                bail!("the clipboard daemon exited without setting the clipboard");
+            }
+        }
+
         info!(
             "Copied stored password or key to clipboard. Will clear in {} seconds.",
-            CLIPBOARD_TIMEOUT.as_secs()
+            timeout.as_secs()
         );
-        sleep(*CLIPBOARD_TIMEOUT);
-        set_contents_string(&mut cp, "".to_owned())?;
-
         Ok(())
     }
 }
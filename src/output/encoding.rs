@@ -0,0 +1,151 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Text encodings for secrets, so binary secrets (e.g. raw key material) can
+//! be passed through channels which only accept text, and back again.
+
+use anyhow::{bail, Result};
+use bdrck::crypto::secret::Secret;
+use clap::ValueEnum;
+use data_encoding::{
+    Encoding, BASE32, BASE32_NOPAD, BASE64, BASE64URL_NOPAD, BASE64_NOPAD, HEXLOWER,
+    HEXLOWER_PERMISSIVE,
+};
+
+static PEM_LABEL: &str = "PWM SECRET";
+const PEM_LINE_LENGTH: usize = 64;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum SecretEncoding {
+    /// The secret's raw bytes, unmodified.
+    Raw,
+    /// Standard base64 (RFC 4648), with padding.
+    Base64,
+    /// URL-safe base64 (RFC 4648), without padding.
+    Base64url,
+    /// Base32 (RFC 4648), with padding.
+    Base32,
+    /// Lowercase hexadecimal.
+    Hex,
+    /// Base64, wrapped in PEM-style BEGIN / END armor lines.
+    Pem,
+}
+
+fn to_secret(data: &[u8]) -> Result<Secret> {
+    let mut s = Secret::with_len(data.len())?;
+    unsafe { s.as_mut_slice() }.copy_from_slice(data);
+    Ok(s)
+}
+
+fn encode_with(encoding: &Encoding, data: &[u8]) -> Result<Secret> {
+    let mut s = Secret::with_len(encoding.encode_len(data.len()))?;
+    encoding.encode_mut(data, unsafe { s.as_mut_slice() });
+    Ok(s)
+}
+
+fn decode_with(encoding: &Encoding, encoded: &[u8]) -> Result<Secret> {
+    let mut s = Secret::with_len(encoding.decode_len(encoded.len())?)?;
+    match encoding.decode_mut(encoded, unsafe { s.as_mut_slice() }) {
+        Ok(len) => s.resize(len)?,
+        Err(e) => bail!("failed to decode secret: {}", e.error),
+    };
+    Ok(s)
+}
+
+fn encode_pem(data: &[u8]) -> Result<Secret> {
+    let encoded = encode_with(&BASE64, data)?;
+    let mut armored = format!("-----BEGIN {}-----\n", PEM_LABEL).into_bytes();
+    for line in unsafe { encoded.as_slice() }.chunks(PEM_LINE_LENGTH) {
+        armored.extend_from_slice(line);
+        armored.push(b'\n');
+    }
+    armored.extend_from_slice(format!("-----END {}-----\n", PEM_LABEL).as_bytes());
+    let armored_secret = to_secret(&armored);
+    // Don't leave the base64 data lying around in unprotected memory.
+    armored.iter_mut().for_each(|b| *b = 0);
+    armored_secret
+}
+
+/// Strip PEM armor: everything up to and including the BEGIN line, and from
+/// the END line onwards. Any label is accepted.
+fn strip_pem(data: &[u8]) -> Result<&[u8]> {
+    let find = |needle: &[u8], from: usize| -> Option<usize> {
+        data[from..]
+            .windows(needle.len())
+            .position(|w| w == needle)
+            .map(|i| i + from)
+    };
+    let begin = match find(b"-----BEGIN ", 0) {
+        None => bail!("failed to decode secret: missing PEM BEGIN line"),
+        Some(i) => i,
+    };
+    let body_start = match find(b"\n", begin) {
+        None => bail!("failed to decode secret: truncated PEM data"),
+        Some(i) => i + 1,
+    };
+    let end = match find(b"-----END ", body_start) {
+        None => bail!("failed to decode secret: missing PEM END line"),
+        Some(i) => i,
+    };
+    Ok(&data[body_start..end])
+}
+
+/// Encode the given secret as text (or, for `Raw`, leave it as-is).
+pub fn encode(secret: &Secret, encoding: SecretEncoding) -> Result<Secret> {
+    let data = unsafe { secret.as_slice() };
+    match encoding {
+        SecretEncoding::Raw => secret.try_clone().map_err(|e| e.into()),
+        SecretEncoding::Base64 => encode_with(&BASE64, data),
+        SecretEncoding::Base64url => encode_with(&BASE64URL_NOPAD, data),
+        SecretEncoding::Base32 => encode_with(&BASE32, data),
+        SecretEncoding::Hex => encode_with(&HEXLOWER, data),
+        SecretEncoding::Pem => encode_pem(data),
+    }
+}
+
+/// Decode the given text back into the original secret. Whitespace (e.g. line
+/// breaks or a trailing newline) and padding are ignored.
+pub fn decode(encoded: &Secret, encoding: SecretEncoding) -> Result<Secret> {
+    if encoding == SecretEncoding::Raw {
+        return Ok(encoded.try_clone()?);
+    }
+
+    let data = unsafe { encoded.as_slice() };
+    let data = match encoding {
+        SecretEncoding::Pem => strip_pem(data)?,
+        _ => data,
+    };
+    let mut normalized = Secret::with_len(data.len())?;
+    let mut len = 0;
+    for b in data
+        .iter()
+        .filter(|b| !b.is_ascii_whitespace() && **b != b'=')
+    {
+        unsafe { normalized.as_mut_slice()[len] = *b };
+        len += 1;
+    }
+    normalized.resize(len)?;
+    if encoding == SecretEncoding::Base32 {
+        unsafe { normalized.as_mut_slice() }.make_ascii_uppercase();
+    }
+
+    let normalized = unsafe { normalized.as_slice() };
+    match encoding {
+        SecretEncoding::Raw => unreachable!(),
+        SecretEncoding::Base64 | SecretEncoding::Pem => decode_with(&BASE64_NOPAD, normalized),
+        SecretEncoding::Base64url => decode_with(&BASE64URL_NOPAD, normalized),
+        SecretEncoding::Base32 => decode_with(&BASE32_NOPAD, normalized),
+        SecretEncoding::Hex => decode_with(&HEXLOWER_PERMISSIVE, normalized),
+    }
+}
@@ -0,0 +1,103 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::output::{encode_for_display, InputEncoding, OutputHandler};
+use crate::util::tmpfs::{is_tmpfs_file, TMPFS_TYPE};
+use anyhow::{bail, Result};
+use bdrck::crypto::secret::Secret;
+use std::fs::{self, File, OpenOptions, Permissions};
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::PathBuf;
+
+/// Output files are only ever readable and writable by their owner.
+const OUTPUT_FILE_MODE: u32 = 0o600;
+
+/// Writes secrets to a file, which is created with restrictive permissions.
+pub(crate) struct FileOutputHandler {
+    pub path: PathBuf,
+    /// Replace the file if it already exists.
+    pub overwrite: bool,
+    /// Refuse to write the file unless it is on a tmpfs (i.e., in memory),
+    /// so the secret never reaches persistent storage.
+    pub require_tmpfs: bool,
+}
+
+impl FileOutputHandler {
+    /// Check that the file we opened is one we're willing to write the secret
+    /// to. This checks the open file rather than its path, so nothing can be
+    /// swapped in between the check and the write.
+    fn check(&self, file: &File) -> Result<()> {
+        if !file.metadata()?.file_type().is_file() {
+            bail!(
+                "refusing to write '{}', which isn't a regular file",
+                self.path.display()
+            );
+        }
+        if self.require_tmpfs && !is_tmpfs_file(file)? {
+            bail!(
+                "refusing to write '{}', which isn't on a {}",
+                self.path.display(),
+                TMPFS_TYPE
+            );
+        }
+        Ok(())
+    }
+}
+
+impl OutputHandler for FileOutputHandler {
+    fn handle(&self, secret: &Secret, encoding: InputEncoding) -> Result<()> {
+        let existed = fs::symlink_metadata(&self.path).is_ok();
+        let mut options = OpenOptions::new();
+        // Never follow a symlink at the path, which could point at a file
+        // elsewhere (e.g. on persistent storage) for us to clobber. Existing
+        // files are only truncated once they've been checked.
+        options
+            .write(true)
+            .mode(OUTPUT_FILE_MODE)
+            .custom_flags(libc::O_NOFOLLOW);
+        match self.overwrite {
+            false => options.create_new(true),
+            true => options.create(true),
+        };
+        let mut file = match options.open(&self.path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                bail!("refusing to overwrite '{}'", self.path.display())
+            }
+            Err(e) if e.raw_os_error() == Some(libc::ELOOP) => {
+                bail!(
+                    "refusing to write '{}', which is a symbolic link",
+                    self.path.display()
+                )
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if let Err(e) = self.check(&file) {
+            if !existed {
+                let _ = fs::remove_file(&self.path);
+            }
+            return Err(e);
+        }
+        file.set_len(0)?;
+        // The mode only applies to newly created files, so make sure an
+        // existing file we're overwriting isn't left readable by others.
+        file.set_permissions(Permissions::from_mode(OUTPUT_FILE_MODE))?;
+
+        let display = encode_for_display(secret, encoding, /*supports_binary=*/ true)?;
+        file.write_all(unsafe { display.as_slice() })?;
+        file.flush()?;
+        Ok(())
+    }
+}
@@ -14,16 +14,25 @@
 
 #[cfg(feature = "clipboard")]
 mod clipboard;
+pub mod encoding;
+mod file;
+pub mod osc52;
+mod pipe;
+#[cfg(feature = "wifiqr")]
+pub(crate) mod qr;
 mod stdout;
 
-use crate::util;
+use crate::output::encoding::SecretEncoding;
 use anyhow::{bail, Error, Result};
 use bdrck::crypto::secret::Secret;
 use clap::ValueEnum;
 use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt;
+use std::path::Path;
 use std::str::FromStr;
+use std::time::Duration;
 
 #[derive(Clone, Copy)]
 pub enum InputEncoding {
@@ -34,6 +43,10 @@ pub enum InputEncoding {
     /// Automatically detect the input's encoding. Try to interpret it as UTF-8,
     /// and if this fails, assume it is arbitrary binary data.
     Auto,
+
+    /// The input has already been encoded exactly as it should be output (see
+    /// `encoding::encode`), so it is output verbatim.
+    Verbatim,
 }
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, ValueEnum)]
@@ -44,6 +57,14 @@ pub enum OutputMethod {
     /// Store the output in the system's clipboard.
     #[cfg(feature = "clipboard")]
     Clipboard,
+
+    /// Store the output in the terminal's clipboard, using the OSC 52 escape
+    /// sequence. This works over SSH, if the local terminal supports it.
+    Osc52,
+
+    /// Display the output as a QR code in the terminal.
+    #[cfg(feature = "wifiqr")]
+    Qr,
 }
 
 impl Default for OutputMethod {
@@ -52,11 +73,48 @@ impl Default for OutputMethod {
     }
 }
 
+/// Which X11 / Wayland selection clipboard output uses.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize, ValueEnum)]
+pub enum ClipboardSelection {
+    /// The normal clipboard, pasted with e.g. Ctrl+V.
+    #[default]
+    Clipboard,
+    /// The primary selection, pasted with e.g. a middle click.
+    Primary,
+}
+
+impl fmt::Display for ClipboardSelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ClipboardSelection::Clipboard => "clipboard",
+                ClipboardSelection::Primary => "primary",
+            }
+        )
+    }
+}
+
+impl FromStr for ClipboardSelection {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match <Self as ValueEnum>::from_str(s, /*ignore_case=*/ true) {
+            Ok(selection) => Ok(selection),
+            Err(_) => bail!("invalid clipboard selection '{}'", s),
+        }
+    }
+}
+
 static OUTPUT_METHOD_STRINGS: Lazy<HashMap<OutputMethod, &'static str>> = Lazy::new(|| {
     let mut m = HashMap::new();
     m.insert(OutputMethod::Stdout, "Stdout");
     #[cfg(feature = "clipboard")]
     m.insert(OutputMethod::Clipboard, "Clipboard");
+    m.insert(OutputMethod::Osc52, "Osc52");
+    #[cfg(feature = "wifiqr")]
+    m.insert(OutputMethod::Qr, "Qr");
     m
 });
 
@@ -98,6 +156,7 @@ pub fn encode_for_display(
     let is_binary = match encoding {
         InputEncoding::Binary => true,
         InputEncoding::Auto => as_utf8.is_err(),
+        InputEncoding::Verbatim => false,
     };
 
     Ok(if !is_binary {
@@ -105,13 +164,7 @@ pub fn encode_for_display(
     } else if supports_binary {
         secret.try_clone()?
     } else {
-        // TODO: Don't use encode() here, do something in-place or directly into a new Secret.
-        let encoded = util::secret::encode(&secret).into_bytes();
-        let mut s = Secret::with_len(encoded.len())?;
-        unsafe {
-            s.as_mut_slice().copy_from_slice(encoded.as_slice());
-        }
-        s
+        encoding::encode(secret, SecretEncoding::Base64)?
     })
 }
 
@@ -124,9 +177,55 @@ fn get_handler(method: OutputMethod) -> Box<dyn OutputHandler> {
         OutputMethod::Stdout => Box::new(stdout::StdoutOutputHandler {}),
         #[cfg(feature = "clipboard")]
         OutputMethod::Clipboard => Box::new(clipboard::ClipboardOutputHandler {}),
+        OutputMethod::Osc52 => Box::new(osc52::Osc52OutputHandler {}),
+        #[cfg(feature = "wifiqr")]
+        OutputMethod::Qr => Box::new(qr::QrOutputHandler {}),
     }
 }
 
 pub fn output_secret(secret: &Secret, encoding: InputEncoding, method: OutputMethod) -> Result<()> {
     get_handler(method).handle(secret, encoding)
 }
+
+/// Write the given secret to a file, which is created readable only by its
+/// owner. Unless `overwrite` is set, existing files are never replaced. If
+/// `require_tmpfs` is set, the file must be on a tmpfs.
+pub fn output_secret_to_file<P: AsRef<Path>>(
+    secret: &Secret,
+    encoding: InputEncoding,
+    path: P,
+    overwrite: bool,
+    require_tmpfs: bool,
+) -> Result<()> {
+    file::FileOutputHandler {
+        path: path.as_ref().to_owned(),
+        overwrite,
+        require_tmpfs,
+    }
+    .handle(secret, encoding)
+}
+
+/// Write the given secret to the stdin of the given shell command.
+pub fn output_secret_to_command(
+    secret: &Secret,
+    encoding: InputEncoding,
+    command: &str,
+) -> Result<()> {
+    pipe::PipeOutputHandler {
+        command: command.to_owned(),
+    }
+    .handle(secret, encoding)
+}
+
+/// Run the background process which owns (and eventually restores) the
+/// clipboard contents for `OutputMethod::Clipboard`.
+#[cfg(feature = "clipboard")]
+pub fn run_clipboard_daemon(selection: ClipboardSelection, timeout: Duration) -> Result<()> {
+    clipboard::run_daemon(selection, timeout)
+}
+
+/// Run the background process which clears the terminal's clipboard for
+/// `OutputMethod::Osc52`.
+pub fn run_osc52_clear(selection: ClipboardSelection, timeout: Duration) -> Result<()> {
+    osc52::run_clear(selection, timeout)
+}
@@ -0,0 +1,161 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Clipboard output via the OSC 52 terminal escape sequence. The terminal
+//! emulator itself sets its (local) clipboard, so this works even over SSH,
+//! where the remote host has no clipboard of its own.
+
+use crate::configuration;
+use crate::output::{encode_for_display, ClipboardSelection, InputEncoding, OutputHandler};
+use anyhow::Result;
+use bdrck::crypto::secret::Secret;
+use data_encoding::BASE64;
+use std::env;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::thread::sleep;
+use std::time::Duration;
+use tracing::info;
+
+/// The command line to run the background clearer with.
+static CLEAR_COMMAND: &str = "osc52-clear";
+
+static TTY_PATH: &str = "/dev/tty";
+
+/// Payloads which aren't valid base64 cause terminals to clear the selection.
+static CLEAR_PAYLOAD: &[u8] = b"!";
+
+/// GNU screen limits the length of passthrough strings, so payloads must be
+/// split into several chunks of at most this many bytes.
+const SCREEN_CHUNK_LENGTH: usize = 76;
+
+/// Terminal multiplexers swallow escape sequences they don't understand, so
+/// for OSC 52 to reach the real terminal it has to be wrapped in a
+/// multiplexer-specific passthrough sequence.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Multiplexer {
+    None,
+    Tmux,
+    Screen,
+}
+
+impl Multiplexer {
+    /// Detect which multiplexer (if any) we're running inside of.
+    pub fn detect() -> Self {
+        if env::var_os("TMUX").is_some() {
+            Multiplexer::Tmux
+        } else if env::var("TERM").is_ok_and(|t| t.starts_with("screen")) {
+            Multiplexer::Screen
+        } else {
+            Multiplexer::None
+        }
+    }
+}
+
+fn selection_parameter(selection: ClipboardSelection) -> &'static [u8] {
+    match selection {
+        ClipboardSelection::Clipboard => b"c",
+        ClipboardSelection::Primary => b"p",
+    }
+}
+
+/// Write an OSC 52 sequence setting the given selection to the given
+/// (already base64-encoded) payload.
+pub fn write_sequence<W: Write>(
+    w: &mut W,
+    selection: ClipboardSelection,
+    payload: &[u8],
+    multiplexer: Multiplexer,
+) -> Result<()> {
+    match multiplexer {
+        Multiplexer::None => {
+            w.write_all(b"\x1b]52;")?;
+            w.write_all(selection_parameter(selection))?;
+            w.write_all(b";")?;
+            w.write_all(payload)?;
+            w.write_all(b"\x07")?;
+        }
+        Multiplexer::Tmux => {
+            // Within tmux's passthrough, escape characters must be doubled.
+            w.write_all(b"\x1bPtmux;\x1b\x1b]52;")?;
+            w.write_all(selection_parameter(selection))?;
+            w.write_all(b";")?;
+            w.write_all(payload)?;
+            w.write_all(b"\x07\x1b\\")?;
+        }
+        Multiplexer::Screen => {
+            w.write_all(b"\x1bP\x1b]52;")?;
+            w.write_all(selection_parameter(selection))?;
+            w.write_all(b";")?;
+            for chunk in payload.chunks(SCREEN_CHUNK_LENGTH) {
+                w.write_all(chunk)?;
+                w.write_all(b"\x1b\\\x1bP")?;
+            }
+            w.write_all(b"\x07\x1b\\")?;
+        }
+    }
+    w.flush()?;
+    Ok(())
+}
+
+fn write_to_tty(selection: ClipboardSelection, payload: &[u8]) -> Result<()> {
+    let mut tty = OpenOptions::new().write(true).open(TTY_PATH)?;
+    write_sequence(&mut tty, selection, payload, Multiplexer::detect())
+}
+
+/// Wait for the given timeout, and then clear the terminal's clipboard.
+pub(crate) fn run_clear(selection: ClipboardSelection, timeout: Duration) -> Result<()> {
+    sleep(timeout);
+    write_to_tty(selection, CLEAR_PAYLOAD)
+}
+
+pub(crate) struct Osc52OutputHandler;
+
+impl OutputHandler for Osc52OutputHandler {
+    /// Copy the given data to the terminal's clipboard. Binary data is base-64
+    /// encoded first, just as for the system clipboard.
+    fn handle(&self, secret: &Secret, encoding: InputEncoding) -> Result<()> {
+        let config = configuration::get()?;
+        let selection = config.get_clipboard_selection();
+        let timeout = config.get_clipboard_timeout();
+
+        let display = encode_for_display(secret, encoding, /*supports_binary=*/ false)?;
+        let display = unsafe { display.as_slice() };
+        let mut payload = Secret::with_len(BASE64.encode_len(display.len()))?;
+        BASE64.encode_mut(display, unsafe { payload.as_mut_slice() });
+        write_to_tty(selection, unsafe { payload.as_slice() })?;
+
+        // Clear the clipboard later from a detached process, so we don't block
+        // the terminal in the meantime.
+        Command::new(env::current_exe()?)
+            .arg(CLEAR_COMMAND)
+            .arg("--selection")
+            .arg(selection.to_string())
+            .arg("--timeout")
+            .arg(timeout.as_secs().to_string())
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .process_group(0)
+            .spawn()?;
+
+        info!(
+            "Copied stored password or key to the terminal's clipboard. Will clear in {} seconds.",
+            timeout.as_secs()
+        );
+        Ok(())
+    }
+}
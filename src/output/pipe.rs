@@ -0,0 +1,56 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::output::{encode_for_display, InputEncoding, OutputHandler};
+use anyhow::{bail, Result};
+use bdrck::crypto::secret::Secret;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+static SHELL: &str = "/bin/sh";
+
+/// Writes secrets to the stdin of a child process, run via the shell. The
+/// child's stdout and stderr are passed through.
+pub(crate) struct PipeOutputHandler {
+    pub command: String,
+}
+
+impl OutputHandler for PipeOutputHandler {
+    fn handle(&self, secret: &Secret, encoding: InputEncoding) -> Result<()> {
+        let display = encode_for_display(secret, encoding, /*supports_binary=*/ true)?;
+
+        let mut child = Command::new(SHELL)
+            .arg("-c")
+            .arg(&self.command)
+            .stdin(Stdio::piped())
+            .spawn()?;
+        {
+            let mut stdin = child.stdin.take().unwrap();
+            // If the child exits without reading everything, that's its
+            // business; we report its exit status below either way.
+            let result = stdin.write_all(unsafe { display.as_slice() });
+            if let Err(e) = result {
+                if e.kind() != std::io::ErrorKind::BrokenPipe {
+                    return Err(e.into());
+                }
+            }
+        }
+
+        let status = child.wait()?;
+        if !status.success() {
+            bail!("'{}' failed: {}", self.command, status);
+        }
+        Ok(())
+    }
+}
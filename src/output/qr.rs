@@ -0,0 +1,105 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Output secrets as QR codes, either rendered directly in the terminal or
+//! written to an image file, so they can be scanned with e.g. a phone.
+
+use crate::configuration;
+use crate::output::{encode_for_display, InputEncoding, OutputHandler};
+use crate::wifiqr::ErrorCorrection;
+use anyhow::Result;
+use bdrck::crypto::secret::Secret;
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc;
+use std::thread;
+
+/// The width of the blank border around the code, in modules. The standard
+/// asks for 4, but 2 is plenty for scanning off a screen, and saves space.
+const QUIET_ZONE: usize = 2;
+
+/// Clear the screen and its scrollback, and move the cursor home.
+static CLEAR_SCREEN: &str = "\x1b[2J\x1b[3J\x1b[H";
+
+static RESET_COLORS: &str = "\x1b[0m";
+
+fn module_colors(dark: bool) -> (u8, u8) {
+    // (foreground, background) SGR codes for black or bright white.
+    match dark {
+        true => (30, 40),
+        false => (97, 107),
+    }
+}
+
+/// Render the given data as a QR code made of Unicode half-block characters,
+/// two modules per character cell. Colors are set explicitly (dark on light),
+/// so the code scans correctly regardless of the terminal's color scheme.
+pub(crate) fn render_terminal(data: &[u8], error_correction: ErrorCorrection) -> Result<String> {
+    let matrix = qrcode_generator::to_matrix(data, error_correction.to_upstream())?;
+    let size = matrix.len() + 2 * QUIET_ZONE;
+    let is_dark = |row: usize, col: usize| -> bool {
+        row >= QUIET_ZONE
+            && col >= QUIET_ZONE
+            && row - QUIET_ZONE < matrix.len()
+            && col - QUIET_ZONE < matrix.len()
+            && matrix[row - QUIET_ZONE][col - QUIET_ZONE]
+    };
+
+    let mut rendered = String::new();
+    for row in (0..size).step_by(2) {
+        for col in 0..size {
+            // The upper half block is drawn in the foreground color, and the
+            // lower half shows the background color.
+            let (fg, _) = module_colors(is_dark(row, col));
+            let (_, bg) = module_colors(row + 1 < size && is_dark(row + 1, col));
+            rendered.push_str(&format!("\x1b[{};{}m\u{2580}", fg, bg));
+        }
+        rendered.push_str(RESET_COLORS);
+        rendered.push('\n');
+    }
+    Ok(rendered)
+}
+
+pub(crate) struct QrOutputHandler;
+
+impl OutputHandler for QrOutputHandler {
+    /// Display the given data as a QR code in the terminal, until the user
+    /// presses Enter or the configured timeout expires, and then clear the
+    /// screen. Binary data is base-64 encoded first.
+    fn handle(&self, secret: &Secret, encoding: InputEncoding) -> Result<()> {
+        let timeout = configuration::get()?.get_clipboard_timeout();
+        let display = encode_for_display(secret, encoding, /*supports_binary=*/ false)?;
+        let rendered = render_terminal(unsafe { display.as_slice() }, ErrorCorrection::default())?;
+
+        let mut stdout = io::stdout();
+        stdout.write_all(rendered.as_bytes())?;
+        stdout.flush()?;
+        eprintln!(
+            "Press Enter to clear the screen (or wait {} seconds).",
+            timeout.as_secs()
+        );
+
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut line = String::new();
+            let _ = io::stdin().lock().read_line(&mut line);
+            let _ = tx.send(());
+        });
+        // Either a keypress or the timeout will do; we don't care which.
+        let _ = rx.recv_timeout(timeout);
+
+        stdout.write_all(CLEAR_SCREEN.as_bytes())?;
+        stdout.flush()?;
+        Ok(())
+    }
+}
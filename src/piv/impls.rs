@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use crate::cli::util::get_repository_path;
+use crate::crypto::key::{CompositeKey, PwmKey};
 use crate::crypto::pwgen;
 use crate::piv::util::{prompt_for_device, prompt_for_device_from, PivKeyAssociation};
 use crate::piv::{AddPivArgs, RmPivArgs, SetupPivArgs};
@@ -25,6 +26,9 @@ use std::path::{Path, PathBuf};
 use yubirs::piv;
 use yubirs::piv::id::{Algorithm, Key, PinPolicy, TouchPolicy};
 
+static ADD_KEY_PROMPT: &'static str = "Master password to add: ";
+static REMOVE_KEY_PROMPT: &'static str = "Master password to remove: ";
+
 fn addpiv_impl<RP: AsRef<Path>>(
     repository_path: RP,
     reader: &str,
@@ -37,14 +41,27 @@ fn addpiv_impl<RP: AsRef<Path>>(
     let public_key_pem = public_key.format(piv::pkey::Format::Pem)?;
     let key: piv::key::Key<piv::hal::PcscHardware> =
         piv::key::Key::new(Some(reader), None, slot, public_key)?;
-    repository.add_key(&key)?;
-
-    // Also add the key to our configuration.
+    let configuration = repository.get_crypto_configuration();
+    let wrapping_key_digest = if configuration.requires_two_factor() {
+        // Repositories which require two factors only accept PIV keys which
+        // are combined with a password.
+        let password_key =
+            configuration.get_password_key(None, ADD_KEY_PROMPT, /*confirm=*/ true)?;
+        let key = CompositeKey::new(PwmKey::from(key), password_key);
+        repository.add_key(&key)?;
+        key.get_digest()
+    } else {
+        repository.add_key(&key)?;
+        key.get_digest()
+    };
+
+    // Also add the key to our configuration. Re-read it, since adding the key
+    // recorded some metadata about it.
     let mut configuration = repository.get_crypto_configuration();
     configuration.add_piv_key(PivKeyAssociation {
         reader: reader.to_owned(),
         serial: serial,
-        wrapping_key_digest: key.get_digest(),
+        wrapping_key_digest: wrapping_key_digest,
         slot: slot,
         public_key_pem: public_key_pem,
     });
@@ -159,7 +176,7 @@ fn rmpiv_impl(
     let _handle = crate::init_with_configuration().unwrap();
     let repository = get_repository_path(repository)?;
     let mut repository = Repository::new(&repository, false, None)?;
-    let mut config = repository.get_crypto_configuration();
+    let config = repository.get_crypto_configuration();
 
     let select_from: Vec<(String, u32)> = config
         .get_piv_keys()
@@ -168,12 +185,12 @@ fn rmpiv_impl(
         .collect::<Vec<(String, u32)>>();
     let (reader, serial) =
         prompt_for_device_from(reader.as_ref().map(|r| r.as_str()), serial, select_from)?;
-    let (to_remove, remaining): (Vec<PivKeyAssociation>, Vec<PivKeyAssociation>) = config
+    let to_remove: PivKeyAssociation = config
         .get_piv_keys()
         .iter()
+        .find(|assoc| assoc.reader == reader && assoc.serial == serial)
         .cloned()
-        .partition(|assoc| assoc.reader == reader && assoc.serial == serial);
-    let to_remove = to_remove.into_iter().next().unwrap();
+        .unwrap();
 
     let public_key = piv::pkey::PublicKey::from_pem(io::Cursor::new(to_remove.public_key_pem))?;
     let key: piv::key::Key<piv::hal::PcscHardware> = piv::key::Key::new(
@@ -183,9 +200,14 @@ fn rmpiv_impl(
         public_key,
     )?;
 
-    repository.remove_key(&key)?;
-    config.set_piv_keys(remaining);
-    repository.set_crypto_configuration(config);
+    // Removing the key also removes its association from our configuration.
+    if config.requires_two_factor() {
+        let password_key =
+            config.get_password_key(None, REMOVE_KEY_PROMPT, /*confirm=*/ false)?;
+        repository.remove_key(&CompositeKey::new(PwmKey::from(key), password_key))?;
+    } else {
+        repository.remove_key(&key)?;
+    }
 
     Ok(())
 }
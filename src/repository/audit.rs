@@ -0,0 +1,187 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::crypto::breach::BreachDatabase;
+use crate::crypto::derive::Derivation;
+use crate::crypto::rng::Generator;
+use crate::crypto::strength;
+use crate::repository::serde::for_each_decrypted;
+use crate::repository::Repository;
+use crate::util;
+use anyhow::Result;
+use hmac::digest::KeyInit;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime};
+
+/// The length of the random key used to hash passwords while looking for
+/// duplicates.
+const DUPLICATE_HASH_KEY_LENGTH: usize = 32;
+
+/// The number of bytes of each keyed hash to report as a fingerprint.
+const FINGERPRINT_LENGTH: usize = 8;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Entries longer than this many bytes (e.g. private keys or certificates)
+/// aren't passwords, so we don't estimate their strength.
+const MAX_PASSWORD_LENGTH: usize = 1024;
+
+/// A stored password whose estimated strength is below the minimum.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct WeakEntry {
+    pub path: String,
+    pub score: u8,
+    pub warning: Option<String>,
+}
+
+/// A stored password which appears in a breached password corpus.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct BreachedEntry {
+    pub path: String,
+    /// The number of times this password was seen in breaches.
+    pub count: u64,
+}
+
+/// A set of paths which all store the same password. Rather than the
+/// password itself, these are identified by a fingerprint of a keyed hash of
+/// it. The key is random and never stored, so fingerprints are only
+/// comparable within a single report.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ReusedGroup {
+    pub fingerprint: String,
+    pub paths: Vec<String>,
+}
+
+/// A stored password which hasn't been changed in a long time.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct StaleEntry {
+    pub path: String,
+    pub last_changed: String,
+    pub age_days: u64,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Report {
+    pub weak: Vec<WeakEntry>,
+    pub breached: Vec<BreachedEntry>,
+    pub reused: Vec<ReusedGroup>,
+    pub stale: Vec<StaleEntry>,
+}
+
+impl Report {
+    pub fn is_clean(&self) -> bool {
+        self.weak.is_empty()
+            && self.breached.is_empty()
+            && self.reused.is_empty()
+            && self.stale.is_empty()
+    }
+}
+
+/// Return whether the given entry looks like a password, i.e. a single line of
+/// reasonably short UTF-8 text. Other entries (binary data, keys, notes) would
+/// just be reported as weak or strong at random, and can be large enough to
+/// make estimating their strength slow.
+fn is_password(plaintext: &[u8]) -> bool {
+    let line = plaintext.strip_suffix(b"\n").unwrap_or(plaintext);
+    line.len() <= MAX_PASSWORD_LENGTH
+        && !line.iter().any(|b| *b == b'\n' || *b == 0)
+        && std::str::from_utf8(line).is_ok()
+}
+
+/// Audit every password stored in the given repository. Passwords with a
+/// strength score below `min_score` are reported as weak, and passwords which
+/// haven't been changed in at least `max_age` are reported as stale.
+/// If a breach database is given, passwords found in it are also reported.
+/// Entries which don't look like passwords aren't checked for strength, and
+/// derivation entries are checked as the passwords they derive.
+/// Plaintext is never included in the report.
+pub fn audit(
+    repository: &Repository,
+    min_score: u8,
+    max_age: Duration,
+    mut breach_db: Option<&mut BreachDatabase>,
+) -> Result<Report> {
+    let mut key = vec![0_u8; DUPLICATE_HASH_KEY_LENGTH];
+    Generator.fill_bytes(&mut key);
+
+    let mut report = Report::default();
+    let mut by_hash: BTreeMap<Vec<u8>, Vec<String>> = BTreeMap::new();
+    for_each_decrypted(repository, |path, mut plaintext| {
+        let path = path.to_str()?.to_owned();
+        if Derivation::is_derivation(&plaintext) {
+            plaintext = repository.derive_password(&Derivation::from_secret(&plaintext)?)?;
+        }
+
+        if is_password(unsafe { plaintext.as_slice() }) {
+            let estimate = strength::estimate_secret(&plaintext);
+            if estimate.score < min_score {
+                report.weak.push(WeakEntry {
+                    path: path.clone(),
+                    score: estimate.score,
+                    warning: estimate.warning().map(|w| w.to_owned()),
+                });
+            }
+        }
+
+        if let Some(db) = breach_db.as_mut() {
+            if let Some(count) = db.lookup(&plaintext)? {
+                report.breached.push(BreachedEntry {
+                    path: path.clone(),
+                    count,
+                });
+            }
+        }
+
+        let mut mac = <Hmac<Sha256> as KeyInit>::new_from_slice(&key)?;
+        mac.update(unsafe { plaintext.as_slice() });
+        by_hash
+            .entry(mac.finalize().into_bytes().to_vec())
+            .or_default()
+            .push(path);
+        Ok(())
+    })?;
+
+    report.reused = by_hash
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|(hash, mut paths)| {
+            paths.sort();
+            ReusedGroup {
+                fingerprint: data_encoding::HEXLOWER.encode(&hash[..FINGERPRINT_LENGTH]),
+                paths,
+            }
+        })
+        .collect();
+
+    let now = SystemTime::now();
+    for (path, time) in repository.last_modified_times()? {
+        let age = now.duration_since(time).unwrap_or_default();
+        if age >= max_age {
+            report.stale.push(StaleEntry {
+                path: path.to_str()?.to_owned(),
+                last_changed: util::format_utc_time(time),
+                age_days: age.as_secs() / SECONDS_PER_DAY,
+            });
+        }
+    }
+
+    report.weak.sort_by(|a, b| a.path.cmp(&b.path));
+    report.breached.sort_by(|a, b| a.path.cmp(&b.path));
+    report.stale.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(report)
+}
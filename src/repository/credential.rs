@@ -0,0 +1,376 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! An implementation of git's credential helper protocol (see
+//! gitcredentials(7) and git-credential(1)), storing credentials in a pwm
+//! repository.
+//!
+//! Credentials are stored at a path built from the request's attributes,
+//! according to a configurable scheme like `git/{protocol}/{host}/{username}`.
+//! The username is part of the path, so only the password is stored.
+
+use crate::crypto::derive::Derivation;
+use crate::repository::path::Path as RepositoryPath;
+use crate::repository::template::get_field;
+use crate::repository::Repository;
+use anyhow::{bail, Error, Result};
+use bdrck::crypto::secret::Secret;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+
+pub static DEFAULT_PATH_SCHEME: &str = "git/{protocol}/{host}/{username}";
+
+static USERNAME_ATTRIBUTE: &str = "username";
+static PASSWORD_ATTRIBUTE: &str = "password";
+/// Attributes which may be used in a path scheme. Of these, only the username
+/// and host are required.
+static SCHEME_ATTRIBUTES: [&str; 4] = ["protocol", "host", "path", "username"];
+static REQUIRED_SCHEME_ATTRIBUTES: [&str; 2] = ["host", "username"];
+/// The only attribute whose value may span several path components.
+static PATH_ATTRIBUTE: &str = "path";
+
+/// A request from git: everything but the password is ordinary data, but the
+/// password (given to 'store' and 'erase') is kept in a `Secret`.
+pub struct Request {
+    pub attributes: BTreeMap<String, String>,
+    pub password: Option<Secret>,
+}
+
+impl Request {
+    /// Read a request in git's `key=value` format, up to a blank line or EOF.
+    pub fn read<R: BufRead>(mut r: R) -> Result<Self> {
+        let mut attributes = BTreeMap::new();
+        let mut password = None;
+        loop {
+            let mut line = Vec::new();
+            if r.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            let result = Self::parse_line(&line, &mut attributes, &mut password);
+            line.iter_mut().for_each(|b| *b = 0);
+            if !result? {
+                break;
+            }
+        }
+        Ok(Request {
+            attributes,
+            password,
+        })
+    }
+
+    /// Parse a single line, returning false if it is the blank line which
+    /// terminates the request.
+    fn parse_line(
+        line: &[u8],
+        attributes: &mut BTreeMap<String, String>,
+        password: &mut Option<Secret>,
+    ) -> Result<bool> {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        if line.is_empty() {
+            return Ok(false);
+        }
+        let equals = match line.iter().position(|b| *b == b'=') {
+            Some(i) => i,
+            None => bail!("invalid credential attribute line"),
+        };
+        let (key, value) = (std::str::from_utf8(&line[..equals])?, &line[equals + 1..]);
+        if key == PASSWORD_ATTRIBUTE {
+            let mut s = Secret::with_len(value.len())?;
+            unsafe { s.as_mut_slice() }.copy_from_slice(value);
+            *password = Some(s);
+        } else {
+            attributes.insert(key.to_owned(), String::from_utf8(value.to_vec())?);
+        }
+        Ok(true)
+    }
+
+    fn username(&self) -> Option<&str> {
+        self.attributes.get(USERNAME_ATTRIBUTE).map(String::as_str)
+    }
+}
+
+/// Write a response to a 'get' request, in git's `key=value` format.
+pub fn write_response<W: Write>(mut w: W, username: &str, password: &Secret) -> Result<()> {
+    let password = unsafe { password.as_slice() };
+    if username.contains('\n') || password.contains(&b'\n') {
+        bail!("credentials containing newlines can't be passed to git");
+    }
+    write!(
+        w,
+        "{}={}\n{}=",
+        USERNAME_ATTRIBUTE, username, PASSWORD_ATTRIBUTE
+    )?;
+    w.write_all(password)?;
+    w.write_all(b"\n")?;
+    w.flush()?;
+    Ok(())
+}
+
+/// A scheme for mapping credential attributes to repository paths, like
+/// `git/{protocol}/{host}/{username}`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PathScheme {
+    scheme: String,
+}
+
+impl Default for PathScheme {
+    fn default() -> Self {
+        PathScheme {
+            scheme: DEFAULT_PATH_SCHEME.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for PathScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.scheme)
+    }
+}
+
+/// Split the given scheme into literal text and `{attribute}` references.
+fn parse_scheme(scheme: &str) -> Result<Vec<(bool, &str)>> {
+    let mut parts = Vec::new();
+    let mut rest = scheme;
+    while let Some(open) = rest.find('{') {
+        let close = match rest[open..].find('}') {
+            Some(i) => open + i,
+            None => bail!("unterminated '{{' in path scheme '{}'", scheme),
+        };
+        parts.push((false, &rest[..open]));
+        parts.push((true, &rest[open + 1..close]));
+        rest = &rest[close + 1..];
+    }
+    parts.push((false, rest));
+    Ok(parts)
+}
+
+impl FromStr for PathScheme {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parts = parse_scheme(s)?;
+        let attributes: Vec<&str> = parts.iter().filter(|p| p.0).map(|p| p.1).collect();
+        if let Some(a) = attributes.iter().find(|a| !SCHEME_ATTRIBUTES.contains(a)) {
+            bail!(
+                "unknown attribute '{{{}}}' in path scheme; expected one of {}",
+                a,
+                SCHEME_ATTRIBUTES
+                    .iter()
+                    .map(|a| format!("{{{}}}", a))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        for required in REQUIRED_SCHEME_ATTRIBUTES {
+            if attributes.iter().filter(|a| **a == required).count() != 1 {
+                bail!("path scheme must contain '{{{}}}' exactly once", required);
+            }
+        }
+        Ok(PathScheme {
+            scheme: s.to_owned(),
+        })
+    }
+}
+
+/// Return whether the given string can be used as a single component of a
+/// path: i.e., it can't escape from, or collide with, the rest of the path.
+fn is_path_component(s: &str) -> bool {
+    !s.is_empty() && s != "." && s != ".." && !s.contains('/')
+}
+
+/// Return whether the given attribute value is safe to put in a path. Only
+/// the (repository) path attribute may contain several components; its empty
+/// components are harmless, since e.g. `a//b` is the same as `a/b`.
+fn is_valid_value(attribute: &str, value: &str) -> bool {
+    match attribute == PATH_ATTRIBUTE {
+        false => is_path_component(value),
+        true => value
+            .split('/')
+            .filter(|c| !c.is_empty())
+            .all(is_path_component),
+    }
+}
+
+impl PathScheme {
+    /// Render the path for the given attributes. The username is given
+    /// separately, so the scheme can also be rendered around an unknown
+    /// username. Returns None if any attribute used by the scheme is missing,
+    /// or has a value which isn't safe to put in a path.
+    fn render(&self, attributes: &BTreeMap<String, String>, username: &str) -> Option<String> {
+        let mut path = String::new();
+        for (is_attribute, part) in parse_scheme(&self.scheme).ok()? {
+            match (is_attribute, part) {
+                (false, text) => path.push_str(text),
+                (true, a) if a == USERNAME_ATTRIBUTE => path.push_str(username),
+                (true, a) => path.push_str(
+                    attributes
+                        .get(a)
+                        .filter(|v| is_valid_value(a, v.as_str()))?,
+                ),
+            }
+        }
+        Some(path)
+    }
+
+    /// Render the path for a fully specified request.
+    pub fn path_for(&self, attributes: &BTreeMap<String, String>) -> Option<String> {
+        let username = attributes.get(USERNAME_ATTRIBUTE)?;
+        if !is_path_component(username) {
+            return None;
+        }
+        self.render(attributes, username)
+    }
+
+    /// Return the username encoded in the given path, if the path matches
+    /// this scheme for the given (username-less) attributes.
+    pub fn match_username<'a>(
+        &self,
+        attributes: &BTreeMap<String, String>,
+        path: &'a str,
+    ) -> Option<&'a str> {
+        // Render around a marker which can't appear in a path we'd render.
+        let marker = "\0";
+        let rendered = self.render(attributes, marker)?;
+        let (prefix, suffix) = rendered.split_once(marker)?;
+        let username = path.strip_prefix(prefix)?.strip_suffix(suffix)?;
+        match is_path_component(username) {
+            false => None,
+            true => Some(username),
+        }
+    }
+
+    /// Return the directory which all paths matching the given (username-less)
+    /// attributes are in, for listing candidates.
+    fn search_prefix(&self, attributes: &BTreeMap<String, String>) -> Option<String> {
+        let rendered = self.render(attributes, "\0")?;
+        let prefix = &rendered[..rendered.find('\0')?];
+        Some(match prefix.rfind('/') {
+            Some(i) => prefix[..i].to_owned(),
+            None => String::new(),
+        })
+    }
+}
+
+/// Return the path and username of the stored credential matching the given
+/// request, if there is exactly one.
+fn find(
+    repository: &Repository,
+    scheme: &PathScheme,
+    request: &Request,
+) -> Result<Option<(RepositoryPath, String)>> {
+    if let Some(username) = request.username() {
+        return Ok(match scheme.path_for(&request.attributes) {
+            None => None,
+            Some(path) => {
+                let path = repository.path(path)?;
+                match path.absolute_path().exists() {
+                    false => None,
+                    true => Some((path, username.to_owned())),
+                }
+            }
+        });
+    }
+
+    let prefix = match scheme.search_prefix(&request.attributes) {
+        None => return Ok(None),
+        Some(prefix) => repository.path(prefix)?,
+    };
+    if !prefix.absolute_path().exists() {
+        return Ok(None);
+    }
+    let mut matches = Vec::new();
+    for path in repository.list(Some(&prefix))? {
+        if let Some(username) = scheme.match_username(&request.attributes, path.to_str()?) {
+            matches.push((path.clone(), username.to_owned()));
+        }
+    }
+    // If there are several candidates, let git ask which username to use.
+    Ok(match matches.len() {
+        1 => matches.pop(),
+        _ => None,
+    })
+}
+
+/// Return the password from the given stored value. For multiline entries,
+/// this is the first line.
+fn to_password(repository: &Repository, stored: &Secret) -> Result<Secret> {
+    let mut password = match Derivation::is_derivation(stored) {
+        false => stored.try_clone()?,
+        true => repository.derive_password(&Derivation::from_secret(stored)?)?,
+    };
+    let len = get_field(unsafe { password.as_slice() }, PASSWORD_ATTRIBUTE).map_or(0, |p| p.len());
+    password.resize(len)?;
+    Ok(password)
+}
+
+fn read_password(repository: &Repository, path: &RepositoryPath) -> Result<Secret> {
+    to_password(repository, &repository.read_decrypt(path)?)
+}
+
+/// Handle a 'get' request, returning the matching username and password.
+pub fn get(
+    repository: &Repository,
+    scheme: &PathScheme,
+    request: &Request,
+) -> Result<Option<(String, Secret)>> {
+    Ok(match find(repository, scheme, request)? {
+        None => None,
+        Some((path, username)) => Some((username, read_password(repository, &path)?)),
+    })
+}
+
+/// Handle a 'store' request. Git sends this after every successful use of a
+/// credential, so nothing is written if the stored password is unchanged.
+pub fn store(repository: &mut Repository, scheme: &PathScheme, request: &Request) -> Result<()> {
+    let (path, password) = match (scheme.path_for(&request.attributes), &request.password) {
+        (Some(path), Some(password)) => (repository.path(path)?, password),
+        // Git expects helpers to silently ignore requests they can't handle.
+        _ => return Ok(()),
+    };
+    let mut value = password.try_clone()?;
+    if path.absolute_path().exists() {
+        let stored = repository.read_decrypt(&path)?;
+        if unsafe { to_password(repository, &stored)?.as_slice() == password.as_slice() } {
+            return Ok(());
+        }
+        // Keep any other lines of a multiline entry.
+        let is_derivation = Derivation::is_derivation(&stored);
+        let stored = unsafe { stored.as_slice() };
+        if let (false, Some(i)) = (is_derivation, stored.iter().position(|b| *b == b'\n')) {
+            let len = value.len();
+            value.resize(len + stored.len() - i)?;
+            unsafe { value.as_mut_slice()[len..].copy_from_slice(&stored[i..]) };
+        }
+    }
+    repository.write_encrypt(&path, value, None)
+}
+
+/// Handle an 'erase' request. If the request includes a password, the stored
+/// credential is only removed if it still matches (i.e., it hasn't been
+/// updated since git found it didn't work).
+pub fn erase(repository: &mut Repository, scheme: &PathScheme, request: &Request) -> Result<()> {
+    let path = match find(repository, scheme, request)? {
+        None => return Ok(()),
+        Some((path, _)) => path,
+    };
+    if let Some(password) = &request.password {
+        let existing = read_password(repository, &path)?;
+        if unsafe { existing.as_slice() != password.as_slice() } {
+            return Ok(());
+        }
+    }
+    repository.remove(&path)
+}
@@ -0,0 +1,163 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! An implementation of Docker's credential helper protocol (see
+//! https://github.com/docker/docker-credential-helpers), storing registry
+//! credentials in a pwm repository.
+//!
+//! Each registry's credentials are stored under a configurable prefix, at a
+//! path made from its (percent-encoded) server URL. Like other multiline
+//! entries, the first line is the secret, followed by a `username: ...` line.
+
+use crate::repository::path::Path as RepositoryPath;
+use crate::repository::template::get_field;
+use crate::repository::Repository;
+use anyhow::{bail, Result};
+use bdrck::crypto::secret::Secret;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub static DEFAULT_PREFIX: &str = "docker";
+
+/// Docker recognizes this exact message as meaning "no credentials stored",
+/// as opposed to some other error.
+pub static NOT_FOUND_MESSAGE: &str = "credentials not found in native keychain";
+
+static PASSWORD_FIELD: &str = "password";
+static USERNAME_FIELD: &str = "username";
+
+/// Characters which are escaped in server URLs, to turn them into a single
+/// path component.
+const SERVER_URL_ESCAPE: &AsciiSet = &NON_ALPHANUMERIC.remove(b'.').remove(b'-').remove(b'_');
+
+/// A set of credentials, as exchanged with Docker.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Credentials {
+    #[serde(rename = "ServerURL")]
+    pub server_url: String,
+    #[serde(rename = "Username")]
+    pub username: String,
+    #[serde(rename = "Secret")]
+    pub secret: String,
+}
+
+/// Return the repository path the given server's credentials are stored at.
+pub fn entry_path(prefix: &str, server_url: &str) -> Result<String> {
+    if server_url.is_empty() {
+        bail!("no server URL given");
+    }
+    let prefix = prefix.trim_end_matches('/');
+    let mut encoded = utf8_percent_encode(server_url, SERVER_URL_ESCAPE).to_string();
+    // A leading '.' would let "." or ".." escape the prefix, or collide with
+    // the repository's own hidden files, so escape it too.
+    if encoded.starts_with('.') {
+        encoded.replace_range(..1, "%2E");
+    }
+    Ok(match prefix.is_empty() {
+        false => format!("{}/{}", prefix, encoded),
+        true => encoded,
+    })
+}
+
+fn to_secret(value: &str) -> Result<Secret> {
+    let mut s = Secret::with_len(value.len())?;
+    unsafe { s.as_mut_slice() }.copy_from_slice(value.as_bytes());
+    Ok(s)
+}
+
+fn read(
+    repository: &Repository,
+    path: &RepositoryPath,
+    server_url: &str,
+) -> Result<Option<Credentials>> {
+    if !path.absolute_path().exists() {
+        return Ok(None);
+    }
+    let stored = repository.read_decrypt(path)?;
+    let stored = unsafe { stored.as_slice() };
+    let field = |name| -> Result<String> {
+        Ok(String::from_utf8(
+            get_field(stored, name).unwrap_or_default().to_vec(),
+        )?)
+    };
+    Ok(Some(Credentials {
+        server_url: server_url.to_owned(),
+        username: field(USERNAME_FIELD)?,
+        secret: field(PASSWORD_FIELD)?,
+    }))
+}
+
+/// Handle a 'store' request. Docker does this on every login, so nothing is
+/// written if the stored credentials are unchanged.
+pub fn store(repository: &mut Repository, prefix: &str, credentials: &Credentials) -> Result<()> {
+    if credentials.username.contains('\n') || credentials.secret.contains('\n') {
+        bail!("credentials containing newlines are not supported");
+    }
+
+    let path = repository.path(entry_path(prefix, &credentials.server_url)?)?;
+    if read(repository, &path, &credentials.server_url)?.as_ref() == Some(credentials) {
+        return Ok(());
+    }
+    let value = to_secret(&format!(
+        "{}\n{}: {}",
+        credentials.secret, USERNAME_FIELD, credentials.username
+    ))?;
+    repository.write_encrypt(&path, value, None)
+}
+
+/// Handle a 'get' request, returning the given server's credentials.
+pub fn get(repository: &Repository, prefix: &str, server_url: &str) -> Result<Credentials> {
+    let path = repository.path(entry_path(prefix, server_url)?)?;
+    match read(repository, &path, server_url)? {
+        None => bail!("{}", NOT_FOUND_MESSAGE),
+        Some(credentials) => Ok(credentials),
+    }
+}
+
+/// Handle an 'erase' request, removing the given server's credentials.
+pub fn erase(repository: &mut Repository, prefix: &str, server_url: &str) -> Result<()> {
+    let path = repository.path(entry_path(prefix, server_url)?)?;
+    if !path.absolute_path().exists() {
+        bail!("{}", NOT_FOUND_MESSAGE);
+    }
+    repository.remove(&path)
+}
+
+/// Handle a 'list' request, returning the username stored for each server.
+pub fn list(repository: &Repository, prefix: &str) -> Result<BTreeMap<String, String>> {
+    let mut servers = BTreeMap::new();
+    let prefix_path = repository.path(prefix.trim_end_matches('/'))?;
+    if !prefix_path.absolute_path().exists() {
+        return Ok(servers);
+    }
+    for path in repository.list(Some(&prefix_path))? {
+        // Skip anything else which happens to be stored under the prefix.
+        let relative = match path
+            .relative_path()
+            .strip_prefix(prefix_path.relative_path())
+        {
+            Ok(r) if r.components().count() == 1 => r.to_string_lossy().into_owned(),
+            _ => continue,
+        };
+        let server_url = percent_decode_str(&relative).decode_utf8()?.into_owned();
+        if entry_path(prefix, &server_url).ok().as_deref() != Some(path.to_str()?) {
+            continue;
+        }
+        if let Some(credentials) = read(repository, &path, &server_url)? {
+            servers.insert(server_url, credentials.username);
+        }
+    }
+    Ok(servers)
+}
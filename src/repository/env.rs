@@ -0,0 +1,186 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Support for passing stored passwords to other programs as environment
+//! variables, rather than via the command line or the user's shell.
+
+use crate::crypto::derive::Derivation;
+use crate::repository::path::Path as RepositoryPath;
+use crate::repository::Repository;
+use anyhow::{bail, Error, Result};
+use bdrck::crypto::secret::Secret;
+use clap::ValueEnum;
+use std::collections::HashSet;
+use std::path::{Component, Path};
+use std::str::FromStr;
+
+/// A request to set an environment variable to the password stored at a path,
+/// written on the command line as `NAME=PATH`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EnvBinding {
+    pub name: String,
+    pub path: String,
+}
+
+impl FromStr for EnvBinding {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, path) = match s.split_once('=') {
+            Some((name, path)) if !path.is_empty() => (name, path),
+            _ => bail!("expected NAME=PATH, got '{}'", s),
+        };
+        if !is_valid_variable_name(name) {
+            bail!("invalid environment variable name '{}'", name);
+        }
+        Ok(EnvBinding {
+            name: name.to_owned(),
+            path: path.to_owned(),
+        })
+    }
+}
+
+/// How to format environment variable assignments for `pwm env`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum EnvFormat {
+    /// `export NAME='value'` lines, suitable for `eval` or `source`.
+    Shell,
+    /// `NAME="value"` lines, suitable for a .env file.
+    Dotenv,
+}
+
+/// Return whether the given string is a portable environment variable name,
+/// i.e. a letter or underscore followed by letters, digits or underscores.
+pub fn is_valid_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Return the environment variable name for the given stored password path,
+/// relative to the given prefix. For example, with prefix `prod`, the path
+/// `prod/db/password` becomes `DB_PASSWORD`.
+pub fn variable_name(prefix: &Path, path: &Path) -> String {
+    let relative = match path.strip_prefix(prefix) {
+        Ok(r) if !r.as_os_str().is_empty() => r,
+        // The prefix names a single entry, so use its file name.
+        _ => path.file_name().map(Path::new).unwrap_or(path),
+    };
+    let mut name = relative
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(c) => Some(c.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("_")
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_ascii_uppercase(),
+            false => '_',
+        })
+        .collect::<String>();
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        name.insert(0, '_');
+    }
+    name
+}
+
+/// Format a single variable assignment, quoting the value so it is
+/// interpreted literally.
+pub fn format_variable(name: &str, value: &str, format: EnvFormat) -> String {
+    match format {
+        EnvFormat::Shell => format!("export {}='{}'", name, value.replace('\'', "'\\''")),
+        EnvFormat::Dotenv => {
+            let mut escaped = String::with_capacity(value.len());
+            for c in value.chars() {
+                match c {
+                    '\\' => escaped.push_str("\\\\"),
+                    '"' => escaped.push_str("\\\""),
+                    '$' => escaped.push_str("\\$"),
+                    '\n' => escaped.push_str("\\n"),
+                    '\r' => escaped.push_str("\\r"),
+                    c => escaped.push(c),
+                }
+            }
+            format!("{}=\"{}\"", name, escaped)
+        }
+    }
+}
+
+/// Read the password at the given path, in a form which can be stored in an
+/// environment variable. Derived passwords are derived.
+fn read_value(repository: &Repository, path: &RepositoryPath) -> Result<Secret> {
+    let mut value = repository.read_decrypt(path)?;
+    if Derivation::is_derivation(&value) {
+        value = repository.derive_password(&Derivation::from_secret(&value)?)?;
+    }
+    if unsafe { value.as_slice() }.contains(&0) {
+        bail!(
+            "'{}' contains NUL bytes, so it can't be stored in an environment variable",
+            path.relative_path().display()
+        );
+    }
+    Ok(value)
+}
+
+fn check_unique<'a, I: Iterator<Item = &'a str>>(names: I) -> Result<()> {
+    let mut seen = HashSet::new();
+    for name in names {
+        if !seen.insert(name) {
+            bail!(
+                "environment variable '{}' would be set more than once",
+                name
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Read the passwords for all of the given bindings, returning each variable
+/// name along with its value.
+pub fn read_bindings(
+    repository: &Repository,
+    bindings: &[EnvBinding],
+) -> Result<Vec<(String, Secret)>> {
+    check_unique(bindings.iter().map(|b| b.name.as_str()))?;
+    bindings
+        .iter()
+        .map(|b| {
+            let path = repository.path(&b.path)?;
+            Ok((b.name.clone(), read_value(repository, &path)?))
+        })
+        .collect()
+}
+
+/// Read every password under the given path prefix, named according to
+/// `variable_name`.
+pub fn read_subtree(
+    repository: &Repository,
+    prefix: &RepositoryPath,
+) -> Result<Vec<(String, Secret)>> {
+    let values = repository
+        .list(Some(prefix))?
+        .iter()
+        .map(|path| {
+            let name = variable_name(prefix.relative_path(), path.relative_path());
+            Ok((name, read_value(repository, path)?))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    check_unique(values.iter().map(|(name, _)| name.as_str()))?;
+    Ok(values)
+}
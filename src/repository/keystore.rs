@@ -12,16 +12,22 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::crypto::configuration::Configuration;
+use crate::crypto::configuration::{Configuration, KeyFileAssociation};
+use crate::crypto::key::{CompositeKey, DigestKey, KeyError, KeyRef};
+use crate::util::unwrap_password_or_prompt;
 use anyhow::{bail, Error, Result};
+use bdrck::crypto::digest::Digest;
 use bdrck::crypto::key::AbstractKey;
 use bdrck::crypto::keystore::DiskKeyStore;
 use bdrck::crypto::secret::Secret;
+use std::fs;
 use std::path::Path;
 
 static MASTER_PASSWORD_PROMPT: &'static str = "Master password: ";
 static ADD_KEY_PROMPT: &'static str = "Master password to add: ";
 static REMOVE_KEY_PROMPT: &'static str = "Master password to remove: ";
+static OLD_PASSWORD_PROMPT: &str = "Current master password: ";
+static NEW_PASSWORD_PROMPT: &str = "New master password: ";
 
 #[cfg(feature = "piv")]
 fn find_piv_master_key(
@@ -37,29 +43,49 @@ fn find_piv_master_key(
     Ok(None)
 }
 
-fn open(
-    keystore: &mut DiskKeyStore,
+/// Like `find_piv_master_key`, but for repositories which can't be unlocked
+/// without a PIV device, so it's an error if none is found.
+fn find_required_piv_master_key(
     crypto_config: &Configuration,
-    password: Option<Secret>,
-) -> Result<()> {
-    if keystore.is_open() {
-        return Ok(());
+) -> Result<impl AbstractKey<Error = KeyError>> {
+    match find_piv_master_key(crypto_config)? {
+        None => bail!("this repository requires both a password and a PIV device, but no PIV device was found"),
+        Some(k) => Ok(k),
     }
+}
 
-    if let Some(piv_key) = find_piv_master_key(crypto_config)? {
-        if let Err(e) = keystore.open(&piv_key) {
-            eprintln!("Failed to use master PIV key ({})", e);
-        }
-    }
+/// Return a key which requires both the given password (or one prompted for)
+/// *and* one of this repository's PIV devices. This is the only kind of key
+/// accepted by repositories which require two-factor unlocking.
+fn get_two_factor_key(
+    crypto_config: &Configuration,
+    password: Option<Secret>,
+    prompt: &str,
+    confirm: bool,
+) -> Result<impl AbstractKey<Error = KeyError>> {
+    let piv_key = find_required_piv_master_key(crypto_config)?;
+    let password_key = crypto_config.get_password_key(password, prompt, confirm)?;
+    Ok(CompositeKey::new(piv_key, password_key))
+}
 
+/// Repeatedly try to open the key store with keys from `get_key`, until we
+/// succeed. If a hard-coded password was provided, we only try once.
+fn open_with_retry<
+    E: Into<Error>,
+    K: AbstractKey<Error = E>,
+    F: Fn(Option<Secret>) -> Result<K>,
+>(
+    keystore: &mut DiskKeyStore,
+    password: Option<Secret>,
+    get_key: F,
+) -> Result<()> {
     while !keystore.is_open() {
         let pw = if let Some(pw) = password.as_ref() {
             Some(pw.try_clone()?)
         } else {
             None
         };
-        let key =
-            crypto_config.get_password_key(pw, MASTER_PASSWORD_PROMPT, /*confirm=*/ false)?;
+        let key = get_key(pw)?;
 
         if password.is_some() {
             // Only try once, if a hard-coded password was provided.
@@ -74,6 +100,62 @@ fn open(
     Ok(())
 }
 
+fn open(
+    keystore: &mut DiskKeyStore,
+    crypto_config: &Configuration,
+    password: Option<Secret>,
+) -> Result<()> {
+    if keystore.is_open() {
+        return Ok(());
+    }
+
+    // If the repository requires two factors, don't bother trying any of the
+    // single-factor keys below.
+    if crypto_config.requires_two_factor() {
+        return open_with_retry(keystore, password, |pw| {
+            get_two_factor_key(
+                crypto_config,
+                pw,
+                MASTER_PASSWORD_PROMPT,
+                /*confirm=*/ false,
+            )
+        });
+    }
+
+    if let Some(piv_key) = find_piv_master_key(crypto_config)? {
+        if let Err(e) = keystore.open(&piv_key) {
+            eprintln!("Failed to use master PIV key ({})", e);
+        }
+    }
+
+    for assoc in crypto_config.get_key_files() {
+        if keystore.is_open() {
+            break;
+        }
+
+        // Key files are often kept on removable media, so it's not an error
+        // if one isn't currently available.
+        if !assoc.path.exists() {
+            continue;
+        }
+
+        let key = match crypto_config.get_key_file_key(&assoc.path) {
+            Ok(key) => key,
+            Err(e) => {
+                eprintln!("Failed to read key file '{}' ({})", assoc.path.display(), e);
+                continue;
+            }
+        };
+        if let Err(e) = keystore.open(&key) {
+            eprintln!("Failed to use key file '{}' ({})", assoc.path.display(), e);
+        }
+    }
+
+    open_with_retry(keystore, password, |pw| {
+        crypto_config.get_password_key(pw, MASTER_PASSWORD_PROMPT, /*confirm=*/ false)
+    })
+}
+
 pub(crate) fn get_keystore<P: AsRef<Path>>(
     path: P,
     allow_create: bool,
@@ -119,11 +201,41 @@ pub(crate) fn add_password_key(
     crypto_config: &Configuration,
     keystore: &mut DiskKeyStore,
     password: Option<Secret>,
-) -> Result<()> {
-    add_key(
-        keystore,
-        &crypto_config.get_password_key(password, ADD_KEY_PROMPT, /*confirm=*/ true)?,
-    )
+) -> Result<Digest> {
+    if crypto_config.requires_two_factor() {
+        let key = get_two_factor_key(
+            crypto_config,
+            password,
+            ADD_KEY_PROMPT,
+            /*confirm=*/ true,
+        )?;
+        add_key(keystore, &key)?;
+        return Ok(key.get_digest());
+    }
+
+    let key = crypto_config.get_password_key(password, ADD_KEY_PROMPT, /*confirm=*/ true)?;
+    add_key(keystore, &key)?;
+    Ok(key.get_digest())
+}
+
+/// Add a wrapping key derived from the contents of the given key file. The
+/// returned association should be recorded in the crypto configuration, so the
+/// key file can be tried automatically when the key store is next opened.
+pub(crate) fn add_key_file_key<P: AsRef<Path>>(
+    crypto_config: &Configuration,
+    keystore: &mut DiskKeyStore,
+    path: P,
+) -> Result<KeyFileAssociation> {
+    if crypto_config.requires_two_factor() {
+        bail!("this repository requires two-factor unlocking, so key files cannot be added");
+    }
+
+    let key = crypto_config.get_key_file_key(path.as_ref())?;
+    add_key(keystore, &key)?;
+    Ok(KeyFileAssociation {
+        path: fs::canonicalize(path.as_ref())?,
+        wrapping_key_digest: key.get_digest(),
+    })
 }
 
 pub(crate) fn remove_key<E: Into<Error>, K: AbstractKey<Error = E>>(
@@ -141,9 +253,285 @@ pub(crate) fn remove_password_key(
     crypto_config: &Configuration,
     keystore: &mut DiskKeyStore,
     password: Option<Secret>,
+) -> Result<Digest> {
+    if crypto_config.requires_two_factor() {
+        let key = get_two_factor_key(
+            crypto_config,
+            password,
+            REMOVE_KEY_PROMPT,
+            /*confirm=*/ false,
+        )?;
+        remove_key(keystore, &key)?;
+        return Ok(key.get_digest());
+    }
+
+    let key =
+        crypto_config.get_password_key(password, REMOVE_KEY_PROMPT, /*confirm=*/ false)?;
+    remove_key(keystore, &key)?;
+    Ok(key.get_digest())
+}
+
+/// Remove the wrapping key with the given digest. This works for any kind of
+/// key, even one we can't currently reconstruct (e.g. a lost PIV device).
+pub(crate) fn remove_key_by_digest(keystore: &mut DiskKeyStore, digest: &Digest) -> Result<()> {
+    remove_key(keystore, &DigestKey::new(digest.clone()))
+}
+
+/// Remove the wrapping key derived from the contents of the given key file,
+/// returning the digest of the key which was removed.
+pub(crate) fn remove_key_file_key<P: AsRef<Path>>(
+    crypto_config: &Configuration,
+    keystore: &mut DiskKeyStore,
+    path: P,
+) -> Result<Digest> {
+    let key = crypto_config.get_key_file_key(path.as_ref())?;
+    remove_key(keystore, &key)?;
+    Ok(key.get_digest())
+}
+
+/// Add a wrapping key derived from the given recovery secret, replacing any
+/// previous recovery key. The returned digest should be recorded in the crypto
+/// configuration, so the old key can be removed if a new one is generated.
+pub(crate) fn set_recovery_key(
+    crypto_config: &Configuration,
+    keystore: &mut DiskKeyStore,
+    recovery_secret: Secret,
+) -> Result<Digest> {
+    if crypto_config.requires_two_factor() {
+        bail!("this repository requires two-factor unlocking, so a recovery key cannot be added");
+    }
+
+    let key = crypto_config.get_password_key(
+        Some(recovery_secret),
+        MASTER_PASSWORD_PROMPT,
+        /*confirm=*/ false,
+    )?;
+    add_key(keystore, &key)?;
+    if let Some(digest) = crypto_config.get_recovery_key_digest() {
+        // The old recovery key may have already been removed (e.g. with
+        // `rm-key`), so it's fine if it isn't found.
+        keystore.remove_key(&DigestKey::new(digest.clone()))?;
+    }
+    Ok(key.get_digest())
+}
+
+/// Replace the key store's wrapping key `from` with `to`. If removing
+/// `from` fails (e.g. because it isn't actually registered), `to` is removed
+/// again so the key store is left unchanged.
+fn replace_key<
+    EA: Into<Error>,
+    KA: AbstractKey<Error = EA>,
+    EB: Into<Error>,
+    KB: AbstractKey<Error = EB>,
+>(
+    keystore: &mut DiskKeyStore,
+    from: &KA,
+    to: &KB,
+) -> Result<()> {
+    add_key(keystore, to)?;
+    if let Err(e) = remove_key(keystore, from) {
+        remove_key(keystore, to)?;
+        return Err(e);
+    }
+    Ok(())
+}
+
+fn replace_password_key<
+    EA: Into<Error>,
+    KA: AbstractKey<Error = EA>,
+    EB: Into<Error>,
+    KB: AbstractKey<Error = EB>,
+>(
+    keystore: &mut DiskKeyStore,
+    old: &KA,
+    new: &KB,
+) -> Result<(Digest, Digest)> {
+    // Check this up front, just to return a clearer error.
+    if !keystore
+        .iter_wrapped_keys()
+        .any(|k| *k.get_wrapping_digest() == old.get_digest())
+    {
+        bail!("the current master password is incorrect");
+    }
+
+    replace_key(keystore, old, new)?;
+    Ok((old.get_digest(), new.get_digest()))
+}
+
+/// Replace the master password `old` with `new`, returning the digests of the
+/// old and new keys. The old password must already be a key for this key
+/// store. If anything fails, the key store is left unchanged.
+pub(crate) fn change_password(
+    crypto_config: &Configuration,
+    keystore: &mut DiskKeyStore,
+    old: Option<Secret>,
+    new: Option<Secret>,
+) -> Result<(Digest, Digest)> {
+    let old = unwrap_password_or_prompt(old, OLD_PASSWORD_PROMPT, /*confirm=*/ false)?;
+    let new = unwrap_password_or_prompt(new, NEW_PASSWORD_PROMPT, /*confirm=*/ true)?;
+
+    if crypto_config.requires_two_factor() {
+        // Only read the PIV device (and prompt for its PIN) once; both the old
+        // and new keys share it.
+        let piv_key = find_required_piv_master_key(crypto_config)?;
+        let old_key = CompositeKey::new(
+            KeyRef::new(&piv_key),
+            crypto_config.get_password_key(
+                Some(old),
+                OLD_PASSWORD_PROMPT,
+                /*confirm=*/ false,
+            )?,
+        );
+        let new_key = CompositeKey::new(
+            KeyRef::new(&piv_key),
+            crypto_config.get_password_key(
+                Some(new),
+                NEW_PASSWORD_PROMPT,
+                /*confirm=*/ true,
+            )?,
+        );
+        return replace_password_key(keystore, &old_key, &new_key);
+    }
+
+    let old_key =
+        crypto_config.get_password_key(Some(old), OLD_PASSWORD_PROMPT, /*confirm=*/ false)?;
+    let new_key =
+        crypto_config.get_password_key(Some(new), NEW_PASSWORD_PROMPT, /*confirm=*/ true)?;
+    replace_password_key(keystore, &old_key, &new_key)
+}
+
+#[cfg(feature = "piv")]
+fn registered_piv_key_digests(crypto_config: &Configuration) -> Vec<Digest> {
+    crypto_config
+        .get_piv_keys()
+        .iter()
+        .map(|k| k.wrapping_key_digest.clone())
+        .collect()
+}
+
+#[cfg(not(feature = "piv"))]
+fn registered_piv_key_digests(_: &Configuration) -> Vec<Digest> {
+    Vec::new()
+}
+
+/// Turn the two-factor unlocking requirement on or off. This converts the
+/// repository's master password into a key which also requires a PIV device,
+/// or vice versa. Since any other keys could still be used on their own, the
+/// repository must have exactly one password, plus exactly one PIV device
+/// registered with it. Further devices can be added (combined with the
+/// password) once two-factor unlocking is required.
+pub(crate) fn set_requires_two_factor(
+    crypto_config: &mut Configuration,
+    keystore: &mut DiskKeyStore,
+    required: bool,
+    password: Option<Secret>,
 ) -> Result<()> {
-    remove_key(
+    if crypto_config.requires_two_factor() == required {
+        return Ok(());
+    }
+
+    let piv_digests = registered_piv_key_digests(crypto_config);
+    if piv_digests.is_empty() {
+        bail!("requiring two-factor unlocking needs at least one PIV device registered with this repository");
+    }
+
+    let password_key = crypto_config.get_password_key(
+        password,
+        MASTER_PASSWORD_PROMPT,
+        /*confirm=*/ false,
+    )?;
+    let piv_key = match find_piv_master_key(crypto_config)? {
+        None => bail!("no PIV device registered with this repository was found"),
+        Some(k) => k,
+    };
+    set_requires_two_factor_with_keys(
+        crypto_config,
         keystore,
-        &crypto_config.get_password_key(password, REMOVE_KEY_PROMPT, /*confirm=*/ false)?,
+        required,
+        &password_key,
+        &piv_key,
+        piv_digests.as_slice(),
     )
 }
+
+/// The guts of `set_requires_two_factor`, given the master password's key, a
+/// PIV device's key, and the digests of all of the PIV device keys registered
+/// with the repository. Either every change to the keystore is made, or none
+/// of them are.
+pub(crate) fn set_requires_two_factor_with_keys<
+    KP: AbstractKey<Error = KeyError>,
+    KV: AbstractKey<Error = KeyError>,
+>(
+    crypto_config: &mut Configuration,
+    keystore: &mut DiskKeyStore,
+    required: bool,
+    password_key: &KP,
+    piv_key: &KV,
+    piv_digests: &[Digest],
+) -> Result<()> {
+    let password_digest = password_key.get_digest();
+    let piv_digest = piv_key.get_digest();
+    let two_factor_key = CompositeKey::new(KeyRef::new(piv_key), KeyRef::new(password_key));
+    let two_factor_digest = two_factor_key.get_digest();
+    let wrapped: Vec<Digest> = keystore
+        .iter_wrapped_keys()
+        .map(|k| k.get_wrapping_digest().clone())
+        .collect();
+
+    if required {
+        if !wrapped.contains(&password_digest) {
+            bail!("the master password is incorrect");
+        }
+        if !wrapped.contains(&piv_digest) || !piv_digests.contains(&piv_digest) {
+            bail!("the PIV device found is not registered with this repository");
+        }
+        // Any other PIV device would either keep working on its own, or be
+        // silently unregistered, so insist the user removes them explicitly.
+        if piv_digests.len() != 1 {
+            bail!("requiring two-factor unlocking needs exactly one PIV device registered with this repository; remove the others first, and add them back afterwards");
+        }
+        if wrapped.len() != 2 {
+            bail!("requiring two-factor unlocking needs a repository whose only keys are one master password and one PIV device; remove the others first");
+        }
+
+        // Each single-factor key could still unlock the repository on its
+        // own, so the two-factor key must replace both of them.
+        add_key(keystore, &two_factor_key)?;
+        if let Err(e) = remove_key(keystore, password_key) {
+            remove_key(keystore, &two_factor_key)?;
+            return Err(e);
+        }
+        if let Err(e) = remove_key(keystore, piv_key) {
+            add_key(keystore, password_key)?;
+            remove_key(keystore, &two_factor_key)?;
+            return Err(e);
+        }
+
+        // The password is still "the same" key as far as the user is
+        // concerned, so keep its label, and keep the PIV device which is now
+        // half of it registered.
+        crypto_config.rename_key_metadata(&password_digest, two_factor_digest.clone());
+        crypto_config.rename_piv_key(&piv_digest, two_factor_digest);
+        crypto_config.forget_key(&piv_digest);
+    } else {
+        if wrapped.as_slice() != [two_factor_digest.clone()] {
+            bail!("disabling two-factor unlocking needs a repository with exactly one key, made up of this master password and PIV device");
+        }
+
+        add_key(keystore, password_key)?;
+        if let Err(e) = add_key(keystore, piv_key) {
+            remove_key(keystore, password_key)?;
+            return Err(e);
+        }
+        if let Err(e) = remove_key(keystore, &two_factor_key) {
+            remove_key(keystore, piv_key)?;
+            remove_key(keystore, password_key)?;
+            return Err(e);
+        }
+
+        crypto_config.rename_key_metadata(&two_factor_digest, password_digest);
+        crypto_config.rename_piv_key(&two_factor_digest, piv_digest);
+    }
+    crypto_config.set_requires_two_factor(required);
+    Ok(())
+}
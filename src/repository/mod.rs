@@ -12,9 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod audit;
+pub mod credential;
+pub mod docker;
+pub mod env;
 pub(crate) mod keystore;
 pub mod path;
 mod repository;
 pub mod serde;
+pub mod template;
 
 pub use crate::repository::repository::*;
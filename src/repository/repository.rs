@@ -12,16 +12,20 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::crypto::configuration::{Configuration, ConfigurationInstance};
+use crate::crypto::configuration::{Configuration, ConfigurationInstance, KeyType};
+use crate::crypto::derive::Derivation;
 use crate::crypto::padding;
 use crate::repository::keystore::{
-    add_key, add_password_key, get_keystore, remove_key, remove_password_key,
+    add_key, add_key_file_key, add_password_key, change_password, get_keystore, remove_key,
+    remove_key_by_digest, remove_key_file_key, remove_password_key, set_recovery_key,
+    set_requires_two_factor,
 };
 use crate::repository::path::Path as RepositoryPath;
 use crate::util::git;
 use crate::util::lazy::{new_lazy_result, LazyResult};
 use anyhow::{bail, Error, Result};
-use bdrck::crypto::key::{AbstractKey, Key, Nonce};
+use bdrck::crypto::digest::Digest;
+use bdrck::crypto::key::{AbstractKey, Key, Nonce, KEY_BYTES};
 use bdrck::crypto::keystore::DiskKeyStore;
 use bdrck::crypto::secret::Secret;
 use git2;
@@ -29,16 +33,24 @@ use once_cell::sync::Lazy;
 use std::fs;
 use std::fs::File;
 use std::io::Write;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 static CRYPTO_CONFIGURATION_PATH: Lazy<PathBuf> =
     Lazy::new(|| PathBuf::from("crypto_configuration.mp"));
 static KEYSTORE_PATH: Lazy<PathBuf> = Lazy::new(|| PathBuf::from("keys.mp"));
+/// While a password is being rotated, its previous value is kept at the same
+/// relative path under this directory.
+static PREVIOUS_VALUES_PATH: Lazy<PathBuf> = Lazy::new(|| PathBuf::from(".previous"));
+static GIT_DIR_PATH: Lazy<PathBuf> = Lazy::new(|| PathBuf::from(".git"));
 
 static CRYPTO_CONFIGURATION_UPDATE_MESSAGE: &'static str = "Update encryption header contents.";
 static KEYSTORE_UPDATE_MESSAGE: &'static str = "Update keys.";
 static STORED_PASSWORD_UPDATE_MESSAGE: &'static str = "Update stored password / key.";
 static STORED_PASSWORD_REMOVE_MESSAGE: &'static str = "Remove stored password / key.";
+static STORED_PASSWORD_ROTATE_MESSAGE: &str = "Rotate stored password / key.";
+static STORED_PASSWORD_CONFIRM_ROTATION_MESSAGE: &str = "Confirm stored password / key rotation.";
+static STORED_PASSWORD_ABORT_ROTATION_MESSAGE: &str = "Abort stored password / key rotation.";
 
 fn get_keystore_path<P: AsRef<Path>>(path: P) -> Result<PathBuf> {
     let repository = git::open_repository(path.as_ref(), /*create=*/ false)?;
@@ -78,6 +90,16 @@ fn write_encrypt(
     Ok(())
 }
 
+/// A summary of one of the wrapping keys which can unlock a repository.
+pub struct KeyInfo {
+    pub digest: Digest,
+    pub key_type: KeyType,
+    pub label: Option<String>,
+    /// When this key was added. This is unknown for keys which were added by
+    /// older versions of pwm.
+    pub added: Option<SystemTime>,
+}
+
 pub struct Repository {
     repository: git2::Repository,
     // NOTE: crypto_configuration is guaranteed to be Some() everywhere except within drop().
@@ -112,14 +134,47 @@ impl Repository {
             keystore.deref().get()?;
         }
 
-        Ok(Repository {
+        let repository = Repository {
             repository: repository,
             crypto_configuration: Some(crypto_configuration),
             keystore: Some(keystore),
-        })
+        };
+        if keystore_is_new {
+            for digest in repository.wrapping_key_digests()? {
+                repository.record_key(digest, None)?;
+            }
+        }
+        Ok(repository)
     }
 
+    /// Return the given path to a stored password, relative to the
+    /// repository's root. Paths which would escape the repository, or which
+    /// the repository uses for its own files, are rejected.
     pub fn path<P: AsRef<Path>>(&self, path: P) -> Result<RepositoryPath> {
+        let path = path.as_ref();
+        if path
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+        {
+            bail!(
+                "invalid path '{}', paths must be relative to (and stay within) the repository",
+                path.display()
+            );
+        }
+        let normalized: PathBuf = path
+            .components()
+            .filter(|c| *c != Component::CurDir)
+            .collect();
+        if normalized == *CRYPTO_CONFIGURATION_PATH
+            || normalized == *KEYSTORE_PATH
+            || normalized.starts_with(PREVIOUS_VALUES_PATH.as_path())
+            || normalized.starts_with(GIT_DIR_PATH.as_path())
+        {
+            bail!(
+                "invalid path '{}', which is reserved for the repository's own use",
+                path.display()
+            );
+        }
         RepositoryPath::new(self.workdir()?, path)
     }
 
@@ -177,32 +232,192 @@ impl Repository {
             .into_iter()
             .filter(|entry| entry != CRYPTO_CONFIGURATION_PATH.as_path())
             .filter(|entry| entry != KEYSTORE_PATH.as_path())
+            .filter(|entry| !entry.starts_with(PREVIOUS_VALUES_PATH.as_path()))
             .map(|entry| self.path(entry))
             .collect()
     }
 
+    /// Return the time each stored password was last changed, according to the
+    /// repository's commit history.
+    pub fn last_modified_times(&self) -> Result<Vec<(RepositoryPath, SystemTime)>> {
+        let times = git::get_last_modified_times(&self.repository)?;
+        Ok(self
+            .list(None)?
+            .into_iter()
+            .filter_map(|path| {
+                let time = *times.get(path.relative_path())?;
+                Some((path, time))
+            })
+            .collect())
+    }
+
+    /// Return the digests of all of the wrapping keys which can unlock this
+    /// repository, along with what we know about each of them.
+    pub fn list_keys(&self) -> Result<Vec<KeyInfo>> {
+        let config = self.get_crypto_configuration();
+        Ok(self
+            .get_key_store()?
+            .iter_wrapped_keys()
+            .map(|wrapped| {
+                let digest = wrapped.get_wrapping_digest().clone();
+                let metadata = config.get_key_metadata(&digest);
+                KeyInfo {
+                    key_type: config.get_key_type(&digest),
+                    label: metadata.and_then(|m| m.label.clone()),
+                    added: metadata.map(|m| UNIX_EPOCH + Duration::from_secs(m.added)),
+                    digest,
+                }
+            })
+            .collect())
+    }
+
+    fn record_key(&self, digest: Digest, label: Option<String>) -> Result<()> {
+        let mut config = self.get_crypto_configuration();
+        config.add_key_metadata(digest, label)?;
+        self.set_crypto_configuration(config);
+        Ok(())
+    }
+
+    fn forget_key(&self, digest: &Digest) {
+        let mut config = self.get_crypto_configuration();
+        config.forget_key(digest);
+        self.set_crypto_configuration(config);
+    }
+
+    /// Labels must be unique, so check before adding a key, to avoid adding a
+    /// key we then can't record.
+    fn check_label_unused(&self, label: Option<&str>) -> Result<()> {
+        if let Some(label) = label {
+            if self
+                .get_crypto_configuration()
+                .find_key_by_label(label)
+                .is_some()
+            {
+                bail!("a key with the label '{}' already exists", label);
+            }
+        }
+        Ok(())
+    }
+
     pub fn add_key<E: Into<Error>, K: AbstractKey<Error = E>>(&mut self, key: &K) -> Result<()> {
-        add_key(self.get_key_store_mut()?, key)
+        add_key(self.get_key_store_mut()?, key)?;
+        self.record_key(key.get_digest(), None)
     }
 
-    pub fn add_password_key(&mut self, password: Option<Secret>) -> Result<()> {
-        add_password_key(
+    pub fn add_password_key(
+        &mut self,
+        password: Option<Secret>,
+        label: Option<String>,
+    ) -> Result<()> {
+        self.check_label_unused(label.as_deref())?;
+        let digest = add_password_key(
             &self.get_crypto_configuration(),
             self.get_key_store_mut()?,
             password,
-        )
+        )?;
+        self.record_key(digest, label)
+    }
+
+    /// Add a wrapping key derived from the contents of the given key file. The
+    /// key file's location is remembered, so it will be tried automatically
+    /// the next time this repository is opened.
+    pub fn add_key_file_key<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        label: Option<String>,
+    ) -> Result<()> {
+        self.check_label_unused(label.as_deref())?;
+        let mut config = self.get_crypto_configuration();
+        let assoc = add_key_file_key(&config, self.get_key_store_mut()?, path)?;
+        let digest = assoc.wrapping_key_digest.clone();
+        config.add_key_file(assoc);
+        self.set_crypto_configuration(config);
+        self.record_key(digest, label)
     }
 
     pub fn remove_key<E: Into<Error>, K: AbstractKey<Error = E>>(&mut self, key: &K) -> Result<()> {
-        remove_key(self.get_key_store_mut()?, key)
+        remove_key(self.get_key_store_mut()?, key)?;
+        self.forget_key(&key.get_digest());
+        Ok(())
     }
 
     pub fn remove_password_key(&mut self, password: Option<Secret>) -> Result<()> {
-        remove_password_key(
+        let digest = remove_password_key(
             &self.get_crypto_configuration(),
             self.get_key_store_mut()?,
             password,
-        )
+        )?;
+        self.forget_key(&digest);
+        Ok(())
+    }
+
+    pub fn remove_key_file_key<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let digest = remove_key_file_key(
+            &self.get_crypto_configuration(),
+            self.get_key_store_mut()?,
+            path,
+        )?;
+        self.forget_key(&digest);
+        Ok(())
+    }
+
+    /// Remove the key with the given label, whatever kind of key it is.
+    pub fn remove_key_by_label(&mut self, label: &str) -> Result<()> {
+        let digest = match self.get_crypto_configuration().find_key_by_label(label) {
+            None => bail!("no key with the label '{}' found", label),
+            Some(d) => d.clone(),
+        };
+        remove_key_by_digest(self.get_key_store_mut()?, &digest)?;
+        self.forget_key(&digest);
+        Ok(())
+    }
+
+    /// Replace the master password `old` with `new` (either of which is
+    /// prompted for if not provided). The key's label is preserved. If any
+    /// step fails, the repository's keys are left unchanged.
+    pub fn change_password(&mut self, old: Option<Secret>, new: Option<Secret>) -> Result<()> {
+        let mut config = self.get_crypto_configuration();
+        let (old_digest, new_digest) =
+            change_password(&config, self.get_key_store_mut()?, old, new)?;
+        config.rename_key_metadata(&old_digest, new_digest);
+        self.set_crypto_configuration(config);
+        Ok(())
+    }
+
+    /// Add a wrapping key derived from the given recovery secret, replacing
+    /// this repository's previous recovery key (if any). The recovery secret
+    /// can later be passed to `new` in place of the master password.
+    pub fn set_recovery_key(&mut self, recovery_secret: Secret) -> Result<()> {
+        let mut config = self.get_crypto_configuration();
+        let digest = set_recovery_key(&config, self.get_key_store_mut()?, recovery_secret)?;
+        if let Some(old) = config.get_recovery_key_digest().cloned() {
+            config.forget_key(&old);
+        }
+        config.set_recovery_key_digest(Some(digest.clone()));
+        config.add_key_metadata(digest, None)?;
+        self.set_crypto_configuration(config);
+        Ok(())
+    }
+
+    /// Require (or stop requiring) both a password and a PIV device to unlock
+    /// this repository. See `keystore::set_requires_two_factor` for details.
+    pub fn set_requires_two_factor(
+        &mut self,
+        required: bool,
+        password: Option<Secret>,
+    ) -> Result<()> {
+        let mut config = self.get_crypto_configuration();
+        set_requires_two_factor(&mut config, self.get_key_store_mut()?, required, password)?;
+        self.set_crypto_configuration(config);
+        Ok(())
+    }
+
+    fn wrapping_key_digests(&self) -> Result<Vec<Digest>> {
+        Ok(self
+            .get_key_store()?
+            .iter_wrapped_keys()
+            .map(|wrapped| wrapped.get_wrapping_digest().clone())
+            .collect())
     }
 
     pub fn write_encrypt(
@@ -216,6 +431,33 @@ impl Repository {
         Ok(())
     }
 
+    /// Store a derivation entry at the given path, returning the password it
+    /// derives.
+    pub fn write_derivation(
+        &mut self,
+        path: &RepositoryPath,
+        derivation: &Derivation,
+    ) -> Result<Secret> {
+        // Derive the password up front, so invalid settings are caught before
+        // the entry is stored.
+        let derived = self.derive_password(derivation)?;
+        self.write_encrypt(path, derivation.to_secret()?, None)?;
+        Ok(derived)
+    }
+
+    /// Compute the password for a derivation entry from this repository's
+    /// master key.
+    pub fn derive_password(&self, derivation: &Derivation) -> Result<Secret> {
+        // The master key never changes, but its serialized form has a
+        // compatibility prefix; derive from just the raw key bytes.
+        let serialized = self.get_master_key()?.serialize()?;
+        let serialized = unsafe { serialized.as_slice() };
+        let mut master_key = Secret::with_len(KEY_BYTES)?;
+        unsafe { master_key.as_mut_slice() }
+            .copy_from_slice(&serialized[serialized.len() - KEY_BYTES..]);
+        derivation.derive(&master_key)
+    }
+
     pub fn read_decrypt(&self, path: &RepositoryPath) -> Result<Secret> {
         if !path.absolute_path().exists() {
             bail!(
@@ -237,9 +479,102 @@ impl Repository {
 
     pub fn remove(&mut self, path: &RepositoryPath) -> Result<()> {
         fs::remove_file(path.absolute_path())?;
-        self.commit_one(STORED_PASSWORD_REMOVE_MESSAGE, path.relative_path())?;
+        // Also drop any previous value retained by an in-progress rotation.
+        let previous = self.previous_path(path)?;
+        if previous.absolute_path().exists() {
+            fs::remove_file(previous.absolute_path())?;
+        }
+        self.commit_all(
+            STORED_PASSWORD_REMOVE_MESSAGE,
+            &[path.relative_path(), previous.relative_path()],
+        )?;
         Ok(())
     }
+
+    fn previous_path(&self, path: &RepositoryPath) -> Result<RepositoryPath> {
+        // This is a reserved path, so `path` would reject it.
+        RepositoryPath::new(
+            self.workdir()?,
+            PREVIOUS_VALUES_PATH.join(path.relative_path()),
+        )
+    }
+
+    /// Returns the previous value retained by an in-progress rotation, or an
+    /// error if the given path isn't being rotated.
+    fn get_rotation(&self, path: &RepositoryPath) -> Result<RepositoryPath> {
+        let previous = self.previous_path(path)?;
+        if !previous.absolute_path().exists() {
+            bail!(
+                "no rotation of '{}' is in progress",
+                path.relative_path().display()
+            );
+        }
+        Ok(previous)
+    }
+
+    /// Returns true if the given path has an in-progress rotation.
+    pub fn is_rotating(&self, path: &RepositoryPath) -> Result<bool> {
+        Ok(self.previous_path(path)?.absolute_path().exists())
+    }
+
+    /// Replace the password at the given path with a new one, but retain the
+    /// old value (readable with `read_previous`) until the rotation is
+    /// confirmed or aborted. Both are committed together, in one commit.
+    pub fn rotate(&mut self, path: &RepositoryPath, plaintext: Secret) -> Result<()> {
+        if !path.absolute_path().exists() {
+            bail!(
+                "no stored password at path '{}'",
+                path.relative_path().display()
+            );
+        }
+        if self.is_rotating(path)? {
+            bail!(
+                "a rotation of '{}' is already in progress; confirm or abort it first",
+                path.relative_path().display()
+            );
+        }
+
+        let previous = self.previous_path(path)?;
+        if let Some(parent) = previous.absolute_path().parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // The old ciphertext is still valid, so just move it aside.
+        fs::rename(path.absolute_path(), previous.absolute_path())?;
+        if let Err(e) = write_encrypt(path, plaintext, self.get_master_key()?, None) {
+            fs::rename(previous.absolute_path(), path.absolute_path())?;
+            return Err(e);
+        }
+        self.commit_all(
+            STORED_PASSWORD_ROTATE_MESSAGE,
+            &[path.relative_path(), previous.relative_path()],
+        )
+    }
+
+    /// Read the previous value of a password which is being rotated.
+    pub fn read_previous(&self, path: &RepositoryPath) -> Result<Secret> {
+        let previous = self.get_rotation(path)?;
+        self.read_decrypt(&previous)
+    }
+
+    /// Finish rotating the given path, discarding its previous value.
+    pub fn confirm_rotation(&mut self, path: &RepositoryPath) -> Result<()> {
+        let previous = self.get_rotation(path)?;
+        fs::remove_file(previous.absolute_path())?;
+        self.commit_one(
+            STORED_PASSWORD_CONFIRM_ROTATION_MESSAGE,
+            previous.relative_path(),
+        )
+    }
+
+    /// Roll back an in-progress rotation, restoring the previous value.
+    pub fn abort_rotation(&mut self, path: &RepositoryPath) -> Result<()> {
+        let previous = self.get_rotation(path)?;
+        fs::rename(previous.absolute_path(), path.absolute_path())?;
+        self.commit_all(
+            STORED_PASSWORD_ABORT_ROTATION_MESSAGE,
+            &[path.relative_path(), previous.relative_path()],
+        )
+    }
 }
 
 impl Drop for Repository {
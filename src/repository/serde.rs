@@ -12,9 +12,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::repository::path::Path as RepositoryPath;
 use crate::repository::Repository;
 use crate::util;
 use anyhow::Result;
+use bdrck::crypto::secret::Secret;
 use serde::{Deserialize, Serialize};
 use serde_json::{from_str, to_string_pretty};
 use std::collections::HashMap;
@@ -24,17 +26,29 @@ pub struct Contents {
     pub contents: HashMap<String, String>,
 }
 
+/// Decrypt every stored password in the repository, calling the given
+/// function with each one in turn.
+pub fn for_each_decrypted<F: FnMut(&RepositoryPath, Secret) -> Result<()>>(
+    repository: &Repository,
+    mut f: F,
+) -> Result<()> {
+    for path in repository.list(None)? {
+        f(&path, repository.read_decrypt(&path)?)?;
+    }
+    Ok(())
+}
+
 pub fn export(repository: &Repository) -> Result<Contents> {
     let mut contents: Contents = Contents {
         contents: HashMap::new(),
     };
 
-    for path in repository.list(None)? {
-        let plaintext = util::secret::encode(&repository.read_decrypt(&path)?);
+    for_each_decrypted(repository, |path, plaintext| {
         contents
             .contents
-            .insert(path.to_str()?.to_owned(), plaintext);
-    }
+            .insert(path.to_str()?.to_owned(), util::secret::encode(&plaintext));
+        Ok(())
+    })?;
 
     Ok(contents)
 }
@@ -0,0 +1,259 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Rendering templates (e.g. configuration files) which contain placeholders
+//! for stored passwords, like `{{ pwm "db/prod" }}`.
+//!
+//! A placeholder may also select a field of a multiline entry, like
+//! `{{ pwm "db/prod" field="user" }}`. By convention, the first line of such
+//! an entry is the password itself, and each following line is a
+//! `key: value` pair. The field "password" always refers to the first line.
+
+use crate::crypto::derive::Derivation;
+use crate::repository::Repository;
+use anyhow::{bail, Result};
+use bdrck::crypto::secret::Secret;
+use std::collections::HashMap;
+
+static OPEN_DELIMITER: &str = "{{";
+static CLOSE_DELIMITER: &str = "}}";
+static KEYWORD: &str = "pwm";
+static FIELD_ATTRIBUTE: &str = "field";
+static PASSWORD_FIELD: &str = "password";
+
+/// A single placeholder in a template, to be replaced by a stored password.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Placeholder {
+    pub path: String,
+    pub field: Option<String>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Segment {
+    Text(String),
+    Placeholder(Placeholder),
+}
+
+/// A parsed template.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+/// A cursor over template source, which keeps track of the current position
+/// for error messages.
+struct Parser<'a> {
+    source: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.source[self.position..]
+    }
+
+    fn line(&self) -> usize {
+        self.source[..self.position].matches('\n').count() + 1
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T> {
+        bail!(
+            "invalid template placeholder on line {}: {}",
+            self.line(),
+            message
+        )
+    }
+
+    fn parse_string(&mut self) -> Result<String> {
+        if !self.rest().starts_with('"') {
+            return self.error("expected a quoted string");
+        }
+        self.position += 1;
+        let mut s = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.position += i + 1;
+                    return Ok(s);
+                }
+                '\\' => match chars.next() {
+                    Some((_, c)) if c == '"' || c == '\\' => s.push(c),
+                    _ => return self.error("invalid escape sequence"),
+                },
+                '\n' => break,
+                c => s.push(c),
+            }
+        }
+        self.error("unterminated string")
+    }
+
+    fn parse_identifier(&mut self) -> Result<&'a str> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return self.error("expected an attribute name or '}}'");
+        }
+        self.position += len;
+        Ok(&rest[..len])
+    }
+
+    /// Parse the remainder of a placeholder, after the `{{ pwm` keyword, up
+    /// to and including the closing delimiter.
+    fn parse_placeholder(&mut self) -> Result<Placeholder> {
+        self.skip_whitespace();
+        let path = self.parse_string()?;
+        let mut field = None;
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with(CLOSE_DELIMITER) {
+                self.position += CLOSE_DELIMITER.len();
+                break;
+            }
+            let name = self.parse_identifier()?;
+            if !self.rest().starts_with('=') {
+                return self.error("expected '=' after attribute name");
+            }
+            self.position += 1;
+            let value = self.parse_string()?;
+            if name != FIELD_ATTRIBUTE {
+                return self.error(&format!("unknown attribute '{}'", name));
+            }
+            if field.replace(value).is_some() {
+                return self.error(&format!("duplicate attribute '{}'", name));
+            }
+        }
+        Ok(Placeholder { path, field })
+    }
+}
+
+impl Template {
+    /// Parse the given template source. Anything which isn't a `{{ pwm ... }}`
+    /// placeholder (including other `{{ ... }}` blocks, e.g. for other
+    /// templating systems) is left untouched.
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut parser = Parser {
+            source,
+            position: 0,
+        };
+        let mut segments = Vec::new();
+        let mut text = String::new();
+        while let Some(offset) = parser.rest().find(OPEN_DELIMITER) {
+            text.push_str(&parser.rest()[..offset]);
+            parser.position += offset + OPEN_DELIMITER.len();
+
+            let start = parser.position;
+            parser.skip_whitespace();
+            let is_placeholder = parser.rest().starts_with(KEYWORD)
+                && parser.rest()[KEYWORD.len()..].starts_with(char::is_whitespace);
+            if !is_placeholder {
+                text.push_str(OPEN_DELIMITER);
+                parser.position = start;
+                continue;
+            }
+            parser.position += KEYWORD.len();
+
+            if !text.is_empty() {
+                segments.push(Segment::Text(std::mem::take(&mut text)));
+            }
+            segments.push(Segment::Placeholder(parser.parse_placeholder()?));
+        }
+        text.push_str(parser.rest());
+        if !text.is_empty() {
+            segments.push(Segment::Text(text));
+        }
+        Ok(Template { segments })
+    }
+
+    /// Return all of the placeholders in this template, in order.
+    pub fn placeholders(&self) -> impl Iterator<Item = &Placeholder> {
+        self.segments.iter().filter_map(|s| match s {
+            Segment::Placeholder(p) => Some(p),
+            Segment::Text(_) => None,
+        })
+    }
+
+    /// Render this template, replacing every placeholder with the password
+    /// it refers to. If any placeholder can't be resolved, this fails without
+    /// producing any output.
+    pub fn render(&self, repository: &Repository) -> Result<Secret> {
+        let mut values: HashMap<&str, Secret> = HashMap::new();
+        for placeholder in self.placeholders() {
+            if values.contains_key(placeholder.path.as_str()) {
+                continue;
+            }
+            let path = repository.path(&placeholder.path)?;
+            if !path.absolute_path().exists() {
+                bail!("no stored password at path '{}'", placeholder.path);
+            }
+            let mut value = repository.read_decrypt(&path)?;
+            if Derivation::is_derivation(&value) {
+                value = repository.derive_password(&Derivation::from_secret(&value)?)?;
+            }
+            values.insert(&placeholder.path, value);
+        }
+
+        let mut rendered = Secret::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Text(text) => append(&mut rendered, text.as_bytes())?,
+                Segment::Placeholder(p) => {
+                    let value = &values[p.path.as_str()];
+                    match &p.field {
+                        None => append(&mut rendered, unsafe { value.as_slice() })?,
+                        Some(field) => match get_field(unsafe { value.as_slice() }, field) {
+                            Some(v) => append(&mut rendered, v)?,
+                            None => bail!("'{}' has no field '{}'", p.path, field),
+                        },
+                    }
+                }
+            }
+        }
+        Ok(rendered)
+    }
+}
+
+fn append(secret: &mut Secret, data: &[u8]) -> Result<()> {
+    let old_len = secret.len();
+    secret.resize(old_len + data.len())?;
+    unsafe { secret.as_mut_slice()[old_len..].copy_from_slice(data) };
+    Ok(())
+}
+
+/// Return the given field of a multiline entry (see the module
+/// documentation). Field names are matched case-insensitively.
+pub fn get_field<'a>(value: &'a [u8], field: &str) -> Option<&'a [u8]> {
+    let mut lines = value
+        .split(|b| *b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line));
+    let first = lines.next()?;
+    if field.eq_ignore_ascii_case(PASSWORD_FIELD) {
+        return Some(first);
+    }
+    lines.find_map(|line| {
+        let colon = line.iter().position(|b| *b == b':')?;
+        let (key, value) = (&line[..colon], &line[colon + 1..]);
+        match key.trim_ascii().eq_ignore_ascii_case(field.as_bytes()) {
+            true => Some(value.trim_ascii()),
+            false => None,
+        }
+    })
+}
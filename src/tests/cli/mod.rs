@@ -0,0 +1,50 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::cli::Cli;
+use clap::Parser;
+
+#[test]
+fn test_pattern_conflicts_with_policy() {
+    crate::init().unwrap();
+
+    for flag in [
+        "-A",
+        "-N",
+        "-S",
+        "--min-letters=1",
+        "--min-numbers=1",
+        "--min-symbols=1",
+        "--max-repeat=2",
+        "--first=letters",
+    ] {
+        assert!(
+            Cli::try_parse_from(["pwm", "generate", "--pattern", "L{4}-D{4}", flag]).is_err(),
+            "{}",
+            flag
+        );
+    }
+
+    // Exclusions do apply to patterns, though.
+    assert!(Cli::try_parse_from([
+        "pwm",
+        "generate",
+        "--pattern",
+        "L{4}-D{4}",
+        "-X",
+        "abc",
+        "--no-ambiguous",
+    ])
+    .is_ok());
+}
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use crate::configuration::*;
+use crate::repository::credential::DEFAULT_PATH_SCHEME;
 use bdrck::testing::temp;
 use once_cell::sync::Lazy;
 use std::fs;
@@ -92,6 +93,83 @@ fn test_get_value_as_str() {
     assert!(get_value_as_str("bogus key").is_err());
 }
 
+#[test]
+fn test_clipboard_settings() {
+    crate::init().unwrap();
+
+    let _guard = match CONFIGURATION_TESTS_MUTEX.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    let file = temp::File::new_file().unwrap();
+    let path: PathBuf = file.path().to_owned();
+    // Remove the file: an empty file isn't a valid serialized configuration struct.
+    fs::remove_file(path.as_path()).unwrap();
+
+    let _handle = SingletonHandle::new(Some(path.as_path())).unwrap();
+    assert_eq!(
+        DEFAULT_CLIPBOARD_TIMEOUT_SECS.to_string(),
+        get_value_as_str(CLIPBOARD_TIMEOUT_KEY).unwrap()
+    );
+    assert_eq!(
+        "clipboard",
+        get_value_as_str(CLIPBOARD_SELECTION_KEY).unwrap()
+    );
+
+    set(CLIPBOARD_TIMEOUT_KEY, "10").unwrap();
+    set(CLIPBOARD_SELECTION_KEY, "PRIMARY").unwrap();
+    assert_eq!("10", get_value_as_str(CLIPBOARD_TIMEOUT_KEY).unwrap());
+    assert_eq!(
+        "primary",
+        get_value_as_str(CLIPBOARD_SELECTION_KEY).unwrap()
+    );
+
+    assert!(set(CLIPBOARD_TIMEOUT_KEY, "soon").is_err());
+    assert!(set(CLIPBOARD_SELECTION_KEY, "secondary").is_err());
+}
+
+#[test]
+fn test_git_credential_scheme() {
+    crate::init().unwrap();
+
+    let _guard = match CONFIGURATION_TESTS_MUTEX.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    let file = temp::File::new_file().unwrap();
+    let path: PathBuf = file.path().to_owned();
+    // Remove the file: an empty file isn't a valid serialized configuration struct.
+    fs::remove_file(path.as_path()).unwrap();
+
+    let _handle = SingletonHandle::new(Some(path.as_path())).unwrap();
+    assert_eq!(
+        DEFAULT_PATH_SCHEME,
+        get_value_as_str(GIT_CREDENTIAL_SCHEME_KEY).unwrap()
+    );
+
+    set(GIT_CREDENTIAL_SCHEME_KEY, "tokens/{host}/{username}").unwrap();
+    assert_eq!(
+        "tokens/{host}/{username}",
+        get_value_as_str(GIT_CREDENTIAL_SCHEME_KEY).unwrap()
+    );
+
+    // Invalid schemes are rejected, leaving the previous value in place.
+    assert!(set(GIT_CREDENTIAL_SCHEME_KEY, "tokens/{host}").is_err());
+    assert!(set(GIT_CREDENTIAL_SCHEME_KEY, "{host}/{user}/{username}").is_err());
+    assert_eq!(
+        "tokens/{host}/{username}",
+        get_value_as_str(GIT_CREDENTIAL_SCHEME_KEY).unwrap()
+    );
+
+    set(GIT_CREDENTIAL_SCHEME_KEY, "").unwrap();
+    assert_eq!(
+        DEFAULT_PATH_SCHEME,
+        get_value_as_str(GIT_CREDENTIAL_SCHEME_KEY).unwrap()
+    );
+}
+
 #[test]
 fn test_reset() {
     crate::init().unwrap();
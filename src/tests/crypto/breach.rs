@@ -0,0 +1,89 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::crypto::breach::*;
+use crate::tests::str_secret;
+use bdrck::testing::temp;
+use sha1::{Digest, Sha1};
+use std::fs;
+
+fn hash(password: &str) -> String {
+    data_encoding::HEXUPPER.encode(&Sha1::digest(password.as_bytes()))
+}
+
+/// Write a breach database containing "password0" through "passwordN", where
+/// "passwordI" has a count of I + 1.
+fn write_database(path: &std::path::Path, n: usize) {
+    let mut entries: Vec<(String, usize)> = (0..n)
+        .map(|i| (hash(&format!("password{}", i)), i + 1))
+        .collect();
+    entries.sort();
+    let contents: String = entries
+        .iter()
+        .map(|(h, c)| format!("{}:{}\r\n", h, c))
+        .collect();
+    fs::write(path, contents).unwrap();
+}
+
+#[test]
+fn test_lookup() {
+    crate::init().unwrap();
+
+    let f = temp::File::new_file().unwrap();
+    write_database(f.path(), 5000);
+    let mut db = BreachDatabase::open(f.path()).unwrap();
+
+    for i in (0..5000).step_by(97).chain([0, 4999]) {
+        assert_eq!(
+            Some(i as u64 + 1),
+            db.lookup(&str_secret(&format!("password{}", i))).unwrap()
+        );
+    }
+    assert_eq!(None, db.lookup(&str_secret("password5000")).unwrap());
+    assert_eq!(None, db.lookup(&str_secret("not breached")).unwrap());
+    assert_eq!(
+        None,
+        db.lookup_hash("0000000000000000000000000000000000000000")
+            .unwrap()
+    );
+    assert_eq!(
+        None,
+        db.lookup_hash("FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF")
+            .unwrap()
+    );
+}
+
+#[test]
+fn test_lookup_lowercase_hash() {
+    crate::init().unwrap();
+
+    let f = temp::File::new_file().unwrap();
+    write_database(f.path(), 10);
+    let mut db = BreachDatabase::open(f.path()).unwrap();
+    assert_eq!(
+        Some(4),
+        db.lookup_hash(&hash("password3").to_ascii_lowercase())
+            .unwrap()
+    );
+}
+
+#[test]
+fn test_empty_database() {
+    crate::init().unwrap();
+
+    let f = temp::File::new_file().unwrap();
+    fs::write(f.path(), "").unwrap();
+    let mut db = BreachDatabase::open(f.path()).unwrap();
+    assert_eq!(None, db.lookup(&str_secret("password")).unwrap());
+}
@@ -104,3 +104,44 @@ fn test_deserialize_with_piv_keys_panics() {
 
     let _ci = ConfigurationInstance::new(f.path()).unwrap();
 }
+
+#[test]
+fn test_generation_profiles() {
+    use crate::crypto::pwgen::{CharacterSet, GeneratorSettings, PasswordPolicy};
+
+    crate::init().unwrap();
+
+    let settings = |length| GeneratorSettings {
+        length,
+        charsets: vec![CharacterSet::Numbers],
+        exclude: vec![],
+        policy: PasswordPolicy::default(),
+        pattern: None,
+    };
+
+    let f = temp::File::new_file().unwrap();
+    fs::remove_file(f.path()).unwrap();
+    {
+        let ci = ConfigurationInstance::new(f.path()).unwrap();
+        let mut c = ci.get();
+        c.set_generation_profile("bank/".to_owned(), settings(8));
+        c.set_generation_profile("bank/savings/".to_owned(), settings(12));
+        c.set_generation_profile("bank/".to_owned(), settings(10));
+        ci.set(c);
+        ci.close().unwrap();
+    }
+
+    // Profiles should survive being written out and read back in.
+    let ci = ConfigurationInstance::new(f.path()).unwrap();
+    let mut c = ci.get();
+    assert_eq!(2, c.get_generation_profiles().len());
+    let length =
+        |c: &Configuration, path| c.find_generation_profile(path).map(|p| p.settings.length);
+    assert_eq!(Some(10), length(&c, "bank/checking"));
+    assert_eq!(Some(12), length(&c, "bank/savings/main"));
+    assert_eq!(None, length(&c, "email/personal"));
+
+    assert!(c.remove_generation_profile("bank/savings/"));
+    assert!(!c.remove_generation_profile("bank/savings/"));
+    assert_eq!(Some(10), length(&c, "bank/savings/main"));
+}
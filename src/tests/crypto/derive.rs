@@ -0,0 +1,112 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::crypto::derive::*;
+use crate::crypto::pwgen::{CharacterSet, GeneratorSettings, PasswordPolicy};
+use crate::tests::str_secret;
+use bdrck::crypto::secret::Secret;
+
+fn test_derivation() -> Derivation {
+    Derivation {
+        site: "example.com".to_owned(),
+        login: "me@example.com".to_owned(),
+        counter: 0,
+        settings: GeneratorSettings {
+            length: 20,
+            charsets: vec![CharacterSet::Letters, CharacterSet::Numbers],
+            exclude: vec![],
+            policy: PasswordPolicy::default(),
+            pattern: None,
+        },
+    }
+}
+
+fn derive(derivation: &Derivation, key: &str) -> Vec<u8> {
+    unsafe { derivation.derive(&str_secret(key)).unwrap().as_slice() }.to_vec()
+}
+
+#[test]
+fn test_derivation_is_deterministic() {
+    crate::init().unwrap();
+
+    let derivation = test_derivation();
+    let password = derive(&derivation, "key");
+    assert_eq!(20, password.len());
+    assert!(password.iter().all(|c| c.is_ascii_alphanumeric()));
+    assert_eq!(password, derive(&derivation, "key"));
+}
+
+#[test]
+fn test_derivation_known_answers() {
+    crate::init().unwrap();
+
+    // These were computed independently from the procedure documented on
+    // Derivation. If they change, every derived password would change too.
+    let mut derivation = test_derivation();
+    assert_eq!(b"9OzlLkikAAhpZxqj66Rk".to_vec(), derive(&derivation, "key"));
+    derivation.counter = 1;
+    assert_eq!(b"Gm5LKN9yWmnbKi6tr4Rk".to_vec(), derive(&derivation, "key"));
+
+    let mut derivation = test_derivation();
+    derivation.settings.pattern = Some("DDDD-DDDD".to_owned());
+    assert_eq!(b"5879-1828".to_vec(), derive(&derivation, "key"));
+}
+
+#[test]
+fn test_derivation_parameters_change_password() {
+    crate::init().unwrap();
+
+    let derivation = test_derivation();
+    let password = derive(&derivation, "key");
+    assert_ne!(password, derive(&derivation, "other key"));
+
+    let mut d = test_derivation();
+    d.counter += 1;
+    assert_ne!(password, derive(&d, "key"));
+
+    let mut d = test_derivation();
+    d.site = "example.org".to_owned();
+    assert_ne!(password, derive(&d, "key"));
+
+    // Moving characters between the site and login must change the password.
+    let mut d = test_derivation();
+    d.site = "example.comm".to_owned();
+    d.login = "e@example.com".to_owned();
+    assert_ne!(password, derive(&d, "key"));
+}
+
+#[test]
+fn test_derivation_with_pattern() {
+    crate::init().unwrap();
+
+    let mut derivation = test_derivation();
+    derivation.settings.pattern = Some("DDDD-DDDD".to_owned());
+    let password = String::from_utf8(derive(&derivation, "key")).unwrap();
+    assert_eq!(9, password.len());
+    assert_eq!(b'-', password.as_bytes()[4]);
+}
+
+#[test]
+fn test_derivation_round_trip() {
+    crate::init().unwrap();
+
+    let derivation = test_derivation();
+    let stored = derivation.to_secret().unwrap();
+    assert!(Derivation::is_derivation(&stored));
+    assert!(!Derivation::is_derivation(&str_secret("hunter2")));
+    assert!(!Derivation::is_derivation(&Secret::new()));
+
+    let decoded = Derivation::from_secret(&stored).unwrap();
+    assert_eq!(derive(&derivation, "key"), derive(&decoded, "key"));
+}
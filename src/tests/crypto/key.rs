@@ -0,0 +1,91 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::crypto::key::*;
+use crate::tests::{random_secret, str_secret};
+use bdrck::crypto::digest::Salt;
+use bdrck::crypto::key::{AbstractKey, Key};
+
+fn password_key(password: &str, salt: &Salt) -> PwmKey<bdrck::error::Error, Key> {
+    PwmKey::from(
+        Key::new_password(
+            &str_secret(password),
+            salt,
+            bdrck::crypto::digest::OPS_LIMIT_INTERACTIVE,
+            bdrck::crypto::digest::MEM_LIMIT_INTERACTIVE,
+        )
+        .unwrap(),
+    )
+}
+
+#[test]
+fn test_composite_key_round_trip() {
+    crate::init().unwrap();
+
+    let salt = Salt::default();
+    let key = CompositeKey::new(password_key("foo", &salt), password_key("bar", &salt));
+    let plaintext = random_secret(40);
+
+    let (nonce, ciphertext) = key.encrypt(&plaintext, None).unwrap();
+    let decrypted = key.decrypt(nonce.as_ref(), ciphertext.as_slice()).unwrap();
+    unsafe {
+        assert_eq!(plaintext.as_slice(), decrypted.as_slice());
+    }
+}
+
+#[test]
+fn test_composite_key_requires_both_keys() {
+    crate::init().unwrap();
+
+    let salt = Salt::default();
+    let key = CompositeKey::new(password_key("foo", &salt), password_key("bar", &salt));
+    let (nonce, ciphertext) = key.encrypt(&random_secret(40), None).unwrap();
+
+    assert!(password_key("foo", &salt)
+        .decrypt(nonce.as_ref(), ciphertext.as_slice())
+        .is_err());
+    // The second key alone only recovers the first key's ciphertext.
+    let intermediate = password_key("bar", &salt)
+        .decrypt(nonce.as_ref(), ciphertext.as_slice())
+        .unwrap();
+    assert_ne!(40, intermediate.len());
+
+    let swapped = CompositeKey::new(password_key("bar", &salt), password_key("foo", &salt));
+    assert!(swapped
+        .decrypt(nonce.as_ref(), ciphertext.as_slice())
+        .is_err());
+}
+
+#[test]
+fn test_composite_key_digest() {
+    crate::init().unwrap();
+
+    let salt = Salt::default();
+    let key = CompositeKey::new(password_key("foo", &salt), password_key("bar", &salt));
+    let same = CompositeKey::new(password_key("foo", &salt), password_key("bar", &salt));
+
+    assert_eq!(key.get_digest(), same.get_digest());
+    assert!(key.get_digest() != password_key("foo", &salt).get_digest());
+    assert!(key.get_digest() != password_key("bar", &salt).get_digest());
+}
+
+#[test]
+fn test_digest_fingerprint() {
+    crate::init().unwrap();
+
+    // The SHA-512 digest of the empty string starts with these bytes.
+    let digest = bdrck::crypto::digest::Digest::from_bytes(&[]);
+    assert_eq!(0xcf, digest_bytes(&digest)[0]);
+    assert_eq!("cf83e1357eefb8bd", digest_fingerprint(&digest));
+}
@@ -12,11 +12,25 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+#[cfg(test)]
+mod breach;
 #[cfg(test)]
 mod configuration;
 #[cfg(test)]
+mod derive;
+#[cfg(test)]
+mod key;
+#[cfg(test)]
+mod otp;
+#[cfg(test)]
 mod padding;
 #[cfg(test)]
 mod pwgen;
 #[cfg(test)]
 mod rng;
+#[cfg(test)]
+mod shamir;
+#[cfg(test)]
+mod strength;
+#[cfg(test)]
+mod wordlist;
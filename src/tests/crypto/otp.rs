@@ -0,0 +1,137 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::crypto::otp::*;
+use crate::tests::str_secret;
+use data_encoding::BASE32_NOPAD;
+use std::time::{Duration, UNIX_EPOCH};
+
+fn rfc_uri(kind: &str, algorithm: &str, secret: &str, extra: &str) -> String {
+    format!(
+        "otpauth://{}/Example:alice@example.com?secret={}&algorithm={}&{}",
+        kind,
+        BASE32_NOPAD.encode(secret.as_bytes()),
+        algorithm,
+        extra
+    )
+}
+
+#[test]
+fn test_hotp_rfc_4226_vectors() {
+    crate::init().unwrap();
+
+    let mut otp = Otp::from_uri(&rfc_uri(
+        "hotp",
+        "SHA1",
+        "12345678901234567890",
+        "counter=0",
+    ))
+    .unwrap();
+    let expected = ["755224", "287082", "359152", "969429", "338314"];
+    for code in expected.iter() {
+        assert_eq!(*code, otp.next_code(UNIX_EPOCH).unwrap());
+    }
+    assert_eq!(OtpKind::Hotp { counter: 5 }, otp.kind);
+}
+
+#[test]
+fn test_totp_rfc_6238_vectors() {
+    crate::init().unwrap();
+
+    let cases = [
+        ("SHA1", "12345678901234567890", 59, "94287082"),
+        ("SHA256", "12345678901234567890123456789012", 59, "46119246"),
+        (
+            "SHA512",
+            "1234567890123456789012345678901234567890123456789012345678901234",
+            59,
+            "90693936",
+        ),
+        ("SHA1", "12345678901234567890", 1111111109, "07081804"),
+        ("SHA1", "12345678901234567890", 2000000000, "69279037"),
+    ];
+    for (algorithm, secret, time, code) in cases.iter() {
+        let mut otp =
+            Otp::from_uri(&rfc_uri("totp", algorithm, secret, "digits=8&period=30")).unwrap();
+        assert_eq!(
+            *code,
+            otp.next_code(UNIX_EPOCH + Duration::from_secs(*time))
+                .unwrap()
+        );
+    }
+}
+
+#[test]
+fn test_uri_round_trip() {
+    crate::init().unwrap();
+
+    let otp = Otp::from_uri(
+        "otpauth://totp/ACME%20Co:john.doe@email.com?secret=hxdm vjec jjws rb3h wizr 4ifu gftm xboz&issuer=ACME%20Co&digits=7&period=60",
+    )
+    .unwrap();
+    assert_eq!("ACME Co:john.doe@email.com", otp.label);
+    assert_eq!(Some("ACME Co"), otp.issuer.as_deref());
+    assert_eq!(Algorithm::Sha1, otp.algorithm);
+    assert_eq!(7, otp.digits);
+    assert_eq!(OtpKind::Totp { period: 60 }, otp.kind);
+
+    let uri = otp.to_uri().unwrap();
+    assert!(Otp::is_otp(&uri));
+    assert_eq!(
+        "otpauth://totp/ACME%20Co:john.doe@email.com?secret=HXDMVJECJJWSRB3HWIZR4IFUGFTMXBOZ&issuer=ACME+Co&algorithm=SHA1&digits=7&period=60",
+        crate::tests::secret_string(&uri)
+    );
+    let decoded = Otp::from_secret(&uri).unwrap();
+    assert_eq!(otp.label, decoded.label);
+    assert_eq!(otp.issuer, decoded.issuer);
+    assert_eq!(otp.digits, decoded.digits);
+    assert_eq!(otp.kind, decoded.kind);
+    unsafe {
+        assert_eq!(otp.secret.as_slice(), decoded.secret.as_slice());
+    }
+}
+
+#[test]
+fn test_uri_escaped_secret() {
+    crate::init().unwrap();
+
+    let plain = Otp::from_uri("otpauth://hotp/bar?secret=GEZDGNBV&counter=3").unwrap();
+    let escaped =
+        Otp::from_uri("OTPAUTH://hotp/bar?counter=3&secret=gezd%20gnbv%3D%3D+#fragment").unwrap();
+    assert_eq!(OtpKind::Hotp { counter: 3 }, escaped.kind);
+    unsafe {
+        assert_eq!(plain.secret.as_slice(), escaped.secret.as_slice());
+    }
+}
+
+#[test]
+fn test_invalid_uris() {
+    crate::init().unwrap();
+
+    for uri in [
+        "https://totp/foo?secret=GEZDGNBV",
+        "otpauth://foo/bar?secret=GEZDGNBV",
+        "otpauth://totp/bar",
+        "otpauth://totp/bar?secret=not*base32",
+        "otpauth://totp/bar?secret=GEZDGNBV&digits=12",
+        "otpauth://totp/bar?secret=GEZDGNBV&algorithm=MD5",
+        "otpauth://hotp/bar?secret=GEZDGNBV",
+    ]
+    .iter()
+    {
+        assert!(Otp::from_uri(uri).is_err(), "{}", uri);
+    }
+
+    assert!(!Otp::is_otp(&str_secret("hunter2")));
+}
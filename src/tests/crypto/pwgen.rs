@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use crate::crypto::pwgen::*;
+use std::collections::BTreeMap;
 
 fn generate_password_str(
     length: Option<usize>,
@@ -90,3 +91,327 @@ fn test_pwgen_charset() {
             .fold(true, |acc, isdigit| acc && isdigit));
     }
 }
+
+fn generate_with_policy_str(length: usize, policy: &PasswordPolicy) -> String {
+    unsafe {
+        std::str::from_utf8(
+            generate_password_with_policy(
+                length,
+                &[
+                    CharacterSet::Letters,
+                    CharacterSet::Numbers,
+                    CharacterSet::Symbols,
+                ],
+                &[],
+                policy,
+            )
+            .unwrap()
+            .as_slice(),
+        )
+        .unwrap()
+        .to_owned()
+    }
+}
+
+#[test]
+fn test_policy_minimum_counts() {
+    crate::init().unwrap();
+
+    let mut min_counts = BTreeMap::new();
+    min_counts.insert(CharacterSet::Letters, 2);
+    min_counts.insert(CharacterSet::Numbers, 3);
+    min_counts.insert(CharacterSet::Symbols, 2);
+    let policy = PasswordPolicy {
+        min_counts,
+        ..Default::default()
+    };
+    for _ in 0..20 {
+        let password = generate_with_policy_str(12, &policy);
+        assert!(password.chars().filter(|c| c.is_ascii_alphabetic()).count() >= 2);
+        assert!(password.chars().filter(|c| c.is_ascii_digit()).count() >= 3);
+        assert!(
+            password
+                .chars()
+                .filter(|c| !c.is_ascii_alphanumeric())
+                .count()
+                >= 2
+        );
+    }
+}
+
+#[test]
+fn test_policy_first_character_and_ambiguous() {
+    crate::init().unwrap();
+
+    let policy = PasswordPolicy {
+        exclude_ambiguous: true,
+        first: Some(CharacterSet::Letters),
+        ..Default::default()
+    };
+    for _ in 0..20 {
+        let password = generate_with_policy_str(40, &policy);
+        assert!(password.chars().next().unwrap().is_ascii_alphabetic());
+        assert!(!password.chars().any(|c| AMBIGUOUS_CHARACTERS.contains(&c)));
+    }
+}
+
+#[test]
+fn test_policy_max_repeat() {
+    crate::init().unwrap();
+
+    let policy = PasswordPolicy {
+        max_repeat: Some(1),
+        ..Default::default()
+    };
+    assert!(policy.is_satisfied_by(b"abab"));
+    assert!(!policy.is_satisfied_by(b"abba"));
+
+    for _ in 0..20 {
+        let password =
+            generate_password_with_policy(20, &[CharacterSet::Numbers], &[], &policy).unwrap();
+        let password = unsafe { password.as_slice() };
+        assert!(password.windows(2).all(|w| w[0] != w[1]));
+    }
+}
+
+#[test]
+fn test_strict_but_feasible_policies() {
+    crate::init().unwrap();
+
+    // Almost no uniformly random passwords satisfy this, so it must not be
+    // found by trial and error.
+    let mut min_counts = BTreeMap::new();
+    min_counts.insert(CharacterSet::Symbols, 15);
+    let policy = PasswordPolicy {
+        min_counts,
+        first: Some(CharacterSet::Letters),
+        ..Default::default()
+    };
+    for _ in 0..20 {
+        let password = generate_with_policy_str(16, &policy);
+        assert!(password.chars().next().unwrap().is_ascii_alphabetic());
+        assert_eq!(
+            15,
+            password
+                .chars()
+                .filter(|c| !c.is_ascii_alphanumeric())
+                .count()
+        );
+    }
+
+    let mut min_counts = BTreeMap::new();
+    min_counts.insert(CharacterSet::Numbers, 1000);
+    min_counts.insert(CharacterSet::Symbols, 1000);
+    let policy = PasswordPolicy {
+        min_counts,
+        max_repeat: Some(2),
+        ..Default::default()
+    };
+    let password = generate_with_policy_str(2048, &policy);
+    assert!(policy.is_satisfied_by(password.as_bytes()));
+}
+
+#[test]
+fn test_impossible_policies() {
+    crate::init().unwrap();
+
+    let mut min_counts = BTreeMap::new();
+    min_counts.insert(CharacterSet::Numbers, 10);
+    let too_many = PasswordPolicy {
+        min_counts,
+        ..Default::default()
+    };
+    assert!(generate_password_with_policy(8, &[CharacterSet::Numbers], &[], &too_many).is_err());
+
+    let unavailable_first = PasswordPolicy {
+        first: Some(CharacterSet::Symbols),
+        ..Default::default()
+    };
+    assert!(
+        generate_password_with_policy(8, &[CharacterSet::Numbers], &[], &unavailable_first)
+            .is_err()
+    );
+
+    let no_repeats = PasswordPolicy {
+        max_repeat: Some(0),
+        ..Default::default()
+    };
+    assert!(generate_password_with_policy(8, &[CharacterSet::Numbers], &[], &no_repeats).is_err());
+    let first_too = PasswordPolicy {
+        min_counts: [(CharacterSet::Numbers, 8)].into_iter().collect(),
+        first: Some(CharacterSet::Letters),
+        ..Default::default()
+    };
+    assert!(generate_password_with_policy(
+        8,
+        &[CharacterSet::Letters, CharacterSet::Numbers],
+        &[],
+        &first_too
+    )
+    .is_err());
+
+    let single_repeats = PasswordPolicy {
+        max_repeat: Some(1),
+        ..Default::default()
+    };
+    assert!(
+        generate_password_with_policy(200, &[CharacterSet::Numbers], &[], &single_repeats).is_err()
+    );
+}
+
+fn generate_from_pattern_str(pattern: &str, exclude: &[char]) -> String {
+    unsafe {
+        std::str::from_utf8(generate_from_pattern(pattern, exclude).unwrap().as_slice())
+            .unwrap()
+            .to_owned()
+    }
+}
+
+#[test]
+fn test_pattern_classes_and_literals() {
+    crate::init().unwrap();
+
+    for _ in 0..20 {
+        let password = generate_from_pattern_str("LLLL-DDDD-SS", &[]);
+        let chars: Vec<char> = password.chars().collect();
+        assert_eq!(12, chars.len());
+        assert!(chars[..4].iter().all(|c| c.is_ascii_alphabetic()));
+        assert_eq!('-', chars[4]);
+        assert!(chars[5..9].iter().all(|c| c.is_ascii_digit()));
+        assert_eq!('-', chars[9]);
+        assert!(chars[10..]
+            .iter()
+            .all(|c| c.is_ascii_graphic() && !c.is_ascii_alphanumeric()));
+
+        let password = generate_from_pattern_str("lUH", &[]);
+        let chars: Vec<char> = password.chars().collect();
+        assert!(chars[0].is_ascii_lowercase());
+        assert!(chars[1].is_ascii_uppercase());
+        assert!(chars[2].is_ascii_hexdigit() && !chars[2].is_ascii_uppercase());
+    }
+}
+
+#[test]
+fn test_pattern_repetition_and_escapes() {
+    crate::init().unwrap();
+
+    let pin = generate_from_pattern_str("D{6}", &[]);
+    assert_eq!(6, pin.len());
+    assert!(pin.chars().all(|c| c.is_ascii_digit()));
+
+    assert_eq!("L-D", generate_from_pattern_str("\\L-\\D", &[]));
+    assert_eq!("ééé", generate_from_pattern_str("é{3}", &[]));
+}
+
+#[test]
+fn test_pattern_excluding_characters() {
+    crate::init().unwrap();
+
+    let digits = generate_from_pattern_str("D{1000}", &['7']);
+    assert!(!digits.contains('7'));
+    assert!(
+        generate_from_pattern("D", &['0', '1', '2', '3', '4', '5', '6', '7', '8', '9']).is_err()
+    );
+}
+
+#[test]
+fn test_invalid_patterns() {
+    crate::init().unwrap();
+
+    for pattern in ["", "D{", "D{x}", "}", "D\\"].iter() {
+        assert!(generate_from_pattern(pattern, &[]).is_err(), "{}", pattern);
+    }
+}
+
+#[test]
+fn test_overlong_patterns() {
+    crate::init().unwrap();
+
+    let longest = format!("a{{{}}}", MAX_PASSWORD_LENGTH);
+    assert_eq!(
+        MAX_PASSWORD_LENGTH,
+        generate_from_pattern_str(&longest, &[]).len()
+    );
+    for pattern in [
+        "a{99999999999}",
+        "a{99999999999999999999999}",
+        "D{4096}D",
+        "D{4000}L{4000}",
+    ]
+    .iter()
+    {
+        assert!(generate_from_pattern(pattern, &[]).is_err(), "{}", pattern);
+    }
+    assert!(generate_password(MAX_PASSWORD_LENGTH + 1, &[CharacterSet::Letters], &[]).is_err());
+}
+
+fn generate_passphrase_str(options: &PassphraseOptions) -> String {
+    unsafe {
+        std::str::from_utf8(generate_passphrase(options).unwrap().as_slice())
+            .unwrap()
+            .to_owned()
+    }
+}
+
+#[test]
+fn test_generating_empty_passphrase() {
+    crate::init().unwrap();
+
+    let options = PassphraseOptions {
+        words: 0,
+        ..Default::default()
+    };
+    assert!(generate_passphrase(&options).is_err());
+}
+
+#[test]
+fn test_passphrase_words() {
+    crate::init().unwrap();
+
+    let options = PassphraseOptions {
+        words: 8,
+        separator: " ".to_owned(),
+        ..Default::default()
+    };
+    for _ in 0..10 {
+        let passphrase = generate_passphrase_str(&options);
+        let words: Vec<&str> = passphrase.split(' ').collect();
+        assert_eq!(8, words.len());
+        assert!(words
+            .iter()
+            .all(|w| crate::crypto::wordlist::WORDLIST.contains(w)));
+    }
+}
+
+#[test]
+fn test_passphrase_capitalization_and_digit() {
+    crate::init().unwrap();
+
+    let options = PassphraseOptions {
+        capitalization: Capitalization::First,
+        insert_digit: true,
+        ..Default::default()
+    };
+    for _ in 0..10 {
+        let passphrase = generate_passphrase_str(&options);
+        let words: Vec<&str> = passphrase.split('-').collect();
+        assert_eq!(RECOMMENDED_MINIMUM_PASSPHRASE_WORDS, words.len());
+        assert!(words
+            .iter()
+            .all(|w| w.chars().next().unwrap().is_ascii_uppercase()));
+        assert_eq!(1, passphrase.chars().filter(|c| c.is_ascii_digit()).count());
+    }
+}
+
+#[test]
+fn test_passphrase_entropy() {
+    crate::init().unwrap();
+
+    let options = PassphraseOptions::default();
+    assert_eq!(66.0, passphrase_entropy(&options));
+    let options = PassphraseOptions {
+        capitalization: Capitalization::Random,
+        ..Default::default()
+    };
+    assert_eq!(72.0, passphrase_entropy(&options));
+}
@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::crypto::rng::Generator;
+use crate::crypto::rng::{uniform_index, DeterministicGenerator, Generator};
 use rand::{Rng, RngCore};
 use std::collections::HashSet;
 
@@ -42,3 +42,46 @@ fn test_rng_range() {
         assert!(n < max);
     }
 }
+
+#[test]
+fn test_deterministic_generator() {
+    crate::init().unwrap();
+
+    let mut a = DeterministicGenerator::new(b"seed");
+    let mut b = DeterministicGenerator::new(b"seed");
+    let mut c = DeterministicGenerator::new(b"other seed");
+
+    // Read in differently sized chunks, spanning block boundaries.
+    let mut a_bytes = vec![0_u8; 100];
+    a.fill_bytes(&mut a_bytes);
+    let mut b_bytes = vec![0_u8; 100];
+    for chunk in b_bytes.chunks_mut(7) {
+        b.fill_bytes(chunk);
+    }
+    let mut c_bytes = vec![0_u8; 100];
+    c.fill_bytes(&mut c_bytes);
+
+    assert_eq!(a_bytes, b_bytes);
+    assert_ne!(a_bytes, c_bytes);
+}
+
+#[test]
+fn test_uniform_index() {
+    crate::init().unwrap();
+
+    let mut generator = Generator;
+    for n in [1, 2, 7, 1000] {
+        for _ in 0..100 {
+            assert!(uniform_index(&mut generator, n) < n);
+        }
+    }
+
+    // The sampling routine is fixed, so a deterministic generator always
+    // produces these same values.
+    let mut generator = DeterministicGenerator::new(b"seed");
+    let tens: Vec<usize> = (0..8).map(|_| uniform_index(&mut generator, 10)).collect();
+    assert_eq!(vec![5, 1, 1, 6, 3, 0, 0, 6], tens);
+    let threes: Vec<usize> = (0..4).map(|_| uniform_index(&mut generator, 3)).collect();
+    assert_eq!(vec![1, 0, 2, 1], threes);
+    assert_eq!(443499542, uniform_index(&mut generator, 1000000007));
+}
@@ -0,0 +1,81 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::crypto::shamir::*;
+use crate::tests::random_secret;
+
+#[test]
+fn test_split_combine_round_trip() {
+    crate::init().unwrap();
+
+    let secret = random_secret(32);
+    let shares = split(&secret, 5, 3).unwrap();
+    assert_eq!(5, shares.len());
+
+    let recovered = combine(&shares[1..4]).unwrap();
+    unsafe {
+        assert_eq!(secret.as_slice(), recovered.as_slice());
+    }
+}
+
+#[test]
+fn test_any_threshold_shares_combine() {
+    crate::init().unwrap();
+
+    let secret = random_secret(32);
+    let shares = split(&secret, 4, 2).unwrap();
+    for a in 0..shares.len() {
+        for b in (a + 1)..shares.len() {
+            let subset = vec![
+                Share::from_mnemonic(&shares[b].to_mnemonic().unwrap(), 32).unwrap(),
+                Share::from_mnemonic(&shares[a].to_mnemonic().unwrap(), 32).unwrap(),
+            ];
+            let recovered = combine(&subset).unwrap();
+            unsafe {
+                assert_eq!(secret.as_slice(), recovered.as_slice());
+            }
+        }
+    }
+}
+
+#[test]
+fn test_combine_too_few_shares_fails() {
+    crate::init().unwrap();
+
+    let shares = split(&random_secret(32), 5, 3).unwrap();
+    assert!(combine(&shares[..2]).is_err());
+}
+
+#[test]
+fn test_invalid_split_parameters() {
+    crate::init().unwrap();
+
+    assert!(split(&random_secret(32), 5, 0).is_err());
+    assert!(split(&random_secret(32), 2, 3).is_err());
+}
+
+#[test]
+fn test_mnemonic_checksum_catches_typos() {
+    crate::init().unwrap();
+
+    let shares = split(&random_secret(32), 3, 2).unwrap();
+    let mnemonic = shares[0].to_mnemonic().unwrap();
+    let mut words: Vec<&str> = mnemonic.split_whitespace().collect();
+    words[3] = if words[3] == "abandon" {
+        "ability"
+    } else {
+        "abandon"
+    };
+    assert!(Share::from_mnemonic(&words.join(" "), 32).is_err());
+}
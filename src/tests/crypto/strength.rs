@@ -0,0 +1,114 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::crypto::strength::*;
+
+fn assert_pattern(password: &str, kind: PatternKind) {
+    let estimate = estimate(password);
+    assert!(
+        estimate.patterns.contains(&kind),
+        "expected {:?} in '{}', got {:?}",
+        kind,
+        password,
+        estimate.patterns
+    );
+}
+
+#[test]
+fn test_common_passwords_are_weak() {
+    crate::init().unwrap();
+
+    for password in &["password", "123456", "qwerty", "P@ssw0rd"] {
+        let estimate = estimate(password);
+        assert!(
+            estimate.score < 2,
+            "'{}' scored {}",
+            password,
+            estimate.score
+        );
+        assert!(estimate.warning().is_some());
+    }
+    assert_pattern("letmein", PatternKind::CommonPassword);
+}
+
+#[test]
+fn test_pattern_detection() {
+    crate::init().unwrap();
+
+    assert_pattern("%%xzfghjkl;", PatternKind::Keyboard);
+    assert_pattern("zzzzzzzz", PatternKind::Repeat);
+    assert_pattern("k9!k9!k9!k9!", PatternKind::Repeat);
+    assert_pattern("mmxmmx", PatternKind::Repeat);
+    assert_pattern("lmnopqrs", PatternKind::Sequence);
+    assert_pattern("97531", PatternKind::Sequence);
+    assert_pattern("x#13/03/1989", PatternKind::Date);
+    assert_pattern("x#19890313", PatternKind::Date);
+    assert_pattern("RAbbit", PatternKind::CommonPassword);
+    assert_pattern("xqzvolcano", PatternKind::Dictionary);
+}
+
+#[test]
+fn test_patterns_reduce_estimate() {
+    crate::init().unwrap();
+
+    // Patterned passwords should be estimated well below their brute force
+    // entropy (8 lowercase letters is ~37.6 bits).
+    assert!(estimate("abcdefgh").guesses_log2 < 15.0);
+    assert!(estimate("qwertyui").guesses_log2 < 15.0);
+    assert!(estimate("aaaaaaaa").guesses_log2 < 15.0);
+}
+
+#[test]
+fn test_long_input() {
+    crate::init().unwrap();
+
+    // These would take far too long if the estimator were super-linear in
+    // the length of its input.
+    let repeated = "A".repeat(100_000);
+    let estimate = estimate(&repeated);
+    assert!(estimate.guesses_log2 < 15.0);
+    assert_eq!(vec![PatternKind::Repeat], estimate.patterns);
+
+    let block: String = (0..200)
+        .map(|i| char::from(b'!' + (i % 90) as u8))
+        .collect();
+    assert_eq!(
+        MAX_SCORE,
+        estimate_secret(&crate::tests::str_secret(&block.repeat(500))).score
+    );
+}
+
+#[test]
+fn test_random_passwords_are_strong() {
+    crate::init().unwrap();
+
+    for password in &[
+        "r7#Kq9!vZx2@Lm",
+        "Wq8$zP3n&Yt6Jd",
+        "correct horse battery staple",
+    ] {
+        let estimate = estimate(password);
+        assert_eq!(MAX_SCORE, estimate.score, "'{}'", password);
+        assert!(estimate.warning().is_none());
+    }
+}
+
+#[test]
+fn test_empty_password() {
+    crate::init().unwrap();
+
+    let estimate = estimate("");
+    assert_eq!(0, estimate.score);
+    assert_eq!(0.0, estimate.guesses_log2);
+}
@@ -0,0 +1,41 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::crypto::wordlist::*;
+
+#[test]
+fn test_wordlist_size() {
+    crate::init().unwrap();
+
+    assert_eq!(2048, WORDLIST.len());
+}
+
+#[test]
+fn test_mnemonic_round_trip() {
+    crate::init().unwrap();
+
+    for len in 0..40 {
+        let data: Vec<u8> = (0..len).map(|i| (i * 37 + 11) as u8).collect();
+        let words = encode_mnemonic(data.as_slice());
+        assert_eq!(mnemonic_len(len), words.len());
+        assert_eq!(data, decode_mnemonic(&words, len).unwrap());
+    }
+}
+
+#[test]
+fn test_decode_unrecognized_word_fails() {
+    crate::init().unwrap();
+
+    assert!(decode_mnemonic(&["abandon", "notaword"], 2).is_err());
+}
@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+#[cfg(test)]
+mod cli;
 #[cfg(test)]
 mod configuration;
 #[cfg(test)]
@@ -21,6 +23,8 @@ mod crypto;
 #[cfg(test)]
 mod odf;
 #[cfg(test)]
+mod output;
+#[cfg(test)]
 mod repository;
 #[cfg(test)]
 mod util;
@@ -39,6 +43,21 @@ pub(crate) fn str_secret(s: &str) -> Secret {
     ret
 }
 
+/// Return the contents of the given secret, which must be UTF-8 text.
+#[cfg(test)]
+pub(crate) fn secret_string(secret: &Secret) -> String {
+    String::from_utf8(unsafe { secret.as_slice() }.to_vec()).unwrap()
+}
+
+/// Store the given text at the given path in the repository.
+#[cfg(test)]
+pub(crate) fn store_str(repository: &mut crate::repository::Repository, path: &str, value: &str) {
+    let path = repository.path(path).unwrap();
+    repository
+        .write_encrypt(&path, str_secret(value), None)
+        .unwrap();
+}
+
 #[cfg(test)]
 pub(crate) fn random_secret(len: usize) -> Secret {
     let mut s = Secret::with_len(len).unwrap();
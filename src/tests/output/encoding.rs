@@ -0,0 +1,108 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::output::encoding::{decode, encode, SecretEncoding};
+use crate::tests::{secret_string, str_secret};
+use bdrck::crypto::secret::Secret;
+use clap::ValueEnum;
+
+fn binary_secret() -> Secret {
+    let bytes: Vec<u8> = (0..=255).collect();
+    let mut s = Secret::with_len(bytes.len()).unwrap();
+    unsafe { s.as_mut_slice() }.copy_from_slice(&bytes);
+    s
+}
+
+fn encode_str(data: &str, encoding: SecretEncoding) -> String {
+    let encoded = encode(&str_secret(data), encoding).unwrap();
+    secret_string(&encoded)
+}
+
+fn decode_str(data: &str, encoding: SecretEncoding) -> String {
+    let decoded = decode(&str_secret(data), encoding).unwrap();
+    secret_string(&decoded)
+}
+
+#[test]
+fn test_round_trip() {
+    crate::init().unwrap();
+
+    let secret = binary_secret();
+    for &encoding in SecretEncoding::value_variants() {
+        let encoded = encode(&secret, encoding).unwrap();
+        if encoding != SecretEncoding::Raw {
+            assert!(std::str::from_utf8(unsafe { encoded.as_slice() }).is_ok());
+        }
+        let decoded = decode(&encoded, encoding).unwrap();
+        assert_eq!(unsafe { secret.as_slice() }, unsafe { decoded.as_slice() });
+    }
+}
+
+#[test]
+fn test_known_encodings() {
+    crate::init().unwrap();
+
+    assert_eq!("Zm9vYg==", encode_str("foob", SecretEncoding::Base64));
+    assert_eq!("76+/", encode_str("\u{fbff}", SecretEncoding::Base64));
+    assert_eq!("76-_", encode_str("\u{fbff}", SecretEncoding::Base64url));
+    assert_eq!("MZXW6YQ=", encode_str("foob", SecretEncoding::Base32));
+    assert_eq!("666f6f62", encode_str("foob", SecretEncoding::Hex));
+    assert_eq!("foob", encode_str("foob", SecretEncoding::Raw));
+}
+
+#[test]
+fn test_pem_armor() {
+    crate::init().unwrap();
+
+    let pem = encode_str(&"x".repeat(100), SecretEncoding::Pem);
+    let lines: Vec<&str> = pem.lines().collect();
+    assert_eq!("-----BEGIN PWM SECRET-----", lines[0]);
+    assert_eq!("-----END PWM SECRET-----", *lines.last().unwrap());
+    assert_eq!(64, lines[1].len());
+    assert!(lines[1..lines.len() - 1].iter().all(|l| l.len() <= 64));
+
+    // Armor with a different label, and surrounding text, is accepted.
+    assert_eq!(
+        "foob",
+        decode_str(
+            "junk\n-----BEGIN SOMETHING-----\nZm9v\nYg==\n-----END SOMETHING-----\n",
+            SecretEncoding::Pem
+        )
+    );
+}
+
+#[test]
+fn test_lenient_decoding() {
+    crate::init().unwrap();
+
+    assert_eq!("foob", decode_str("Zm9vYg==\n", SecretEncoding::Base64));
+    assert_eq!("foob", decode_str("Zm9vYg", SecretEncoding::Base64));
+    assert_eq!("foob", decode_str("mzxw6yq=", SecretEncoding::Base32));
+    assert_eq!("foob", decode_str("666F6F62\n", SecretEncoding::Hex));
+    assert_eq!("fo ob\n", decode_str("fo ob\n", SecretEncoding::Raw));
+}
+
+#[test]
+fn test_invalid_input() {
+    crate::init().unwrap();
+
+    assert!(decode(&str_secret("not base64!"), SecretEncoding::Base64).is_err());
+    assert!(decode(&str_secret("abc"), SecretEncoding::Hex).is_err());
+    assert!(decode(&str_secret("Zm9vYg=="), SecretEncoding::Pem).is_err());
+    assert!(decode(
+        &str_secret("-----BEGIN X-----\nZm9v\n"),
+        SecretEncoding::Pem
+    )
+    .is_err());
+}
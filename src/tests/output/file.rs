@@ -0,0 +1,170 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::crypto::pwgen::generate_hex;
+use crate::output::{output_secret_to_file, InputEncoding};
+use crate::tests::{random_secret, str_secret};
+use bdrck::testing::temp;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+fn mode<P: AsRef<Path>>(path: P) -> u32 {
+    fs::metadata(path).unwrap().permissions().mode() & 0o777
+}
+
+#[test]
+fn test_output_file_is_private() {
+    crate::init().unwrap();
+
+    let dir = temp::Dir::new("pwm-test").unwrap();
+    let path = dir.path().join("key.pem");
+    let secret = random_secret(1024);
+    output_secret_to_file(&secret, InputEncoding::Binary, &path, false, false).unwrap();
+
+    assert_eq!(0o600, mode(&path));
+    // Binary data is written as-is, not base64 encoded.
+    assert_eq!(
+        unsafe { secret.as_slice() },
+        fs::read(&path).unwrap().as_slice()
+    );
+}
+
+#[test]
+fn test_output_file_overwrite() {
+    crate::init().unwrap();
+
+    let dir = temp::Dir::new("pwm-test").unwrap();
+    let path = dir.path().join("existing");
+    fs::write(&path, "existing contents").unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+    assert!(
+        output_secret_to_file(&str_secret("new"), InputEncoding::Auto, &path, false, false)
+            .is_err()
+    );
+    assert_eq!("existing contents", fs::read_to_string(&path).unwrap());
+
+    output_secret_to_file(&str_secret("new"), InputEncoding::Auto, &path, true, false).unwrap();
+    assert_eq!("new", fs::read_to_string(&path).unwrap());
+    assert_eq!(0o600, mode(&path));
+}
+
+#[test]
+fn test_output_file_tmpfs() {
+    crate::init().unwrap();
+
+    // Procfs is never a tmpfs, so this must be refused before trying to write.
+    assert!(output_secret_to_file(
+        &str_secret("secret"),
+        InputEncoding::Auto,
+        "/proc/pwm-test",
+        false,
+        true
+    )
+    .is_err());
+
+    // /dev/shm is a tmpfs on most Linux systems.
+    let shm = Path::new("/dev/shm");
+    if shm.is_dir() {
+        let path = shm.join(format!("pwm-test-{}", generate_hex(8)));
+        let result = output_secret_to_file(
+            &str_secret("secret"),
+            InputEncoding::Auto,
+            &path,
+            false,
+            true,
+        );
+        let contents = fs::read_to_string(&path);
+        let _ = fs::remove_file(&path);
+        result.unwrap();
+        assert_eq!("secret", contents.unwrap());
+    }
+}
+
+#[test]
+fn test_output_file_refuses_symlinks() {
+    crate::init().unwrap();
+
+    let dir = temp::Dir::new("pwm-test").unwrap();
+    let target = dir.path().join("target");
+    fs::write(&target, "target contents").unwrap();
+    let link = dir.path().join("link");
+    std::os::unix::fs::symlink(&target, &link).unwrap();
+
+    for overwrite in [false, true] {
+        assert!(output_secret_to_file(
+            &str_secret("secret"),
+            InputEncoding::Auto,
+            &link,
+            overwrite,
+            false
+        )
+        .is_err());
+    }
+    assert_eq!("target contents", fs::read_to_string(&target).unwrap());
+}
+
+#[test]
+fn test_output_file_tmpfs_checks_the_file() {
+    crate::init().unwrap();
+
+    // A symlink on a tmpfs to a file elsewhere must not pass the check.
+    let shm = Path::new("/dev/shm");
+    if shm.is_dir() {
+        let dir = temp::Dir::new("pwm-test").unwrap();
+        let target = dir.path().join("target");
+        fs::write(&target, "target contents").unwrap();
+        let link = shm.join(format!("pwm-test-{}", generate_hex(8)));
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        let result = output_secret_to_file(
+            &str_secret("secret"),
+            InputEncoding::Auto,
+            &link,
+            true,
+            true,
+        );
+        let _ = fs::remove_file(&link);
+        assert!(result.is_err());
+        assert_eq!("target contents", fs::read_to_string(&target).unwrap());
+    }
+
+    // An existing file which isn't on a tmpfs is left alone, and a new one
+    // isn't left behind.
+    let dir = temp::Dir::new("pwm-test").unwrap();
+    if !crate::util::tmpfs::is_tmpfs(dir.path()).unwrap() {
+        let existing = dir.path().join("existing");
+        fs::write(&existing, "existing contents").unwrap();
+        assert!(output_secret_to_file(
+            &str_secret("secret"),
+            InputEncoding::Auto,
+            &existing,
+            true,
+            true
+        )
+        .is_err());
+        assert_eq!("existing contents", fs::read_to_string(&existing).unwrap());
+
+        let new = dir.path().join("new");
+        assert!(output_secret_to_file(
+            &str_secret("secret"),
+            InputEncoding::Auto,
+            &new,
+            false,
+            true
+        )
+        .is_err());
+        assert!(!new.exists());
+    }
+}
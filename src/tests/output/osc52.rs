@@ -0,0 +1,81 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::output::osc52::*;
+use crate::output::ClipboardSelection;
+
+fn sequence(selection: ClipboardSelection, payload: &[u8], multiplexer: Multiplexer) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_sequence(&mut out, selection, payload, multiplexer).unwrap();
+    out
+}
+
+#[test]
+fn test_plain_sequence() {
+    crate::init().unwrap();
+
+    assert_eq!(
+        b"\x1b]52;c;aHVudGVyMg==\x07".to_vec(),
+        sequence(
+            ClipboardSelection::Clipboard,
+            b"aHVudGVyMg==",
+            Multiplexer::None
+        )
+    );
+    assert_eq!(
+        b"\x1b]52;p;aHVudGVyMg==\x07".to_vec(),
+        sequence(
+            ClipboardSelection::Primary,
+            b"aHVudGVyMg==",
+            Multiplexer::None
+        )
+    );
+}
+
+#[test]
+fn test_tmux_passthrough() {
+    crate::init().unwrap();
+
+    assert_eq!(
+        b"\x1bPtmux;\x1b\x1b]52;c;aHVudGVyMg==\x07\x1b\\".to_vec(),
+        sequence(
+            ClipboardSelection::Clipboard,
+            b"aHVudGVyMg==",
+            Multiplexer::Tmux
+        )
+    );
+}
+
+#[test]
+fn test_screen_passthrough_is_chunked() {
+    crate::init().unwrap();
+
+    let payload = vec![b'A'; 200];
+    let out = sequence(ClipboardSelection::Clipboard, &payload, Multiplexer::Screen);
+    assert!(out.starts_with(b"\x1bP\x1b]52;c;"));
+    assert!(out.ends_with(b"\x07\x1b\\"));
+
+    // Each passthrough string must be short enough for screen to accept.
+    let s = String::from_utf8(out).unwrap();
+    for part in s.split("\x1b\\") {
+        assert!(part.len() <= 100, "passthrough too long: {}", part.len());
+    }
+    // Stripping the wrapping should give back the whole payload.
+    let stripped: String = s
+        .replace("\x1b\\", "")
+        .replace("\x1bP", "")
+        .replace("\x1b]52;c;", "")
+        .replace('\x07', "");
+    assert_eq!(String::from_utf8(payload).unwrap(), stripped);
+}
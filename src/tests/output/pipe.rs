@@ -0,0 +1,44 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::output::{output_secret_to_command, InputEncoding};
+use crate::tests::str_secret;
+use bdrck::testing::temp;
+use std::fs;
+
+#[test]
+fn test_pipe_to_command() {
+    crate::init().unwrap();
+
+    let dir = temp::Dir::new("pwm-test").unwrap();
+    let path = dir.path().join("out");
+    output_secret_to_command(
+        &str_secret("hunter2"),
+        InputEncoding::Auto,
+        &format!("cat > '{}'", path.display()),
+    )
+    .unwrap();
+    assert_eq!("hunter2", fs::read_to_string(&path).unwrap());
+}
+
+#[test]
+fn test_pipe_to_failing_command() {
+    crate::init().unwrap();
+
+    assert!(
+        output_secret_to_command(&str_secret("hunter2"), InputEncoding::Auto, "exit 3").is_err()
+    );
+    // A command which doesn't read its input at all is fine, if it succeeds.
+    output_secret_to_command(&str_secret("hunter2"), InputEncoding::Auto, "true").unwrap();
+}
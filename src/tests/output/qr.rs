@@ -0,0 +1,41 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::output::qr::*;
+use crate::wifiqr::ErrorCorrection;
+
+#[test]
+fn test_render_terminal_dimensions() {
+    crate::init().unwrap();
+
+    // "hunter2" fits in a version 1 code, which is 21x21 modules. With a two
+    // module quiet zone on each side, that's 25x25, or 13 lines of 25 cells.
+    let rendered = render_terminal(b"hunter2", ErrorCorrection::Medium).unwrap();
+    let lines: Vec<&str> = rendered.lines().collect();
+    assert_eq!(13, lines.len());
+    for line in lines {
+        assert_eq!(25, line.matches('\u{2580}').count());
+        assert!(line.ends_with("\x1b[0m"));
+    }
+}
+
+#[test]
+fn test_render_terminal_quiet_zone() {
+    crate::init().unwrap();
+
+    // The first line is entirely quiet zone, so every cell is light on light.
+    let rendered = render_terminal(b"hunter2", ErrorCorrection::Medium).unwrap();
+    let first = rendered.lines().next().unwrap();
+    assert_eq!(25, first.matches("\x1b[97;107m\u{2580}").count());
+}
@@ -0,0 +1,183 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::crypto::breach::BreachDatabase;
+use crate::repository::audit::*;
+use crate::repository::Repository;
+use crate::tests::{store_str, str_secret};
+use bdrck::crypto::secret::Secret;
+use bdrck::testing::temp;
+use sha1::{Digest, Sha1};
+use std::fs;
+use std::time::Duration;
+
+#[test]
+fn test_audit_weak_and_reused() {
+    crate::init().unwrap();
+
+    let repository_dir = temp::Dir::new("pwm-test").unwrap();
+    let mut repository =
+        Repository::new(repository_dir.path(), true, Some(str_secret("foobar"))).unwrap();
+    store_str(&mut repository, "a", "letmein");
+    store_str(&mut repository, "b", "r7#Kq9!vZx2@Lm");
+    store_str(&mut repository, "c/d", "r7#Kq9!vZx2@Lm");
+    store_str(&mut repository, "e", "Wq8$zP3n&Yt6Jd");
+
+    let report = audit(&repository, 3, Duration::from_secs(24 * 60 * 60), None).unwrap();
+    assert_eq!(
+        vec!["a"],
+        report
+            .weak
+            .iter()
+            .map(|e| e.path.as_str())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(1, report.reused.len());
+    assert_eq!(vec!["b", "c/d"], report.reused[0].paths);
+    assert!(report.stale.is_empty());
+
+    // Plaintext must never appear in the report.
+    let serialized = serde_json::to_string(&report).unwrap();
+    assert!(!serialized.contains("letmein"));
+    assert!(!serialized.contains("r7#Kq9"));
+}
+
+#[test]
+fn test_audit_derived_passwords() {
+    use crate::crypto::derive::Derivation;
+    use crate::crypto::pwgen::{CharacterSet, GeneratorSettings, PasswordPolicy};
+
+    crate::init().unwrap();
+
+    let repository_dir = temp::Dir::new("pwm-test").unwrap();
+    let mut repository =
+        Repository::new(repository_dir.path(), true, Some(str_secret("foobar"))).unwrap();
+    let derivation = Derivation {
+        site: "example.com".to_owned(),
+        login: "me".to_owned(),
+        counter: 0,
+        settings: GeneratorSettings {
+            length: 4,
+            charsets: vec![CharacterSet::Numbers],
+            exclude: vec![],
+            policy: PasswordPolicy::default(),
+            pattern: None,
+        },
+    };
+    let path = repository.path("derived").unwrap();
+    let derived = repository.write_derivation(&path, &derivation).unwrap();
+    // Storing the derived password elsewhere is reuse, even though the stored
+    // derivation entry itself differs.
+    let copy = repository.path("copy").unwrap();
+    repository.write_encrypt(&copy, derived, None).unwrap();
+
+    let report = audit(&repository, 3, Duration::from_secs(24 * 60 * 60), None).unwrap();
+    let weak: Vec<&str> = report.weak.iter().map(|e| e.path.as_str()).collect();
+    assert_eq!(vec!["copy", "derived"], weak);
+    assert_eq!(1, report.reused.len());
+    assert_eq!(vec!["copy", "derived"], report.reused[0].paths);
+}
+
+#[test]
+fn test_audit_skips_non_passwords() {
+    crate::init().unwrap();
+
+    let repository_dir = temp::Dir::new("pwm-test").unwrap();
+    let mut repository =
+        Repository::new(repository_dir.path(), true, Some(str_secret("foobar"))).unwrap();
+    // All of these would be estimated as weak, if they were passwords.
+    store_str(&mut repository, "key", &"A".repeat(1024 * 1024));
+    store_str(
+        &mut repository,
+        "pem",
+        "-----BEGIN KEY-----\naaaaaaaa\n-----END KEY-----\n",
+    );
+    let binary = repository.path("binary").unwrap();
+    let mut data = Secret::with_len(16).unwrap();
+    unsafe { data.as_mut_slice().fill(0xff) };
+    repository.write_encrypt(&binary, data, None).unwrap();
+    store_str(&mut repository, "password", "letmein\n");
+
+    let report = audit(&repository, 3, Duration::from_secs(24 * 60 * 60), None).unwrap();
+    assert_eq!(
+        vec!["password"],
+        report
+            .weak
+            .iter()
+            .map(|e| e.path.as_str())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_audit_stale() {
+    crate::init().unwrap();
+
+    let repository_dir = temp::Dir::new("pwm-test").unwrap();
+    let mut repository =
+        Repository::new(repository_dir.path(), true, Some(str_secret("foobar"))).unwrap();
+    store_str(&mut repository, "a", "r7#Kq9!vZx2@Lm");
+    store_str(&mut repository, "b", "Wq8$zP3n&Yt6Jd");
+
+    // With a zero maximum age, everything is stale.
+    let report = audit(&repository, 0, Duration::from_secs(0), None).unwrap();
+    assert_eq!(
+        vec!["a", "b"],
+        report
+            .stale
+            .iter()
+            .map(|e| e.path.as_str())
+            .collect::<Vec<_>>()
+    );
+    assert!(report.weak.is_empty());
+    assert!(report.reused.is_empty());
+}
+
+#[test]
+fn test_audit_breached() {
+    crate::init().unwrap();
+
+    let breached = "Hx9!mQ2#vL7$pR4z";
+    let f = temp::File::new_file().unwrap();
+    fs::write(
+        f.path(),
+        format!(
+            "{}:42\n",
+            data_encoding::HEXUPPER.encode(&Sha1::digest(breached.as_bytes()))
+        ),
+    )
+    .unwrap();
+    let mut db = BreachDatabase::open(f.path()).unwrap();
+
+    let repository_dir = temp::Dir::new("pwm-test").unwrap();
+    let mut repository =
+        Repository::new(repository_dir.path(), true, Some(str_secret("foobar"))).unwrap();
+    store_str(&mut repository, "a", breached);
+    store_str(&mut repository, "b", "Wq8$zP3n&Yt6Jd");
+
+    let report = audit(
+        &repository,
+        0,
+        Duration::from_secs(24 * 60 * 60),
+        Some(&mut db),
+    )
+    .unwrap();
+    assert_eq!(
+        vec![BreachedEntry {
+            path: "a".to_owned(),
+            count: 42
+        }],
+        report.breached
+    );
+}
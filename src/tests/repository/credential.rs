@@ -0,0 +1,257 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::repository::credential::*;
+use crate::repository::Repository;
+use crate::tests::{secret_string, store_str, str_secret};
+use bdrck::testing::temp;
+
+fn request(input: &str) -> Request {
+    Request::read(input.as_bytes()).unwrap()
+}
+
+fn get_str(repository: &Repository, scheme: &PathScheme, input: &str) -> Option<(String, String)> {
+    get(repository, scheme, &request(input))
+        .unwrap()
+        .map(|(username, password)| (username, secret_string(&password)))
+}
+
+fn read_str(repository: &Repository, path: &str) -> String {
+    let secret = repository
+        .read_decrypt(&repository.path(path).unwrap())
+        .unwrap();
+    secret_string(&secret)
+}
+
+#[test]
+fn test_read_request() {
+    crate::init().unwrap();
+
+    let r = request("protocol=https\nhost=example.com\npassword=a=b\n\nignored=1\n");
+    assert_eq!(2, r.attributes.len());
+    assert_eq!("https", r.attributes["protocol"]);
+    assert_eq!("example.com", r.attributes["host"]);
+    assert!(!r.attributes.contains_key("ignored"));
+    assert_eq!(b"a=b", unsafe { r.password.unwrap().as_slice() });
+
+    assert!(Request::read("nonsense\n".as_bytes()).is_err());
+}
+
+#[test]
+fn test_write_response() {
+    crate::init().unwrap();
+
+    let mut out = Vec::new();
+    write_response(&mut out, "alice", &str_secret("hunter2")).unwrap();
+    assert_eq!(
+        "username=alice\npassword=hunter2\n",
+        String::from_utf8(out).unwrap()
+    );
+
+    assert!(write_response(Vec::new(), "alice", &str_secret("a\nb")).is_err());
+}
+
+#[test]
+fn test_path_scheme() {
+    crate::init().unwrap();
+
+    let scheme = PathScheme::default();
+    let attributes = request("protocol=https\nhost=example.com:8443\n").attributes;
+    assert_eq!(
+        Some("alice"),
+        scheme.match_username(&attributes, "git/https/example.com:8443/alice")
+    );
+    assert_eq!(
+        None,
+        scheme.match_username(&attributes, "git/https/example.com:8443/a/b")
+    );
+    assert_eq!(
+        None,
+        scheme.match_username(&attributes, "git/http/example.com:8443/alice")
+    );
+
+    let full = request("protocol=https\nhost=example.com\nusername=bob\n").attributes;
+    assert_eq!(
+        Some("git/https/example.com/bob".to_owned()),
+        scheme.path_for(&full)
+    );
+    // Missing attributes mean we can't handle the request.
+    let partial = request("host=example.com\nusername=bob\n").attributes;
+    assert_eq!(None, scheme.path_for(&partial));
+
+    assert!("{host}".parse::<PathScheme>().is_err());
+    assert!("{host}/{username}/{username}"
+        .parse::<PathScheme>()
+        .is_err());
+    assert!("{host}/{port}/{username}".parse::<PathScheme>().is_err());
+    assert!("{host/{username}".parse::<PathScheme>().is_err());
+    let custom: PathScheme = "{host}{path}/{username}".parse().unwrap();
+    let with_path = request("host=h\npath=/org/repo.git\nusername=u\n").attributes;
+    assert_eq!(
+        Some("h/org/repo.git/u".to_owned()),
+        custom.path_for(&with_path)
+    );
+
+    // Attribute values must not be able to escape their place in the path.
+    for input in [
+        "protocol=https\nhost=..\nusername=bob\n",
+        "protocol=https\nhost=.\nusername=bob\n",
+        "protocol=https\nhost=a/b\nusername=bob\n",
+        "protocol=https\nhost=example.com\nusername=.\n",
+        "protocol=https\nhost=example.com\nusername=..\n",
+        "protocol=..\nhost=example.com\nusername=bob\n",
+    ] {
+        assert_eq!(
+            None,
+            scheme.path_for(&request(input).attributes),
+            "{}",
+            input
+        );
+    }
+    for input in [
+        "host=h\npath=../../x\nusername=u\n",
+        "host=h\npath=org/./repo.git\nusername=u\n",
+    ] {
+        assert_eq!(
+            None,
+            custom.path_for(&request(input).attributes),
+            "{}",
+            input
+        );
+    }
+    let attributes = request("protocol=https\nhost=example.com\n").attributes;
+    assert_eq!(
+        None,
+        scheme.match_username(&attributes, "git/https/example.com/..")
+    );
+}
+
+#[test]
+fn test_store_rejects_escaping_paths() {
+    crate::init().unwrap();
+
+    let parent = temp::Dir::new("pwm-test").unwrap();
+    let repository_dir = parent.sub_path("repository").unwrap();
+    let mut repository =
+        Repository::new(&repository_dir, true, Some(str_secret("foobar"))).unwrap();
+    let scheme: PathScheme = "git/{host}/{path}/{username}".parse().unwrap();
+
+    for input in [
+        "host=..\npath=x\nusername=alice\npassword=hunter2\n",
+        "host=h\npath=x\nusername=.\npassword=hunter2\n",
+        "host=h\npath=../../x\nusername=alice\npassword=hunter2\n",
+    ] {
+        store(&mut repository, &scheme, &request(input)).unwrap();
+    }
+    assert!(repository.list(None).unwrap().is_empty());
+    assert!(!parent.path().join("x").exists());
+    assert_eq!(
+        vec!["repository"],
+        std::fs::read_dir(parent.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_store_get_erase() {
+    crate::init().unwrap();
+
+    let repository_dir = temp::Dir::new("pwm-test").unwrap();
+    let mut repository =
+        Repository::new(repository_dir.path(), true, Some(str_secret("foobar"))).unwrap();
+    let scheme = PathScheme::default();
+
+    assert_eq!(
+        None,
+        get_str(&repository, &scheme, "protocol=https\nhost=example.com\n")
+    );
+
+    store(
+        &mut repository,
+        &scheme,
+        &request("protocol=https\nhost=example.com\nusername=alice\npassword=hunter2\n"),
+    )
+    .unwrap();
+    assert_eq!(
+        "hunter2",
+        read_str(&repository, "git/https/example.com/alice")
+    );
+
+    // With or without a username, the stored credential is found.
+    let expected = Some(("alice".to_owned(), "hunter2".to_owned()));
+    assert_eq!(
+        expected,
+        get_str(&repository, &scheme, "protocol=https\nhost=example.com\n")
+    );
+    assert_eq!(
+        expected,
+        get_str(
+            &repository,
+            &scheme,
+            "protocol=https\nhost=example.com\nusername=alice\n"
+        )
+    );
+    assert_eq!(
+        None,
+        get_str(
+            &repository,
+            &scheme,
+            "protocol=https\nhost=example.com\nusername=bob\n"
+        )
+    );
+
+    // Updating the password keeps any other lines in the entry.
+    store_str(
+        &mut repository,
+        "git/https/example.com/alice",
+        "hunter2\nnote: CI token",
+    );
+    let path = repository.path("git/https/example.com/alice").unwrap();
+    store(
+        &mut repository,
+        &scheme,
+        &request("protocol=https\nhost=example.com\nusername=alice\npassword=hunter3\n"),
+    )
+    .unwrap();
+    assert_eq!(
+        "hunter3\nnote: CI token",
+        read_str(&repository, "git/https/example.com/alice")
+    );
+
+    // With several usernames for a host, git has to tell us which one to use.
+    store(
+        &mut repository,
+        &scheme,
+        &request("protocol=https\nhost=example.com\nusername=bob\npassword=letmein\n"),
+    )
+    .unwrap();
+    assert_eq!(
+        None,
+        get_str(&repository, &scheme, "protocol=https\nhost=example.com\n")
+    );
+
+    // Erasing with a stale password leaves the updated credential alone.
+    let erase_alice = "protocol=https\nhost=example.com\nusername=alice\n";
+    erase(
+        &mut repository,
+        &scheme,
+        &request(&format!("{}password=hunter2\n", erase_alice)),
+    )
+    .unwrap();
+    assert!(path.absolute_path().exists());
+    erase(&mut repository, &scheme, &request(erase_alice)).unwrap();
+    assert!(!path.absolute_path().exists());
+}
@@ -0,0 +1,142 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::repository::docker::*;
+use crate::repository::Repository;
+use crate::tests::{store_str, str_secret};
+use bdrck::testing::temp;
+use std::collections::BTreeMap;
+
+static HUB_URL: &str = "https://index.docker.io/v1/";
+
+fn credentials(server_url: &str, username: &str, secret: &str) -> Credentials {
+    Credentials {
+        server_url: server_url.to_owned(),
+        username: username.to_owned(),
+        secret: secret.to_owned(),
+    }
+}
+
+#[test]
+fn test_entry_path() {
+    crate::init().unwrap();
+
+    assert_eq!(
+        "docker/https%3A%2F%2Findex.docker.io%2Fv1%2F",
+        entry_path("docker", HUB_URL).unwrap()
+    );
+    assert_eq!(
+        "a/b/registry.example.com%3A5000",
+        entry_path("a/b/", "registry.example.com:5000").unwrap()
+    );
+    assert_eq!("ghcr.io", entry_path("", "ghcr.io").unwrap());
+
+    // Server URLs must never be able to escape the prefix.
+    assert_eq!("docker/%2E", entry_path("docker", ".").unwrap());
+    assert_eq!("docker/%2E.", entry_path("docker", "..").unwrap());
+    assert_eq!("%2E.", entry_path("", "..").unwrap());
+    assert_eq!(
+        "docker/%2Eprevious",
+        entry_path("docker", ".previous").unwrap()
+    );
+    assert_eq!("docker/%2E.%2F..", entry_path("docker", "../..").unwrap());
+    assert!(entry_path("docker", "").is_err());
+}
+
+#[test]
+fn test_json_format() {
+    crate::init().unwrap();
+
+    let parsed: Credentials =
+        serde_json::from_str(r#"{"ServerURL":"ghcr.io","Username":"alice","Secret":"hunter2"}"#)
+            .unwrap();
+    assert_eq!(credentials("ghcr.io", "alice", "hunter2"), parsed);
+    assert_eq!(
+        r#"{"ServerURL":"ghcr.io","Username":"alice","Secret":"hunter2"}"#,
+        serde_json::to_string(&parsed).unwrap()
+    );
+}
+
+#[test]
+fn test_store_get_list_erase() {
+    crate::init().unwrap();
+
+    let repository_dir = temp::Dir::new("pwm-test").unwrap();
+    let mut repository =
+        Repository::new(repository_dir.path(), true, Some(str_secret("foobar"))).unwrap();
+
+    let error = get(&repository, "docker", HUB_URL).unwrap_err();
+    assert_eq!(NOT_FOUND_MESSAGE, error.to_string());
+    assert!(list(&repository, "docker").unwrap().is_empty());
+
+    let hub = credentials(HUB_URL, "alice", "hunter2");
+    let ghcr = credentials("ghcr.io", "bob", "ghp_abc123");
+    store(&mut repository, "docker", &hub).unwrap();
+    store(&mut repository, "docker", &ghcr).unwrap();
+    // Unrelated entries under the prefix are ignored.
+    store_str(&mut repository, "docker/notes/readme", "hello");
+
+    assert_eq!(hub, get(&repository, "docker", HUB_URL).unwrap());
+    assert_eq!(ghcr, get(&repository, "docker", "ghcr.io").unwrap());
+    let mut expected = BTreeMap::new();
+    expected.insert(HUB_URL.to_owned(), "alice".to_owned());
+    expected.insert("ghcr.io".to_owned(), "bob".to_owned());
+    assert_eq!(expected, list(&repository, "docker").unwrap());
+
+    let updated = credentials(HUB_URL, "alice", "hunter3");
+    store(&mut repository, "docker", &updated).unwrap();
+    assert_eq!(updated, get(&repository, "docker", HUB_URL).unwrap());
+
+    erase(&mut repository, "docker", HUB_URL).unwrap();
+    assert!(get(&repository, "docker", HUB_URL).is_err());
+    assert!(erase(&mut repository, "docker", HUB_URL).is_err());
+    assert_eq!(1, list(&repository, "docker").unwrap().len());
+
+    assert!(store(
+        &mut repository,
+        "docker",
+        &credentials("ghcr.io", "bob", "a\nb")
+    )
+    .is_err());
+}
+
+#[test]
+fn test_dot_server_urls_stay_under_prefix() {
+    crate::init().unwrap();
+
+    let repository_dir = temp::Dir::new("pwm-test").unwrap();
+    let mut repository =
+        Repository::new(repository_dir.path(), true, Some(str_secret("foobar"))).unwrap();
+    store_str(&mut repository, "other", "hello");
+    let other = repository.path("other").unwrap();
+
+    // Nothing is stored at these paths, so this must not remove the prefix
+    // (or the repository root) instead.
+    for server_url in [".", ".."] {
+        assert!(erase(&mut repository, "docker", server_url).is_err());
+    }
+    assert!(other.absolute_path().exists());
+
+    let dot = credentials(".", "alice", "hunter2");
+    let dotdot = credentials("..", "bob", "hunter3");
+    store(&mut repository, "docker", &dot).unwrap();
+    store(&mut repository, "docker", &dotdot).unwrap();
+    assert_eq!(dot, get(&repository, "docker", ".").unwrap());
+    assert_eq!(dotdot, get(&repository, "docker", "..").unwrap());
+    assert_eq!(2, list(&repository, "docker").unwrap().len());
+
+    erase(&mut repository, "docker", "..").unwrap();
+    assert_eq!(dot, get(&repository, "docker", ".").unwrap());
+    assert!(other.absolute_path().exists());
+}
@@ -0,0 +1,114 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::repository::env::*;
+use crate::repository::Repository;
+use crate::tests::{secret_string, store_str, str_secret};
+use bdrck::testing::temp;
+use std::path::Path;
+
+fn to_strings(values: Vec<(String, bdrck::crypto::secret::Secret)>) -> Vec<(String, String)> {
+    values
+        .into_iter()
+        .map(|(name, value)| (name, secret_string(&value)))
+        .collect()
+}
+
+#[test]
+fn test_parse_binding() {
+    crate::init().unwrap();
+
+    assert_eq!(
+        EnvBinding {
+            name: "DB_PASS".to_owned(),
+            path: "db/prod=1".to_owned(),
+        },
+        "DB_PASS=db/prod=1".parse::<EnvBinding>().unwrap()
+    );
+    assert!("DB_PASS".parse::<EnvBinding>().is_err());
+    assert!("DB_PASS=".parse::<EnvBinding>().is_err());
+    assert!("1DB=db/prod".parse::<EnvBinding>().is_err());
+    assert!("DB-PASS=db/prod".parse::<EnvBinding>().is_err());
+}
+
+#[test]
+fn test_variable_name() {
+    crate::init().unwrap();
+
+    let name = |prefix: &str, path: &str| variable_name(Path::new(prefix), Path::new(path));
+    assert_eq!("DB_PASSWORD", name("prod", "prod/db/password"));
+    assert_eq!("API_KEY", name("prod/", "prod/api-key"));
+    assert_eq!("PROD_API_KEY", name("", "prod/api.key"));
+    assert_eq!("API_KEY", name("prod/api-key", "prod/api-key"));
+    assert_eq!("_2FA", name("", "2fa"));
+}
+
+#[test]
+fn test_format_variable() {
+    crate::init().unwrap();
+
+    assert_eq!(
+        "export A='it'\\''s $x'",
+        format_variable("A", "it's $x", EnvFormat::Shell)
+    );
+    assert_eq!(
+        "A=\"say \\\"\\$x\\\"\\nok\\\\\"",
+        format_variable("A", "say \"$x\"\nok\\", EnvFormat::Dotenv)
+    );
+}
+
+#[test]
+fn test_read_bindings_and_subtree() {
+    crate::init().unwrap();
+
+    let repository_dir = temp::Dir::new("pwm-test").unwrap();
+    let mut repository =
+        Repository::new(repository_dir.path(), true, Some(str_secret("foobar"))).unwrap();
+    store_str(&mut repository, "prod/db/password", "hunter2");
+    store_str(&mut repository, "prod/api-key", "abc123");
+    store_str(&mut repository, "dev/api-key", "def456");
+
+    let bindings = vec![
+        "DB_PASS=prod/db/password".parse().unwrap(),
+        "KEY=dev/api-key".parse().unwrap(),
+    ];
+    assert_eq!(
+        vec![
+            ("DB_PASS".to_owned(), "hunter2".to_owned()),
+            ("KEY".to_owned(), "def456".to_owned()),
+        ],
+        to_strings(read_bindings(&repository, &bindings).unwrap())
+    );
+
+    let duplicates = vec![
+        "KEY=prod/api-key".parse().unwrap(),
+        "KEY=dev/api-key".parse().unwrap(),
+    ];
+    assert!(read_bindings(&repository, &duplicates).is_err());
+
+    let prefix = repository.path("prod").unwrap();
+    let mut values = to_strings(read_subtree(&repository, &prefix).unwrap());
+    values.sort();
+    assert_eq!(
+        vec![
+            ("API_KEY".to_owned(), "abc123".to_owned()),
+            ("DB_PASSWORD".to_owned(), "hunter2".to_owned()),
+        ],
+        values
+    );
+
+    // Names which collide within a subtree are an error.
+    store_str(&mut repository, "prod/api_key", "ghi789");
+    assert!(read_subtree(&repository, &prefix).is_err());
+}
@@ -89,3 +89,164 @@ fn test_open_bad_key_fails() {
     )
     .is_err());
 }
+
+#[test]
+fn test_requiring_two_factor() {
+    use crate::crypto::key::CompositeKey;
+    use bdrck::crypto::key::AbstractKey;
+    use bdrck::crypto::keystore::DiskKeyStore;
+
+    crate::init().unwrap();
+
+    let directory = temp::Dir::new(TEST_KEYSTORE_DIR).unwrap();
+    let path = directory.sub_path(TEST_KEYSTORE_FILE).unwrap();
+    let mut config = Configuration::default();
+    let password_key = config
+        .get_password_key(Some(str_secret("foo")), "", false)
+        .unwrap();
+    // There's no PIV device in tests, so a second password stands in for one.
+    let piv_key = config
+        .get_password_key(Some(str_secret("piv")), "", false)
+        .unwrap();
+    let two_factor_key = CompositeKey::new(
+        config
+            .get_password_key(Some(str_secret("piv")), "", false)
+            .unwrap(),
+        config
+            .get_password_key(Some(str_secret("foo")), "", false)
+            .unwrap(),
+    );
+    let piv_digests = vec![piv_key.get_digest()];
+
+    {
+        let mut keystore = get_keystore(
+            &path,
+            /*allow_create=*/ true,
+            &config,
+            Some(str_secret("foo")),
+        )
+        .unwrap();
+        add_key(&mut keystore, &piv_key).unwrap();
+        set_requires_two_factor_with_keys(
+            &mut config,
+            &mut keystore,
+            /*required=*/ true,
+            &password_key,
+            &piv_key,
+            piv_digests.as_slice(),
+        )
+        .unwrap();
+        assert!(config.requires_two_factor());
+    }
+
+    {
+        // Neither factor alone should open the repository any more.
+        let mut keystore = DiskKeyStore::new(&path, /*force_overwrite=*/ false).unwrap();
+        assert_eq!(1, keystore.iter_wrapped_keys().count());
+        assert!(keystore.open(&password_key).is_err());
+        assert!(keystore.open(&piv_key).is_err());
+        keystore.open(&two_factor_key).unwrap();
+        assert!(keystore.is_open());
+        assert!(get_keystore(&path, false, &config, Some(str_secret("foo"))).is_err());
+    }
+
+    {
+        let mut keystore = DiskKeyStore::new(&path, /*force_overwrite=*/ false).unwrap();
+        keystore.open(&two_factor_key).unwrap();
+        set_requires_two_factor_with_keys(
+            &mut config,
+            &mut keystore,
+            /*required=*/ false,
+            &password_key,
+            &piv_key,
+            piv_digests.as_slice(),
+        )
+        .unwrap();
+        assert!(!config.requires_two_factor());
+    }
+
+    let mut keystore = DiskKeyStore::new(&path, /*force_overwrite=*/ false).unwrap();
+    assert_eq!(2, keystore.iter_wrapped_keys().count());
+    assert!(keystore.open(&two_factor_key).is_err());
+    keystore.open(&piv_key).unwrap();
+    let keystore = get_keystore(&path, false, &config, Some(str_secret("foo"))).unwrap();
+    assert!(keystore.is_open());
+}
+
+#[test]
+fn test_requiring_two_factor_with_other_keys_fails() {
+    use bdrck::crypto::key::AbstractKey;
+
+    crate::init().unwrap();
+
+    let directory = temp::Dir::new(TEST_KEYSTORE_DIR).unwrap();
+    let mut config = Configuration::default();
+    let password_key = config
+        .get_password_key(Some(str_secret("foo")), "", false)
+        .unwrap();
+    let piv_key = config
+        .get_password_key(Some(str_secret("piv")), "", false)
+        .unwrap();
+    let mut keystore = get_keystore(
+        directory.sub_path(TEST_KEYSTORE_FILE).unwrap(),
+        /*allow_create=*/ true,
+        &config,
+        Some(str_secret("foo")),
+    )
+    .unwrap();
+    add_key(&mut keystore, &piv_key).unwrap();
+    add_password_key(&config, &mut keystore, Some(str_secret("bar"))).unwrap();
+
+    assert!(set_requires_two_factor_with_keys(
+        &mut config,
+        &mut keystore,
+        /*required=*/ true,
+        &password_key,
+        &piv_key,
+        &[piv_key.get_digest()],
+    )
+    .is_err());
+    assert!(!config.requires_two_factor());
+    assert_eq!(3, keystore.iter_wrapped_keys().count());
+}
+
+#[test]
+fn test_requiring_two_factor_with_several_piv_devices_fails() {
+    use bdrck::crypto::key::AbstractKey;
+
+    crate::init().unwrap();
+
+    let directory = temp::Dir::new(TEST_KEYSTORE_DIR).unwrap();
+    let mut config = Configuration::default();
+    let password_key = config
+        .get_password_key(Some(str_secret("foo")), "", false)
+        .unwrap();
+    let piv_key = config
+        .get_password_key(Some(str_secret("piv")), "", false)
+        .unwrap();
+    let backup_piv_key = config
+        .get_password_key(Some(str_secret("backup")), "", false)
+        .unwrap();
+    let mut keystore = get_keystore(
+        directory.sub_path(TEST_KEYSTORE_FILE).unwrap(),
+        /*allow_create=*/ true,
+        &config,
+        Some(str_secret("foo")),
+    )
+    .unwrap();
+    add_key(&mut keystore, &piv_key).unwrap();
+    add_key(&mut keystore, &backup_piv_key).unwrap();
+
+    // The backup device must not be silently unregistered.
+    assert!(set_requires_two_factor_with_keys(
+        &mut config,
+        &mut keystore,
+        /*required=*/ true,
+        &password_key,
+        &piv_key,
+        &[piv_key.get_digest(), backup_piv_key.get_digest()],
+    )
+    .is_err());
+    assert!(!config.requires_two_factor());
+    assert_eq!(3, keystore.iter_wrapped_keys().count());
+}
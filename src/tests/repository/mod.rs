@@ -12,9 +12,19 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+#[cfg(test)]
+mod audit;
+#[cfg(test)]
+mod credential;
+#[cfg(test)]
+mod docker;
+#[cfg(test)]
+mod env;
 #[cfg(test)]
 mod keystore;
 #[cfg(test)]
 mod repository;
 #[cfg(test)]
 mod serde;
+#[cfg(test)]
+mod template;
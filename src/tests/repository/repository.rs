@@ -12,9 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::crypto::configuration::KeyType;
+use crate::crypto::derive::Derivation;
+use crate::crypto::pwgen::{CharacterSet, GeneratorSettings, PasswordPolicy};
 use crate::repository::*;
 use crate::tests::{random_secret, str_secret};
 use anyhow::Result;
+use bdrck::crypto::secret::Secret;
 use bdrck::testing::temp;
 use std::ops::{Deref, DerefMut};
 
@@ -167,6 +171,29 @@ fn test_repository_listing() {
     );
 }
 
+#[test]
+fn test_reserved_paths_are_rejected() {
+    crate::init().unwrap();
+
+    let t = TestRepository::new("foobar").unwrap();
+    for path in [
+        ".previous/foo",
+        "./.previous/foo",
+        ".previous",
+        "keys.mp",
+        "crypto_configuration.mp",
+        ".git/config",
+        "../foo",
+        "foo/../../bar",
+        "/etc/foo",
+    ] {
+        assert!(t.path(path).is_err(), "{}", path);
+    }
+    for path in ["foo", "foo/keys.mp", "foo/.previous/bar", ".previously"] {
+        assert!(t.path(path).is_ok(), "{}", path);
+    }
+}
+
 #[test]
 fn test_remove() {
     crate::init().unwrap();
@@ -200,7 +227,9 @@ fn test_adding_duplicate_key() {
     crate::init().unwrap();
 
     let mut t = TestRepository::new("foobar").unwrap();
-    assert!(t.add_password_key(Some(str_secret("foobar"))).is_err());
+    assert!(t
+        .add_password_key(Some(str_secret("foobar")), None)
+        .is_err());
 }
 
 #[test]
@@ -221,7 +250,7 @@ fn test_adding_key_succeeds() {
             .unwrap();
 
         repository
-            .add_password_key(Some(pwb.try_clone().unwrap()))
+            .add_password_key(Some(pwb.try_clone().unwrap()), None)
             .unwrap();
     }
 
@@ -268,7 +297,7 @@ fn test_removing_key_succeeds() {
             .unwrap();
 
         repository
-            .add_password_key(Some(pwb.try_clone().unwrap()))
+            .add_password_key(Some(pwb.try_clone().unwrap()), None)
             .unwrap();
         repository
             .remove_password_key(Some(pwa.try_clone().unwrap()))
@@ -291,3 +320,395 @@ fn test_removing_key_succeeds() {
         assert_eq!(plaintext.as_slice(), output_plaintext.as_slice());
     }
 }
+
+#[test]
+fn test_key_file_unlocks_repository() {
+    crate::init().unwrap();
+
+    let repository_dir = temp::Dir::new(TEST_REPO_DIR).unwrap();
+    let key_file_dir = temp::Dir::new(TEST_REPO_DIR).unwrap();
+    let key_file = key_file_dir.sub_path("key").unwrap();
+    let path = "test";
+    let plaintext = random_secret(1024);
+
+    std::fs::write(&key_file, unsafe { random_secret(256).as_slice() }).unwrap();
+
+    {
+        let mut repository =
+            Repository::new(repository_dir.path(), true, Some(str_secret("foobar"))).unwrap();
+        let path = repository.path(path).unwrap();
+        repository
+            .write_encrypt(&path, plaintext.try_clone().unwrap(), None)
+            .unwrap();
+
+        repository.add_key_file_key(&key_file, None).unwrap();
+    }
+
+    // The configured key file should be tried before the (wrong) password.
+    let repository =
+        Repository::new(repository_dir.path(), false, Some(str_secret("barbaz"))).unwrap();
+    let path = repository.path(path).unwrap();
+    let output_plaintext = repository.read_decrypt(&path).unwrap();
+    unsafe {
+        assert_eq!(plaintext.as_slice(), output_plaintext.as_slice());
+    }
+}
+
+#[test]
+fn test_recovery_key_unlocks_repository() {
+    crate::init().unwrap();
+
+    let repository_dir = temp::Dir::new(TEST_REPO_DIR).unwrap();
+    let path = "test";
+    let plaintext = random_secret(1024);
+    let old_recovery_secret = random_secret(32);
+    let recovery_secret = random_secret(32);
+
+    {
+        let mut repository =
+            Repository::new(repository_dir.path(), true, Some(str_secret("foobar"))).unwrap();
+        let path = repository.path(path).unwrap();
+        repository
+            .write_encrypt(&path, plaintext.try_clone().unwrap(), None)
+            .unwrap();
+
+        repository
+            .set_recovery_key(old_recovery_secret.try_clone().unwrap())
+            .unwrap();
+        // Generating a new recovery key should replace the old one.
+        repository
+            .set_recovery_key(recovery_secret.try_clone().unwrap())
+            .unwrap();
+    }
+
+    {
+        // The replaced recovery key should no longer work.
+        let repository =
+            Repository::new(repository_dir.path(), false, Some(old_recovery_secret)).unwrap();
+        let path = repository.path(path).unwrap();
+        assert!(repository.read_decrypt(&path).is_err());
+    }
+
+    let repository = Repository::new(repository_dir.path(), false, Some(recovery_secret)).unwrap();
+    let path = repository.path(path).unwrap();
+    let output_plaintext = repository.read_decrypt(&path).unwrap();
+    unsafe {
+        assert_eq!(plaintext.as_slice(), output_plaintext.as_slice());
+    }
+}
+
+#[test]
+fn test_changing_password() {
+    crate::init().unwrap();
+
+    let repository_dir = temp::Dir::new(TEST_REPO_DIR).unwrap();
+    let path = "test";
+    let plaintext = random_secret(1024);
+
+    {
+        let mut repository =
+            Repository::new(repository_dir.path(), true, Some(str_secret("foobar"))).unwrap();
+        let path = repository.path(path).unwrap();
+        repository
+            .write_encrypt(&path, plaintext.try_clone().unwrap(), None)
+            .unwrap();
+
+        repository
+            .change_password(Some(str_secret("foobar")), Some(str_secret("barbaz")))
+            .unwrap();
+        assert_eq!(1, repository.list_keys().unwrap().len());
+    }
+
+    {
+        let repository =
+            Repository::new(repository_dir.path(), false, Some(str_secret("foobar"))).unwrap();
+        let path = repository.path(path).unwrap();
+        assert!(repository.read_decrypt(&path).is_err());
+    }
+
+    let repository =
+        Repository::new(repository_dir.path(), false, Some(str_secret("barbaz"))).unwrap();
+    let path = repository.path(path).unwrap();
+    let output_plaintext = repository.read_decrypt(&path).unwrap();
+    unsafe {
+        assert_eq!(plaintext.as_slice(), output_plaintext.as_slice());
+    }
+}
+
+#[test]
+fn test_changing_password_with_wrong_password_fails() {
+    crate::init().unwrap();
+
+    let mut t = TestRepository::new("foobar").unwrap();
+    assert!(t
+        .change_password(Some(str_secret("barbaz")), Some(str_secret("bazqux")))
+        .is_err());
+    // The failed change shouldn't have left any extra keys behind.
+    assert_eq!(1, t.list_keys().unwrap().len());
+}
+
+#[test]
+fn test_changing_password_to_existing_key_fails() {
+    crate::init().unwrap();
+
+    let mut t = TestRepository::new("foobar").unwrap();
+    t.add_password_key(Some(str_secret("barbaz")), None)
+        .unwrap();
+    assert!(t
+        .change_password(Some(str_secret("foobar")), Some(str_secret("barbaz")))
+        .is_err());
+    assert_eq!(2, t.list_keys().unwrap().len());
+}
+
+#[test]
+fn test_derived_password_survives_password_change() {
+    crate::init().unwrap();
+
+    let mut t = TestRepository::new("foobar").unwrap();
+    let derivation = Derivation {
+        site: "example.com".to_owned(),
+        login: "me".to_owned(),
+        counter: 0,
+        settings: GeneratorSettings {
+            length: 16,
+            charsets: vec![CharacterSet::Letters],
+            exclude: vec![],
+            policy: PasswordPolicy::default(),
+            pattern: None,
+        },
+    };
+    let path = t.path("derived").unwrap();
+    let before = t.write_derivation(&path, &derivation).unwrap();
+    assert_eq!(1, t.list(None).unwrap().len());
+    unsafe {
+        assert_eq!(
+            before.as_slice(),
+            t.derive_password(&derivation).unwrap().as_slice()
+        );
+    }
+    t.change_password(Some(str_secret("foobar")), Some(str_secret("barbaz")))
+        .unwrap();
+    let after = t.derive_password(&derivation).unwrap();
+    unsafe {
+        assert_eq!(before.as_slice(), after.as_slice());
+    }
+
+    // A different repository (with a different master key) derives a
+    // different password.
+    let other = TestRepository::new("foobar").unwrap();
+    let other = other.derive_password(&derivation).unwrap();
+    unsafe {
+        assert_ne!(before.as_slice(), other.as_slice());
+    }
+}
+
+fn count_commits(repository: &Repository) -> usize {
+    let git = git2::Repository::open(repository.workdir().unwrap()).unwrap();
+    let mut revwalk = git.revwalk().unwrap();
+    revwalk.push_head().unwrap();
+    revwalk.count()
+}
+
+fn assert_secret_eq(expected: &str, actual: Secret) {
+    unsafe {
+        assert_eq!(expected.as_bytes(), actual.as_slice());
+    }
+}
+
+#[test]
+fn test_rotate_and_confirm() {
+    crate::init().unwrap();
+
+    let mut t = TestRepository::new("foobar").unwrap();
+    let path = t.path("test").unwrap();
+    t.write_encrypt(&path, str_secret("old"), None).unwrap();
+    assert!(!t.is_rotating(&path).unwrap());
+    assert!(t.read_previous(&path).is_err());
+
+    let commits = count_commits(&t);
+    t.rotate(&path, str_secret("new")).unwrap();
+    assert_eq!(commits + 1, count_commits(&t));
+    assert!(t.is_rotating(&path).unwrap());
+    assert_secret_eq("new", t.read_decrypt(&path).unwrap());
+    assert_secret_eq("old", t.read_previous(&path).unwrap());
+    // The previous value shouldn't show up as a stored password.
+    assert_eq!(1, t.list(None).unwrap().len());
+    // Only one rotation can be in progress at a time.
+    assert!(t.rotate(&path, str_secret("newer")).is_err());
+
+    t.confirm_rotation(&path).unwrap();
+    assert!(!t.is_rotating(&path).unwrap());
+    assert_secret_eq("new", t.read_decrypt(&path).unwrap());
+    assert!(t.read_previous(&path).is_err());
+    assert!(t.confirm_rotation(&path).is_err());
+}
+
+#[test]
+fn test_rotate_and_abort() {
+    crate::init().unwrap();
+
+    let mut t = TestRepository::new("foobar").unwrap();
+    let path = t.path("a/b").unwrap();
+    t.write_encrypt(&path, str_secret("old"), None).unwrap();
+    t.rotate(&path, str_secret("new")).unwrap();
+    t.abort_rotation(&path).unwrap();
+    assert!(!t.is_rotating(&path).unwrap());
+    assert_secret_eq("old", t.read_decrypt(&path).unwrap());
+    assert!(t.abort_rotation(&path).is_err());
+}
+
+#[test]
+fn test_rotate_missing_password_fails() {
+    crate::init().unwrap();
+
+    let mut t = TestRepository::new("foobar").unwrap();
+    let path = t.path("test").unwrap();
+    assert!(t.rotate(&path, str_secret("new")).is_err());
+}
+
+#[test]
+fn test_remove_during_rotation() {
+    crate::init().unwrap();
+
+    let mut t = TestRepository::new("foobar").unwrap();
+    let path = t.path("test").unwrap();
+    t.write_encrypt(&path, str_secret("old"), None).unwrap();
+    t.rotate(&path, str_secret("new")).unwrap();
+    t.remove(&path).unwrap();
+    assert!(!t.is_rotating(&path).unwrap());
+    assert_eq!(0, t.list(None).unwrap().len());
+}
+
+#[test]
+fn test_listing_keys() {
+    crate::init().unwrap();
+
+    let key_file_dir = temp::Dir::new(TEST_REPO_DIR).unwrap();
+    let key_file = key_file_dir.sub_path("key").unwrap();
+    std::fs::write(&key_file, unsafe { random_secret(256).as_slice() }).unwrap();
+
+    let mut t = TestRepository::new("foobar").unwrap();
+    t.add_password_key(Some(str_secret("barbaz")), Some("backup".to_owned()))
+        .unwrap();
+    t.add_key_file_key(&key_file, Some("usb".to_owned()))
+        .unwrap();
+
+    let keys = t.list_keys().unwrap();
+    assert_eq!(3, keys.len());
+    let summary: Vec<(KeyType, Option<&str>)> = keys
+        .iter()
+        .map(|k| (k.key_type, k.label.as_deref()))
+        .collect();
+    assert_eq!(
+        vec![
+            (KeyType::Password, None),
+            (KeyType::Password, Some("backup")),
+            (KeyType::KeyFile, Some("usb")),
+        ],
+        summary
+    );
+    assert!(keys.iter().all(|k| k.added.is_some()));
+}
+
+#[test]
+fn test_adding_duplicate_label_fails() {
+    crate::init().unwrap();
+
+    let mut t = TestRepository::new("foobar").unwrap();
+    t.add_password_key(Some(str_secret("barbaz")), Some("backup".to_owned()))
+        .unwrap();
+    assert!(t
+        .add_password_key(Some(str_secret("bazqux")), Some("backup".to_owned()))
+        .is_err());
+    assert_eq!(2, t.list_keys().unwrap().len());
+}
+
+#[test]
+fn test_removing_key_by_label() {
+    crate::init().unwrap();
+
+    let repository_dir = temp::Dir::new(TEST_REPO_DIR).unwrap();
+    let path = "test";
+
+    {
+        let mut repository =
+            Repository::new(repository_dir.path(), true, Some(str_secret("foobar"))).unwrap();
+        let path = repository.path(path).unwrap();
+        repository
+            .write_encrypt(&path, random_secret(1024), None)
+            .unwrap();
+
+        repository
+            .add_password_key(Some(str_secret("barbaz")), Some("backup".to_owned()))
+            .unwrap();
+        assert!(repository.remove_key_by_label("missing").is_err());
+        repository.remove_key_by_label("backup").unwrap();
+        assert_eq!(1, repository.list_keys().unwrap().len());
+    }
+
+    let repository =
+        Repository::new(repository_dir.path(), false, Some(str_secret("barbaz"))).unwrap();
+    let path = repository.path(path).unwrap();
+    assert!(repository.read_decrypt(&path).is_err());
+}
+
+#[test]
+fn test_removing_key_file_key() {
+    crate::init().unwrap();
+
+    let repository_dir = temp::Dir::new(TEST_REPO_DIR).unwrap();
+    let key_file_dir = temp::Dir::new(TEST_REPO_DIR).unwrap();
+    let key_file = key_file_dir.sub_path("key").unwrap();
+    let path = "test";
+
+    std::fs::write(&key_file, unsafe { random_secret(256).as_slice() }).unwrap();
+
+    {
+        let mut repository =
+            Repository::new(repository_dir.path(), true, Some(str_secret("foobar"))).unwrap();
+        let path = repository.path(path).unwrap();
+        repository
+            .write_encrypt(&path, random_secret(1024), None)
+            .unwrap();
+
+        repository.add_key_file_key(&key_file, None).unwrap();
+        repository.remove_key_file_key(&key_file).unwrap();
+        assert!(repository
+            .get_crypto_configuration()
+            .get_key_files()
+            .is_empty());
+    }
+
+    // With the key file removed, a wrong password should no longer work.
+    let repository =
+        Repository::new(repository_dir.path(), false, Some(str_secret("barbaz"))).unwrap();
+    let path = repository.path(path).unwrap();
+    assert!(repository.read_decrypt(&path).is_err());
+}
+
+#[test]
+fn test_requiring_two_factor_without_piv_device_fails() {
+    crate::init().unwrap();
+
+    let repository_dir = temp::Dir::new(TEST_REPO_DIR).unwrap();
+
+    {
+        let mut repository =
+            Repository::new(repository_dir.path(), true, Some(str_secret("foobar"))).unwrap();
+        // There are no PIV devices available, so this must fail without
+        // changing anything.
+        assert!(repository
+            .set_requires_two_factor(true, Some(str_secret("foobar")))
+            .is_err());
+        assert!(!repository.get_crypto_configuration().requires_two_factor());
+    }
+
+    // The password alone should still unlock the repository.
+    let mut repository =
+        Repository::new(repository_dir.path(), false, Some(str_secret("foobar"))).unwrap();
+    let path = repository.path("test").unwrap();
+    repository
+        .write_encrypt(&path, random_secret(1024), None)
+        .unwrap();
+    assert!(repository.read_decrypt(&path).is_ok());
+}
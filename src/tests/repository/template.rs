@@ -0,0 +1,107 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::repository::template::*;
+use crate::repository::Repository;
+use crate::tests::{secret_string, store_str, str_secret};
+use bdrck::testing::temp;
+
+fn render_str(repository: &Repository, source: &str) -> anyhow::Result<String> {
+    let rendered = Template::parse(source)?.render(repository)?;
+    Ok(secret_string(&rendered))
+}
+
+#[test]
+fn test_parse_placeholders() {
+    crate::init().unwrap();
+
+    let template = Template::parse(
+        "a: {{ pwm \"db/prod\" }}\nb: {{pwm \"x\\\"y\"   field=\"user\"}}\nc: {{ other }}",
+    )
+    .unwrap();
+    assert_eq!(
+        vec![
+            Placeholder {
+                path: "db/prod".to_owned(),
+                field: None,
+            },
+            Placeholder {
+                path: "x\"y".to_owned(),
+                field: Some("user".to_owned()),
+            },
+        ],
+        template.placeholders().cloned().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_parse_errors() {
+    crate::init().unwrap();
+
+    for source in [
+        "{{ pwm db/prod }}",
+        "{{ pwm \"db/prod }}",
+        "{{ pwm \"db/prod\"",
+        "{{ pwm \"db/prod\" user=\"x\" }}",
+        "{{ pwm \"db/prod\" field \"x\" }}",
+        "{{ pwm \"db/prod\" field=\"x\" field=\"y\" }}",
+    ] {
+        assert!(Template::parse(source).is_err(), "{}", source);
+    }
+
+    let error = Template::parse("ok\nok\n{{ pwm \"x\" bad }}")
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("line 3"), "{}", error);
+}
+
+#[test]
+fn test_get_field() {
+    crate::init().unwrap();
+
+    let value = b"hunter2\r\nUser: alice\nurl: https://example.com:8443\n";
+    assert_eq!(Some(&b"hunter2"[..]), get_field(value, "password"));
+    assert_eq!(Some(&b"alice"[..]), get_field(value, "user"));
+    assert_eq!(
+        Some(&b"https://example.com:8443"[..]),
+        get_field(value, "URL")
+    );
+    assert_eq!(None, get_field(value, "host"));
+}
+
+#[test]
+fn test_render() {
+    crate::init().unwrap();
+
+    let repository_dir = temp::Dir::new("pwm-test").unwrap();
+    let mut repository =
+        Repository::new(repository_dir.path(), true, Some(str_secret("foobar"))).unwrap();
+    store_str(&mut repository, "db/prod", "hunter2\nuser: alice");
+    store_str(&mut repository, "api", "abc123");
+
+    assert_eq!(
+        "user={{ user }} name=alice pass=hunter2 key=abc123 again=abc123\n",
+        render_str(
+            &repository,
+            "user={{ user }} name={{ pwm \"db/prod\" field=\"user\" }} \
+             pass={{ pwm \"db/prod\" field=\"password\" }} key={{ pwm \"api\" }} \
+             again={{ pwm \"api\" }}\n"
+        )
+        .unwrap()
+    );
+
+    // Missing paths and fields are errors.
+    assert!(render_str(&repository, "{{ pwm \"db/dev\" }}").is_err());
+    assert!(render_str(&repository, "{{ pwm \"api\" field=\"user\" }}").is_err());
+}
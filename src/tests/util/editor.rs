@@ -0,0 +1,102 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::tests::{secret_string, str_secret};
+use crate::util::editor::edit_secret_with;
+use bdrck::testing::temp;
+use std::fs;
+use std::path::PathBuf;
+
+fn edited_str(original: &str, editor: &str) -> Option<String> {
+    edit_secret_with(&str_secret(original), editor)
+        .unwrap()
+        .map(|s| secret_string(&s))
+}
+
+#[test]
+fn test_edit_unchanged() {
+    crate::init().unwrap();
+
+    assert_eq!(None, edited_str("foo\nbar", "true"));
+}
+
+#[test]
+fn test_edit_changed() {
+    crate::init().unwrap();
+
+    assert_eq!(
+        Some("foo\nbaz\n".to_owned()),
+        edited_str("foo\nbar\n", "sed -i s/bar/baz/")
+    );
+}
+
+#[test]
+fn test_edit_strips_added_newline() {
+    crate::init().unwrap();
+
+    // Saving without changes, but with an added trailing newline, is no edit.
+    assert_eq!(None, edited_str("foobar", "echo >> "));
+    assert_eq!(
+        Some("bazbar".to_owned()),
+        edited_str("foobar", "printf 'bazbar\\n' > ")
+    );
+}
+
+#[test]
+fn test_edit_failed_editor() {
+    crate::init().unwrap();
+
+    assert!(edit_secret_with(&str_secret("foobar"), "exit 1; true").is_err());
+}
+
+#[test]
+fn test_edit_removes_temporary_files() {
+    crate::init().unwrap();
+
+    let dir = temp::Dir::new("pwm-test").unwrap();
+    let record = dir.path().join("record");
+    // Record where the file was, and leave a "swap file" next to it.
+    let editor = format!(
+        "f() {{ echo \"$1\" > '{}'; echo swap > \"$1.swp\"; echo baz >> \"$1\"; }}; f",
+        record.display()
+    );
+    assert_eq!(Some("foo\nbaz\n".to_owned()), edited_str("foo\n", &editor));
+
+    let path = PathBuf::from(fs::read_to_string(&record).unwrap().trim_end());
+    assert!(!path.exists());
+    assert!(!path.parent().unwrap().exists());
+}
+
+#[test]
+fn test_edit_removes_temporary_subdirectories() {
+    crate::init().unwrap();
+
+    let dir = temp::Dir::new("pwm-test").unwrap();
+    let record = dir.path().join("record");
+    let outside = dir.path().join("outside");
+    fs::write(&outside, "keep me").unwrap();
+    // Leave a backup directory next to the file, with a symlink to a file
+    // outside of it, which must not be zeroed.
+    let editor = format!(
+        "f() {{ echo \"$1\" > '{}'; d=\"$(dirname \"$1\")/backup\"; mkdir -p \"$d/nested\"; \
+         cp \"$1\" \"$d/nested/copy\"; ln -s '{}' \"$d/link\"; echo baz >> \"$1\"; }}; f",
+        record.display(),
+        outside.display()
+    );
+    assert_eq!(Some("foo\nbaz\n".to_owned()), edited_str("foo\n", &editor));
+
+    let path = PathBuf::from(fs::read_to_string(&record).unwrap().trim_end());
+    assert!(!path.parent().unwrap().exists());
+    assert_eq!("keep me", fs::read_to_string(&outside).unwrap());
+}
@@ -31,3 +31,16 @@ fn test_encode_round_trip() {
         assert_eq!(test_secret.as_slice(), decoded.as_slice());
     }
 }
+
+#[test]
+fn test_read_to_secret() {
+    crate::init().unwrap();
+
+    for len in [0, 1, 8191, 8192, 8193, 100_000] {
+        let data: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+        let secret = read_to_secret(&mut data.as_slice()).unwrap();
+        unsafe {
+            assert_eq!(data.as_slice(), secret.as_slice());
+        }
+    }
+}
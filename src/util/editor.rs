@@ -0,0 +1,230 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Editing secrets with the user's text editor. The secret is written to a
+//! private temporary file on a tmpfs, so it never reaches persistent storage,
+//! and the file is overwritten and removed afterwards no matter what.
+
+use crate::crypto::pwgen::generate_hex;
+use crate::util::tmpfs::find_tmpfs_dir;
+use anyhow::{bail, Result};
+use bdrck::crypto::secret::Secret;
+use signal_hook::consts::{SIGHUP, SIGINT, SIGQUIT, SIGTERM};
+use signal_hook::SigId;
+use std::env;
+use std::fs::{self, DirBuilder, File, OpenOptions};
+use std::io::{Read, Write};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+static SHELL: &str = "/bin/sh";
+/// Environment variables naming the user's editor, in order of preference.
+static EDITOR_VARIABLES: [&str; 2] = ["VISUAL", "EDITOR"];
+static DEFAULT_EDITOR: &str = "vi";
+
+const TEMP_DIR_MODE: u32 = 0o700;
+const TEMP_FILE_MODE: u32 = 0o600;
+static TEMP_FILE_NAME: &str = "secret";
+
+/// Return the editor command configured in the environment.
+pub fn get_editor() -> String {
+    EDITOR_VARIABLES
+        .iter()
+        .filter_map(|v| env::var(v).ok())
+        .find(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_EDITOR.to_owned())
+}
+
+/// A private directory on a tmpfs, containing a file with the secret being
+/// edited. Editors also tend to leave swap or backup files next to the file
+/// they are editing, so the whole directory is shredded when it is dropped.
+struct TempSecretDir {
+    dir: PathBuf,
+    shredded: bool,
+}
+
+impl TempSecretDir {
+    fn create(secret: &Secret) -> Result<Self> {
+        let dir = find_tmpfs_dir()?.join(format!("pwm-edit-{}", generate_hex(8)));
+        DirBuilder::new().mode(TEMP_DIR_MODE).create(&dir)?;
+        let temp = TempSecretDir {
+            dir,
+            shredded: false,
+        };
+
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(TEMP_FILE_MODE)
+            .open(temp.path())?;
+        file.write_all(unsafe { secret.as_slice() })?;
+        file.sync_all()?;
+        Ok(temp)
+    }
+
+    fn path(&self) -> PathBuf {
+        self.dir.join(TEMP_FILE_NAME)
+    }
+
+    fn read(&self) -> Result<Secret> {
+        let mut file = File::open(self.path())?;
+        let mut secret = Secret::with_len(file.metadata()?.len() as usize)?;
+        file.read_exact(unsafe { secret.as_mut_slice() })?;
+        Ok(secret)
+    }
+
+    /// Overwrite every file in the directory (including any subdirectories
+    /// the editor created) with zeros, and then remove the whole directory.
+    fn shred(&mut self) -> Result<()> {
+        if self.shredded {
+            return Ok(());
+        }
+        overwrite_tree_with_zeros(&self.dir)?;
+        fs::remove_dir_all(&self.dir)?;
+        self.shredded = true;
+        Ok(())
+    }
+}
+
+impl Drop for TempSecretDir {
+    fn drop(&mut self) {
+        if let Err(e) = self.shred() {
+            eprintln!(
+                "Failed to remove temporary files in '{}': {}",
+                self.dir.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Overwrite every regular file under the given directory with zeros. Symbolic
+/// links are not followed, since whatever they point to isn't ours.
+fn overwrite_tree_with_zeros(dir: &Path) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            overwrite_tree_with_zeros(&entry.path())?;
+        } else if file_type.is_file() {
+            overwrite_with_zeros(&entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+fn overwrite_with_zeros(path: &Path) -> Result<()> {
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    let len = file.metadata()?.len() as usize;
+    let zeros = [0_u8; 4096];
+    let mut written = 0;
+    while written < len {
+        let n = (len - written).min(zeros.len());
+        file.write_all(&zeros[..n])?;
+        written += n;
+    }
+    file.sync_all()?;
+    Ok(())
+}
+
+/// While this is alive, signals which would normally kill us are caught
+/// instead, so we get a chance to clean up. Interrupts (^C) go to the editor
+/// as well, which usually handles them itself, so they are ignored entirely;
+/// termination signals are remembered, so the edit can be abandoned.
+struct SignalGuard {
+    ids: Vec<SigId>,
+    terminated: Arc<AtomicBool>,
+}
+
+impl SignalGuard {
+    fn register() -> Result<Self> {
+        let interrupted = Arc::new(AtomicBool::new(false));
+        let terminated = Arc::new(AtomicBool::new(false));
+        let mut ids = Vec::new();
+        for signal in [SIGINT, SIGQUIT] {
+            ids.push(signal_hook::flag::register(signal, interrupted.clone())?);
+        }
+        for signal in [SIGTERM, SIGHUP] {
+            ids.push(signal_hook::flag::register(signal, terminated.clone())?);
+        }
+        Ok(SignalGuard { ids, terminated })
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.terminated.load(Ordering::SeqCst) {
+            bail!("terminated by a signal; discarding changes");
+        }
+        Ok(())
+    }
+}
+
+impl Drop for SignalGuard {
+    fn drop(&mut self) {
+        for id in self.ids.drain(..) {
+            signal_hook::low_level::unregister(id);
+        }
+    }
+}
+
+fn run_editor(editor: &str, path: &Path) -> Result<ExitStatus> {
+    // Like git, run the editor via the shell, so it can include arguments
+    // (e.g. "code --wait").
+    Ok(Command::new(SHELL)
+        .arg("-c")
+        .arg(format!("{} \"$@\"", editor))
+        .arg(editor)
+        .arg(path)
+        .status()?)
+}
+
+/// Let the user edit the given secret with the given editor command. Returns
+/// the edited secret, or None if it was left unchanged. Most editors add a
+/// trailing newline when saving, so if the original secret didn't end with
+/// one, a single trailing newline is removed from the result.
+pub fn edit_secret_with(secret: &Secret, editor: &str) -> Result<Option<Secret>> {
+    let signals = SignalGuard::register()?;
+    let mut temp = TempSecretDir::create(secret)?;
+
+    let status = run_editor(editor, &temp.path())?;
+    signals.check()?;
+    if !status.success() {
+        bail!(
+            "editor '{}' failed ({}); discarding changes",
+            editor,
+            status
+        );
+    }
+    let mut edited = temp.read()?;
+    temp.shred()?;
+    signals.check()?;
+
+    let original = unsafe { secret.as_slice() };
+    let len = edited.len();
+    if !original.ends_with(b"\n") && unsafe { edited.as_slice() }.ends_with(b"\n") {
+        edited.resize(len - 1)?;
+    }
+    Ok(match original == unsafe { edited.as_slice() } {
+        true => None,
+        false => Some(edited),
+    })
+}
+
+/// Let the user edit the given secret with their preferred editor (see
+/// `get_editor`).
+pub fn edit_secret(secret: &Secret) -> Result<Option<Secret>> {
+    edit_secret_with(secret, &get_editor())
+}
@@ -17,7 +17,9 @@ use git2::{
     self, Commit, ErrorClass, ErrorCode, Index, ObjectType, Oid, Repository, Signature, Tree,
 };
 use std::collections::vec_deque::VecDeque;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 static EMPTY_TREE_OID: &'static str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
 
@@ -132,6 +134,36 @@ pub fn get_repository_listing(repository: &Repository, path_filter: &Path) -> Re
     Ok(listing)
 }
 
+/// Walk the given repository's history backwards from HEAD, returning the
+/// time of the most recent commit which modified each path. Paths which were
+/// deleted in HEAD may also be included.
+pub fn get_last_modified_times(repository: &Repository) -> Result<HashMap<PathBuf, SystemTime>> {
+    let mut times: HashMap<PathBuf, SystemTime> = HashMap::new();
+    let head = match get_head_commit(repository)? {
+        None => return Ok(times),
+        Some(head) => head,
+    };
+
+    let mut revwalk = repository.revwalk()?;
+    revwalk.push(head.id())?;
+    for oid in revwalk {
+        let commit = repository.find_commit(oid?)?;
+        let parent_tree = match commit.parents().next() {
+            Some(parent) => parent.tree()?,
+            None => repository.find_tree(Oid::from_str(EMPTY_TREE_OID).unwrap())?,
+        };
+        let time = UNIX_EPOCH + Duration::from_secs(commit.time().seconds().max(0) as u64);
+        let diff = repository.diff_tree_to_tree(Some(&parent_tree), Some(&commit.tree()?), None)?;
+        for delta in diff.deltas() {
+            if let Some(path) = delta.new_file().path() {
+                times.entry(path.to_path_buf()).or_insert(time);
+            }
+        }
+    }
+
+    Ok(times)
+}
+
 fn commit_tree(
     repository: &Repository,
     author: Option<&Signature>,
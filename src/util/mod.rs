@@ -12,32 +12,46 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod editor;
 pub mod git;
 pub mod lazy;
 pub mod secret;
+pub mod tmpfs;
+pub mod tty;
 
+use crate::util::tty::Tty;
 use anyhow::Result;
 use bdrck;
+use bdrck::cli::{AbstractStream, Stream};
 use bdrck::crypto::secret::Secret;
 use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn prompt_for_string<IS: AbstractStream, OS: AbstractStream>(
+    input: IS,
+    output: OS,
+    prompt: &str,
+    confirm: bool,
+) -> Result<String> {
+    Ok(match confirm {
+        false => bdrck::cli::prompt_for_string(input, output, prompt, /*is_sensitive=*/ true)?,
+        true => {
+            bdrck::cli::prompt_for_string_confirm(
+                input, output, prompt, /*is_sensitive=*/ true,
+            )?
+        }
+    })
+}
 
 /// Prompt the user for a password using the given prompt on stderr, and then
 /// read the result on stdin. If confirm is set, we'll prompt for the password
-/// twice, and make sure they copies match.
+/// twice, and make sure they copies match. If stdin isn't a terminal (e.g.
+/// because it is being used to pass data to us), the controlling terminal is
+/// used for both the prompt and the input instead.
 pub fn password_prompt(prompt: &str, confirm: bool) -> Result<Secret> {
-    let sb = match confirm {
-        false => bdrck::cli::prompt_for_string(
-            bdrck::cli::Stream::Stdin,
-            bdrck::cli::Stream::Stderr,
-            prompt,
-            /*is_sensitive=*/ true,
-        )?,
-        true => bdrck::cli::prompt_for_string_confirm(
-            bdrck::cli::Stream::Stdin,
-            bdrck::cli::Stream::Stderr,
-            prompt,
-            /*is_sensitive=*/ true,
-        )?,
+    let sb = match Stream::Stdin.isatty() {
+        true => prompt_for_string(Stream::Stdin, Stream::Stderr, prompt, confirm)?,
+        false => prompt_for_string(Tty::open()?, Tty::open()?, prompt, confirm)?,
     }
     .into_bytes();
 
@@ -92,3 +106,34 @@ pub fn multiline_password_prompt(prompt: &str) -> Result<Secret> {
 
     Ok(secret)
 }
+
+/// Format the given time as a UTC date and time, e.g. "2024-01-31 13:45:00".
+pub fn format_utc_time(time: SystemTime) -> String {
+    let seconds = match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs(),
+        Err(_) => 0,
+    };
+    let (days, seconds) = (seconds / 86400, seconds % 86400);
+
+    // Convert days since the epoch to a civil date. See:
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        (seconds % 3600) / 60,
+        seconds % 60
+    )
+}
@@ -16,13 +16,15 @@ use anyhow::{bail, Result};
 use bdrck::crypto::secret::Secret;
 use data_encoding::BASE64;
 use std::fs::File;
+use std::io::{self, Read};
 use std::path::Path;
 
 const MAX_KEY_FILE_SIZE_BYTES: usize = 1024 * 1024 * 10; // 10 MiB
 
-pub fn load_file<P: AsRef<Path>>(path: P) -> Result<Secret> {
-    use std::io::Read;
+/// The initial buffer size for `read_to_secret`.
+const READ_BUFFER_SIZE: usize = 8192;
 
+pub fn load_file<P: AsRef<Path>>(path: P) -> Result<Secret> {
     let mut file = File::open(path.as_ref())?;
     let len = file.metadata()?.len() as usize;
     if len > MAX_KEY_FILE_SIZE_BYTES {
@@ -40,6 +42,27 @@ pub fn load_file<P: AsRef<Path>>(path: P) -> Result<Secret> {
     Ok(s)
 }
 
+/// Read everything from the given reader into a Secret. Unlike e.g.
+/// `read_to_end`, this never leaves (partial) copies of the data behind in
+/// ordinary memory as the buffer grows.
+pub fn read_to_secret<R: Read>(reader: &mut R) -> Result<Secret> {
+    let mut s = Secret::new();
+    let mut len = 0;
+    loop {
+        if len == s.len() {
+            s.resize(std::cmp::max(2 * len, READ_BUFFER_SIZE))?;
+        }
+        match reader.read(unsafe { &mut s.as_mut_slice()[len..] }) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    s.resize(len)?;
+    Ok(s)
+}
+
 // TODO: Implement a better migration feature and remove this.
 pub fn decode(encoded: &str) -> Result<Secret> {
     let mut s = Secret::with_len(BASE64.decode_len(encoded.len())?)?;
@@ -0,0 +1,92 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{bail, Result};
+use std::env;
+use std::fs::{self, File};
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+static MOUNTS_PATH: &str = "/proc/self/mounts";
+pub static TMPFS_TYPE: &str = "tmpfs";
+
+/// Candidate directories for in-memory temporary files, in order of preference.
+static SHM_DIR: &str = "/dev/shm";
+static RUNTIME_DIR_VARIABLE: &str = "XDG_RUNTIME_DIR";
+
+/// Return the filesystem type of the mount containing the given (absolute,
+/// canonical) path, according to the kernel's mount table.
+pub fn filesystem_type(path: &Path) -> Result<String> {
+    let mounts = fs::read_to_string(MOUNTS_PATH)?;
+    let mut best: Option<(PathBuf, String)> = None;
+    for line in mounts.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 3 {
+            continue;
+        }
+        // Spaces etc. in mount points are octal-escaped.
+        let mount_point = PathBuf::from(fields[1].replace("\\040", " "));
+        let is_better = best
+            .as_ref()
+            .is_none_or(|(p, _)| mount_point.as_os_str().len() >= p.as_os_str().len());
+        if path.starts_with(&mount_point) && is_better {
+            best = Some((mount_point, fields[2].to_owned()));
+        }
+    }
+    match best {
+        Some((_, fs_type)) => Ok(fs_type),
+        None => bail!(
+            "failed to find the filesystem containing '{}'",
+            path.display()
+        ),
+    }
+}
+
+/// Return whether or not the given directory is on a tmpfs, i.e. whether files
+/// written there are kept in memory rather than on persistent storage.
+pub fn is_tmpfs<P: AsRef<Path>>(dir: P) -> Result<bool> {
+    Ok(filesystem_type(&fs::canonicalize(dir)?)? == TMPFS_TYPE)
+}
+
+/// Return whether the given open file is on a tmpfs. Unlike `is_tmpfs`, this
+/// checks the file itself, so it can't be fooled by symlinks, or by its path
+/// changing after the check.
+pub fn is_tmpfs_file(file: &File) -> Result<bool> {
+    let mut stat = MaybeUninit::<libc::statfs>::uninit();
+    if unsafe { libc::fstatfs(file.as_raw_fd(), stat.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    let stat = unsafe { stat.assume_init() };
+    Ok(stat.f_type == libc::TMPFS_MAGIC)
+}
+
+/// Return a directory on a tmpfs which temporary files containing secrets can
+/// be written to: either `/dev/shm`, or `$XDG_RUNTIME_DIR`.
+pub fn find_tmpfs_dir() -> Result<PathBuf> {
+    let candidates = Some(PathBuf::from(SHM_DIR))
+        .into_iter()
+        .chain(env::var_os(RUNTIME_DIR_VARIABLE).map(PathBuf::from));
+    for dir in candidates {
+        if dir.is_dir() && is_tmpfs(&dir).unwrap_or(false) {
+            return Ok(dir);
+        }
+    }
+    bail!(
+        "failed to find a tmpfs to write temporary files to (tried {} and ${})",
+        SHM_DIR,
+        RUNTIME_DIR_VARIABLE
+    )
+}
@@ -0,0 +1,102 @@
+// Copyright 2015 Axel Rasmussen
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Direct access to the process's controlling terminal, for prompting when
+//! stdin is in use for something else (e.g. by `git-credential`).
+
+use bdrck::cli::{AbstractStream, AbstractTerminalAttributes, TerminalFlag};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Result as IoResult, Write};
+use std::mem::MaybeUninit;
+use std::os::unix::io::AsRawFd;
+
+static TTY_PATH: &str = "/dev/tty";
+
+pub struct TtyAttributes {
+    inner: libc::termios,
+}
+
+impl fmt::Debug for TtyAttributes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TtyAttributes")
+            .field("c_lflag", &self.inner.c_lflag)
+            .finish()
+    }
+}
+
+fn flag_value(flag: TerminalFlag) -> libc::tcflag_t {
+    match flag {
+        TerminalFlag::Echo => libc::ECHO,
+        TerminalFlag::EchoNewlines => libc::ECHONL,
+    }
+}
+
+impl AbstractTerminalAttributes for TtyAttributes {
+    fn enable(&mut self, flag: TerminalFlag) {
+        self.inner.c_lflag |= flag_value(flag);
+    }
+
+    fn disable(&mut self, flag: TerminalFlag) {
+        self.inner.c_lflag &= !flag_value(flag);
+    }
+}
+
+/// The controlling terminal, usable as both an input and an output stream
+/// for `bdrck::cli`'s prompt functions.
+pub struct Tty {
+    file: File,
+}
+
+impl Tty {
+    pub fn open() -> IoResult<Self> {
+        Ok(Tty {
+            file: OpenOptions::new().read(true).write(true).open(TTY_PATH)?,
+        })
+    }
+}
+
+impl AbstractStream for Tty {
+    type Attributes = TtyAttributes;
+
+    fn isatty(&self) -> bool {
+        unsafe { libc::isatty(self.file.as_raw_fd()) == 1 }
+    }
+
+    fn get_attributes(&self) -> IoResult<Self::Attributes> {
+        let mut attrs = MaybeUninit::uninit();
+        if unsafe { libc::tcgetattr(self.file.as_raw_fd(), attrs.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(TtyAttributes {
+            inner: unsafe { attrs.assume_init() },
+        })
+    }
+
+    fn set_attributes(&mut self, attributes: &Self::Attributes) -> IoResult<()> {
+        let fd = self.file.as_raw_fd();
+        if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &attributes.inner) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn as_reader(&self) -> Option<Box<dyn Read>> {
+        Some(Box::new(self.file.try_clone().ok()?))
+    }
+
+    fn as_writer(&self) -> Option<Box<dyn Write>> {
+        Some(Box::new(self.file.try_clone().ok()?))
+    }
+}
@@ -13,7 +13,6 @@
 // limitations under the License.
 
 use crate::cli::GenerateArgs;
-use crate::crypto::pwgen;
 use crate::output::{output_secret, InputEncoding, OutputMethod};
 use crate::util::password_prompt;
 use anyhow::{bail, Result};
@@ -22,7 +21,7 @@ use clap::{Args, ValueEnum};
 use qrcode_generator::{self, QrCodeEcc};
 use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 const WPA_MAX_PASSWORD_LENGTH: usize = 63;
 
@@ -33,7 +32,7 @@ const QR_IMAGE_SIZE_PIXELS: usize = 300;
 ///
 /// We define our own enum so we can implement some traits for command line argument parsing.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, ValueEnum)]
-enum ErrorCorrection {
+pub(crate) enum ErrorCorrection {
     /// 7% of data bytes can be restored.
     Low,
     /// 15% of data bytes can be restored.
@@ -51,7 +50,7 @@ impl Default for ErrorCorrection {
 }
 
 impl ErrorCorrection {
-    fn to_upstream(&self) -> QrCodeEcc {
+    pub(crate) fn to_upstream(&self) -> QrCodeEcc {
         match self {
             ErrorCorrection::Low => QrCodeEcc::Low,
             ErrorCorrection::Medium => QrCodeEcc::Medium,
@@ -113,6 +112,65 @@ pub(crate) fn wifiqr_encode(ssid: &str, is_hidden: bool, password: &Secret) -> R
     Ok(s)
 }
 
+/// Render the given data as a QR code, and write it to the given output path.
+/// The image format is autodetected from the path's extension (PNG or SVG).
+pub(crate) fn write_qr_code<P: AsRef<Path>>(
+    data: &[u8],
+    error_correction: ErrorCorrection,
+    output: P,
+    overwrite: bool,
+) -> Result<()> {
+    let output = output.as_ref();
+
+    // Determine the image format first; if the extension is invalid, we want
+    // to return an error before writing anything to disk.
+    let format: ImageFormat = match output.extension().map(|ext| ext.to_str()).flatten() {
+        Some("png") => ImageFormat::Png,
+        Some("svg") => ImageFormat::Svg,
+        None => bail!(
+            "invalid output path '{}', file extension is not valid UTF-8",
+            output.display()
+        ),
+        _ => bail!(
+            "invalid output path '{}', expected PNG or SVG extension",
+            output.display()
+        ),
+    };
+
+    // TODO: Don't do this check, use File::open or File::create instead.
+    // Check if the output already exists, and create its parent directory.
+    if output.exists() {
+        if !overwrite {
+            bail!("refusing to overwrite '{}'", output.display());
+        }
+        if !output.is_file() {
+            bail!("found directory, expected file at '{}'", output.display());
+        }
+    }
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    // Write the QR code to the output path.
+    match format {
+        ImageFormat::Png => qrcode_generator::to_png_to_file(
+            data,
+            error_correction.to_upstream(),
+            QR_IMAGE_SIZE_PIXELS,
+            output,
+        )?,
+        ImageFormat::Svg => qrcode_generator::to_svg_to_file::<&[u8], String, &Path>(
+            data,
+            error_correction.to_upstream(),
+            QR_IMAGE_SIZE_PIXELS,
+            None,
+            output,
+        )?,
+    };
+
+    Ok(())
+}
+
 #[derive(Args)]
 pub(crate) struct WifiqrArgs {
     #[arg(short = 's', long)]
@@ -153,63 +211,16 @@ pub(crate) fn wifiqr_command(args: WifiqrArgs) -> Result<()> {
     let password = if args.prompt {
         password_prompt("Password:", /*confirm=*/ true)?
     } else {
-        let charsets = args.generate.to_charsets();
-        let custom_exclude: Vec<char> = args
-            .generate
-            .custom_exclude
-            .map_or(vec![], |x| x.chars().collect());
-        pwgen::generate_password(args.password_length, &charsets, &custom_exclude)?
-    };
-
-    // Determine the image format first; if the extension is invalid, we want
-    // to return an error before writing anything to disk.
-    let format: ImageFormat = match args.output.extension().map(|ext| ext.to_str()).flatten() {
-        Some("png") => ImageFormat::Png,
-        Some("svg") => ImageFormat::Svg,
-        None => bail!(
-            "invalid output path '{}', file extension is not valid UTF-8",
-            args.output.display()
-        ),
-        _ => bail!(
-            "invalid output path '{}', expected PNG or SVG extension",
-            args.output.display()
-        ),
+        args.generate.generate(args.password_length)?
     };
 
-    // TODO: Don't do this check, use File::open or File::create instead.
-    // Check if the output already exists, and create its parent directory.
-    if args.output.exists() {
-        if !args.overwrite {
-            bail!("refusing to overwrite '{}'", args.output.display());
-        }
-        if !args.output.is_file() {
-            bail!(
-                "found directory, expected file at '{}'",
-                args.output.display()
-            );
-        }
-    }
-    if let Some(parent) = args.output.parent() {
-        fs::create_dir_all(parent)?;
-    }
-
-    // Write the QR code to the output path.
     let encoded = wifiqr_encode(&args.ssid, args.hidden, &password)?;
-    match format {
-        ImageFormat::Png => qrcode_generator::to_png_to_file(
-            unsafe { encoded.as_slice() },
-            args.error_correction.to_upstream(),
-            QR_IMAGE_SIZE_PIXELS,
-            args.output,
-        )?,
-        ImageFormat::Svg => qrcode_generator::to_svg_to_file::<&[u8], String, PathBuf>(
-            unsafe { encoded.as_slice() },
-            args.error_correction.to_upstream(),
-            QR_IMAGE_SIZE_PIXELS,
-            None,
-            args.output,
-        )?,
-    };
+    write_qr_code(
+        unsafe { encoded.as_slice() },
+        args.error_correction,
+        &args.output,
+        args.overwrite,
+    )?;
 
     if !args.prompt {
         // Also print out the secret as plain text.